@@ -0,0 +1,6 @@
+//! Caller authentication utilities.
+//!
+//! This module provides extractors that resolve who is calling the proxy so
+//! that per-role policy can be enforced by the handlers.
+
+pub mod identity;
@@ -0,0 +1,125 @@
+//! Caller identity extraction.
+//!
+//! The proxy is expected to run behind an authenticating gateway which
+//! forwards the authenticated user and their roles as trusted headers.
+
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Response},
+};
+
+/// Header carrying the authenticated user name.
+pub const USER_HEADER: &str = "x-proxy-user";
+
+/// Header carrying the comma separated roles of the authenticated user.
+pub const ROLES_HEADER: &str = "x-proxy-roles";
+
+/// The authenticated caller of a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// The user name forwarded by the gateway
+    pub user: String,
+    /// The roles assigned to the user, used to look up policy
+    pub roles: Vec<String>,
+}
+
+impl Identity {
+    /// Parses an identity from the raw user and roles header values.
+    ///
+    /// Returns `None` if the user is missing or blank. Empty role entries
+    /// are ignored.
+    pub fn from_headers(user: Option<&str>, roles: Option<&str>) -> Option<Self> {
+        let user = user.map(str::trim).filter(|u| !u.is_empty())?;
+        let roles = roles
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Some(Self {
+            user: user.to_string(),
+            roles,
+        })
+    }
+}
+
+/// Rejection returned when a request carries no usable identity.
+#[derive(Debug)]
+pub struct MissingIdentity;
+
+impl IntoResponse for MissingIdentity {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected request without a '{}' header", USER_HEADER);
+
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({
+                "error": {
+                    "type": "security_exception",
+                    "reason": "missing authentication credentials",
+                },
+                "status": StatusCode::UNAUTHORIZED.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+impl<S> FromRequestParts<S> for Identity
+where
+    S: Send + Sync,
+{
+    type Rejection = MissingIdentity;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| parts.headers.get(name).and_then(|v| v.to_str().ok());
+
+        Identity::from_headers(header(USER_HEADER), header(ROLES_HEADER)).ok_or(MissingIdentity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_from_headers() {
+        let identity = Identity::from_headers(Some("alice"), Some("reader, analyst,,")).unwrap();
+        assert_eq!(identity.user, "alice");
+        assert_eq!(identity.roles, vec!["reader", "analyst"]);
+    }
+
+    #[test]
+    fn test_identity_without_roles() {
+        let identity = Identity::from_headers(Some("alice"), None).unwrap();
+        assert!(identity.roles.is_empty());
+    }
+
+    #[test]
+    fn test_identity_requires_user() {
+        assert!(Identity::from_headers(None, Some("reader")).is_none());
+        assert!(Identity::from_headers(Some("  "), Some("reader")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_identity_extractor_rejects_missing_user() {
+        use axum::http::Request;
+
+        let (mut parts, _) = Request::builder()
+            .uri("/movies/_search")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let result = Identity::from_request_parts(&mut parts, &()).await;
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().into_response().status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
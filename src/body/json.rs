@@ -0,0 +1,101 @@
+//! Optional JSON request body handling.
+//!
+//! Search-like APIs of OpenSearch accept GET requests without a body as
+//! well as POST requests with a JSON object body.
+
+use axum::{
+    Json,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use serde_json::{Map, Value};
+
+/// Parses an optional JSON object body. An empty body is treated as an
+/// empty object.
+///
+/// # Arguments
+///
+/// * `bytes` - The raw bytes of the request body
+///
+/// # Returns
+///
+/// * `Ok(Value)` - The parsed JSON object
+/// * `Err(String)` - Description of the parsing failure
+///
+pub fn parse_optional_json(bytes: &[u8]) -> Result<Value, String> {
+    if bytes.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Value::Object(Map::new()));
+    }
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(value @ Value::Object(_)) => Ok(value),
+        Ok(_) => Err("request body must be a JSON object".to_string()),
+        Err(e) => Err(format!("Invalid JSON: {}", e)),
+    }
+}
+
+/// A custom extractor for optional JSON object bodies.
+///
+/// Unlike `Json`, this extractor does not require a content type and
+/// accepts requests without a body, which are made available as an empty
+/// object.
+#[derive(Debug, Clone)]
+pub struct OptionalJsonBody(pub Value);
+
+/// Error response for invalid JSON bodies.
+#[derive(Debug)]
+pub struct JsonBodyError(pub String);
+
+impl IntoResponse for JsonBodyError {
+    fn into_response(self) -> Response {
+        tracing::warn!("JSON body validation failed: {}", self.0);
+
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": {
+                    "type": "parse_exception",
+                    "reason": self.0,
+                },
+                "status": StatusCode::BAD_REQUEST.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+impl<S> FromRequest<S> for OptionalJsonBody
+where
+    S: Send + Sync,
+{
+    type Rejection = JsonBodyError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|_| JsonBodyError("Failed to read request body".to_string()))?;
+
+        parse_optional_json(&bytes)
+            .map(OptionalJsonBody)
+            .map_err(JsonBodyError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_optional_json() {
+        assert_eq!(parse_optional_json(b""), Ok(json!({})));
+        assert_eq!(parse_optional_json(b" \n"), Ok(json!({})));
+        assert_eq!(
+            parse_optional_json(br#"{"size": 1}"#),
+            Ok(json!({ "size": 1 }))
+        );
+        assert!(parse_optional_json(b"[1]").is_err());
+        assert!(parse_optional_json(b"{").is_err());
+    }
+}
@@ -3,4 +3,5 @@
 //! This module provides extractors and validators for different body formats
 //! used by OpenSearch APIs.
 
+pub mod json;
 pub mod ndjson;
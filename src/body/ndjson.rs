@@ -10,6 +10,7 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use bytes::Bytes;
+use serde_json::Value;
 use std::fmt;
 
 /// Error type for NDJSON validation failures.
@@ -45,12 +46,30 @@ impl std::error::Error for NdjsonValidationError {}
 /// * `Err(NdjsonValidationError)` - Details about the validation failure
 ///
 pub fn validate_ndjson_lines(bytes: &[u8]) -> Result<usize, NdjsonValidationError> {
+    parse_ndjson_lines(bytes).map(|lines| lines.len())
+}
+
+/// Parses NDJSON from raw bytes into one JSON value per line.
+///
+/// Trailing empty lines are accepted, empty lines in the middle of the
+/// content are rejected.
+///
+/// # Arguments
+///
+/// * `bytes` - The raw bytes to parse as NDJSON
+///
+/// # Returns
+///
+/// * `Ok(Vec<Value>)` - The parsed JSON lines in order
+/// * `Err(NdjsonValidationError)` - Details about the validation failure
+///
+pub fn parse_ndjson_lines(bytes: &[u8]) -> Result<Vec<Value>, NdjsonValidationError> {
     if bytes.is_empty() {
-        return Ok(0);
+        return Ok(Vec::new());
     }
 
     let lines: Vec<&[u8]> = bytes.split(|b| *b == b'\n').collect();
-    let mut valid_line_count = 0;
+    let mut values = Vec::with_capacity(lines.len());
 
     for (index, line) in lines.iter().enumerate() {
         let line_number = index + 1; // 1-based line numbering for user-facing errors
@@ -73,17 +92,30 @@ pub fn validate_ndjson_lines(bytes: &[u8]) -> Result<usize, NdjsonValidationErro
         }
 
         // Validate that the line is valid JSON
-        if let Err(e) = serde_json::from_slice::<serde_json::Value>(line) {
-            return Err(NdjsonValidationError {
-                line_number,
-                message: format!("Invalid JSON: {}", e),
-            });
+        match serde_json::from_slice::<Value>(line) {
+            Ok(value) => values.push(value),
+            Err(e) => {
+                return Err(NdjsonValidationError {
+                    line_number,
+                    message: format!("Invalid JSON: {}", e),
+                });
+            }
         }
-
-        valid_line_count += 1;
     }
 
-    Ok(valid_line_count)
+    Ok(values)
+}
+
+/// Serializes JSON values back into NDJSON, one value per line with a
+/// trailing newline as required by OpenSearch.
+pub fn to_ndjson_bytes(lines: &[Value]) -> Bytes {
+    let mut buffer = Vec::new();
+    for line in lines {
+        // Serializing a `Value` into a `Vec` cannot fail
+        serde_json::to_writer(&mut buffer, line).expect("JSON value must serialize");
+        buffer.push(b'\n');
+    }
+    Bytes::from(buffer)
 }
 
 /// A custom extractor for NDJSON request bodies.
@@ -230,6 +262,15 @@ mod tests {
         assert_eq!(result.unwrap(), 3);
     }
 
+    #[test]
+    fn test_parse_and_serialize_roundtrip() {
+        let input = b"{\"index\":\"test\"}\n{\"query\":{\"match_all\":{}}}\n";
+        let lines = parse_ndjson_lines(input).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["index"], "test");
+        assert_eq!(to_ndjson_bytes(&lines).as_ref(), input);
+    }
+
     #[tokio::test]
     async fn test_ndjson_body_extractor_valid() {
         use axum::body::Body;
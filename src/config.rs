@@ -1,12 +1,51 @@
+use std::fmt;
+
 use serde::Deserialize;
 
 /// Application configuration loaded from environment variables.
 ///
 /// # Fields
 /// - `opensearch_url` - OpenSearch instance URL (OPENSEARCH_URL)
-#[derive(Debug, Clone, Deserialize)]
+/// - `policy_file` - Optional path to the JSON access policy (POLICY_FILE)
+/// - `masking_key` - Optional secret key for hashed field masking (MASKING_KEY)
+/// - `queries_file` - Optional path to the JSON named query catalogue (QUERIES_FILE)
+/// - `dashboards_service_user` - Optional user of OpenSearch Dashboards, enabling the
+///   Dashboards mode (DASHBOARDS_SERVICE_USER)
+/// - `dashboards_user_header` - Optional header in which Dashboards forwards the end
+///   user (DASHBOARDS_USER_HEADER)
+/// - `dashboards_roles_header` - Optional header in which Dashboards forwards the roles
+///   of the end user (DASHBOARDS_ROLES_HEADER)
+/// - `dashboards_index` - Optional system index of Dashboards, `.kibana` by default
+///   (DASHBOARDS_INDEX)
+#[derive(Clone, Deserialize)]
 pub struct Config {
-    pub opensearch_url: String, // OPENSEARCH_URL
+    pub opensearch_url: String,                  // OPENSEARCH_URL
+    pub policy_file: Option<String>,             // POLICY_FILE
+    pub masking_key: Option<String>,             // MASKING_KEY
+    pub queries_file: Option<String>,            // QUERIES_FILE
+    pub dashboards_service_user: Option<String>, // DASHBOARDS_SERVICE_USER
+    pub dashboards_user_header: Option<String>,  // DASHBOARDS_USER_HEADER
+    pub dashboards_roles_header: Option<String>, // DASHBOARDS_ROLES_HEADER
+    pub dashboards_index: Option<String>,        // DASHBOARDS_INDEX
+}
+
+impl fmt::Debug for Config {
+    // Secrets must never end up in the logs
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("opensearch_url", &self.opensearch_url)
+            .field("policy_file", &self.policy_file)
+            .field(
+                "masking_key",
+                &self.masking_key.as_ref().map(|_| "<redacted>"),
+            )
+            .field("queries_file", &self.queries_file)
+            .field("dashboards_service_user", &self.dashboards_service_user)
+            .field("dashboards_user_header", &self.dashboards_user_header)
+            .field("dashboards_roles_header", &self.dashboards_roles_header)
+            .field("dashboards_index", &self.dashboards_index)
+            .finish()
+    }
 }
 
 impl Config {
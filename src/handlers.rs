@@ -1,3 +1,27 @@
+pub mod async_search;
+pub mod bulk;
+pub mod cluster_info;
+pub mod dashboards;
+pub mod debug_output;
+pub mod discovery;
+pub mod documents;
+pub mod error_sanitizer;
+pub mod field_security;
+pub mod index_authorization;
+pub mod masking;
+pub mod mustache;
+pub mod named_queries;
 pub mod opensearch;
+pub mod owner_fields;
+pub mod point_in_time;
+pub mod pseudonymization;
 pub mod public;
+pub mod query_inspector;
+pub mod scroll;
+pub mod search_params;
+pub mod search_response;
+pub mod search_template;
 pub mod security_filter;
+pub mod sql;
+pub mod tasks;
+pub mod terms_enum;
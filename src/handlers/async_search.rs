@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+use crate::auth::identity::Identity;
+use crate::handlers::search_params::{SearchParams, parse_time};
+use crate::repositories::context::SearchContextRepository;
+
+/// Keep-alive the asynchronous search plugin applies when none is given.
+/// It is set explicitly, so that it is capped by the `max_keep_alive` of
+/// the caller's roles.
+const DEFAULT_KEEP_ALIVE: &str = "12h";
+
+/// Reasons an asynchronous search request is rejected before it reaches
+/// OpenSearch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsyncSearchRejection {
+    /// A submission does not name an index
+    MissingIndex,
+    /// The search is unknown, expired or was submitted by another identity
+    ContextMissing(String),
+}
+
+impl IntoResponse for AsyncSearchRejection {
+    fn into_response(self) -> Response {
+        let (status, error_type, reason) = match self {
+            Self::MissingIndex => (
+                StatusCode::BAD_REQUEST,
+                "action_request_validation_exception",
+                "Validation Failed: 1: asynchronous searches require an [index] parameter;"
+                    .to_string(),
+            ),
+            Self::ContextMissing(id) => {
+                tracing::warn!("Rejected access to unknown asynchronous search '{}'", id);
+                (
+                    StatusCode::NOT_FOUND,
+                    "resource_not_found_exception",
+                    format!("asynchronous search [{}] not found", id),
+                )
+            }
+        };
+
+        (
+            status,
+            Json(json!({
+                "error": { "type": error_type, "reason": reason },
+                "status": status.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// An asynchronous search submitted through the proxy.
+#[derive(Debug, Clone)]
+pub struct AsyncSearchContext {
+    /// The prepared search body, needed to transform the results
+    pub request: Value,
+}
+
+/// A service binding asynchronous searches to the identity that submitted
+/// them.
+///
+/// The results of an asynchronous search are fetched by id alone, so every
+/// search submitted through the proxy is remembered with its creator and
+/// its prepared body until its keep-alive ends. Only that identity may
+/// fetch or delete it, and its results are transformed like those of a
+/// synchronous search.
+#[derive(Clone)]
+pub struct AsyncSearchService {
+    searches: SearchContextRepository<AsyncSearchContext>,
+}
+
+impl AsyncSearchService {
+    pub fn new(searches: SearchContextRepository<AsyncSearchContext>) -> Self {
+        Self { searches }
+    }
+
+    /// Removes the `index` parameter of a submission and sets the default
+    /// keep-alive if none is given.
+    pub fn take_index(&self, params: &mut SearchParams) -> Result<String, AsyncSearchRejection> {
+        let index = params
+            .extract_if(.., |(name, _)| name == "index")
+            .last()
+            .map(|(_, index)| index)
+            .filter(|index| !index.is_empty())
+            .ok_or(AsyncSearchRejection::MissingIndex)?;
+        if !params.iter().any(|(name, _)| name == "keep_alive") {
+            params.push(("keep_alive".to_string(), DEFAULT_KEEP_ALIVE.to_string()));
+        }
+        Ok(index)
+    }
+
+    /// Returns the keep-alive given in validated parameters.
+    pub fn keep_alive(&self, params: &SearchParams) -> Option<Duration> {
+        params
+            .iter()
+            .find(|(name, _)| name == "keep_alive")
+            .and_then(|(_, value)| parse_time(value))
+    }
+
+    /// Remembers the search submitted by a response, if any.
+    pub fn register(
+        &self,
+        identity: &Identity,
+        response: &Value,
+        keep_alive: Duration,
+        context: AsyncSearchContext,
+    ) {
+        if let Some(id) = response.get("id").and_then(Value::as_str) {
+            self.searches.insert(id, identity, keep_alive, context);
+        }
+    }
+
+    /// Returns the context of a search submitted by the given identity.
+    pub fn context(
+        &self,
+        identity: &Identity,
+        id: &str,
+    ) -> Result<AsyncSearchContext, AsyncSearchRejection> {
+        self.searches
+            .get(id, identity)
+            .ok_or_else(|| AsyncSearchRejection::ContextMissing(id.to_string()))
+    }
+
+    /// Extends the lifetime of a search whose keep-alive was updated.
+    pub fn extend(&self, id: &str, keep_alive: Duration) {
+        self.searches.extend(id, keep_alive);
+    }
+
+    /// Forgets a deleted search.
+    pub fn forget(&self, id: &str) {
+        self.searches.remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(user: &str) -> Identity {
+        Identity {
+            user: user.to_string(),
+            roles: vec!["analyst".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_take_index() {
+        let service = AsyncSearchService::new(SearchContextRepository::new());
+
+        let mut params = vec![
+            ("index".to_string(), "movies".to_string()),
+            ("wait_for_completion_timeout".to_string(), "1s".to_string()),
+        ];
+        assert_eq!(service.take_index(&mut params), Ok("movies".to_string()));
+        assert_eq!(
+            params,
+            vec![
+                ("wait_for_completion_timeout".to_string(), "1s".to_string()),
+                ("keep_alive".to_string(), "12h".to_string()),
+            ]
+        );
+        assert_eq!(
+            service.keep_alive(&params),
+            Some(Duration::from_secs(12 * 60 * 60))
+        );
+
+        assert_eq!(
+            service.take_index(&mut vec![]),
+            Err(AsyncSearchRejection::MissingIndex)
+        );
+    }
+
+    #[test]
+    fn test_search_is_bound_to_creator() {
+        let service = AsyncSearchService::new(SearchContextRepository::new());
+        let context = AsyncSearchContext {
+            request: json!({ "size": 0 }),
+        };
+        service.register(
+            &identity("alice"),
+            &json!({ "id": "FklfVlU4", "state": "RUNNING" }),
+            Duration::from_secs(60),
+            context,
+        );
+
+        assert_eq!(
+            service
+                .context(&identity("alice"), "FklfVlU4")
+                .unwrap()
+                .request,
+            json!({ "size": 0 })
+        );
+        assert_eq!(
+            service.context(&identity("bob"), "FklfVlU4").unwrap_err(),
+            AsyncSearchRejection::ContextMissing("FklfVlU4".to_string())
+        );
+
+        service.forget("FklfVlU4");
+        assert!(service.context(&identity("alice"), "FklfVlU4").is_err());
+    }
+}
@@ -0,0 +1,771 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+use crate::handlers::{
+    field_security::FieldPolicy, index_authorization::ForbiddenIndex, masking::MaskingPolicy,
+    owner_fields::OwnerFieldViolation, pseudonymization::PseudonymPolicy,
+    search_params::SearchParams,
+};
+
+/// Metadata accepted in bulk action lines besides `_index` and `_id`.
+const ACTION_METADATA: &[&str] = &[
+    "if_primary_term",
+    "if_seq_no",
+    "require_alias",
+    "retry_on_conflict",
+    "routing",
+    "version",
+    "version_type",
+];
+
+/// Query string parameters of single document writes that are metadata
+/// of the bulk item the write is sent as.
+const ITEM_PARAMS: &[&str] = &[
+    "if_primary_term",
+    "if_seq_no",
+    "retry_on_conflict",
+    "version",
+    "version_type",
+];
+
+/// Most ids looked up by a single visibility search, the default result
+/// window of an index.
+const MAX_VISIBILITY_IDS: usize = 10_000;
+
+/// Keys accepted in the body of a bulk update. `_source` is missing, as
+/// the updated document it returns would bypass field level security.
+const UPDATE_KEYS: &[&str] = &[
+    "detect_noop",
+    "doc",
+    "doc_as_upsert",
+    "script",
+    "scripted_upsert",
+    "upsert",
+];
+
+/// Keys of a bulk update running a script on the document.
+const SCRIPT_KEYS: &[&str] = &["script", "scripted_upsert"];
+
+/// Error returned for bulk bodies that cannot be split into actions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidBulkRequest(pub String);
+
+impl IntoResponse for InvalidBulkRequest {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected invalid bulk request: {}", self.0);
+
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": { "type": "illegal_argument_exception", "reason": self.0 },
+                "status": StatusCode::BAD_REQUEST.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// The error of a single bulk item, reported within the bulk response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkItemError {
+    pub status: StatusCode,
+    pub error_type: &'static str,
+    pub reason: String,
+}
+
+impl BulkItemError {
+    fn invalid(reason: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error_type: "action_request_validation_exception",
+            reason: reason.into(),
+        }
+    }
+
+    fn forbidden(reason: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            error_type: "security_exception",
+            reason: reason.into(),
+        }
+    }
+
+    /// The error of an update of a document the caller may not see.
+    pub fn document_missing(id: &str) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            error_type: "document_missing_exception",
+            reason: format!("[{}]: document missing", id),
+        }
+    }
+}
+
+impl From<ForbiddenIndex> for BulkItemError {
+    fn from(error: ForbiddenIndex) -> Self {
+        Self::forbidden(format!("no permissions for index [{}]", error.0))
+    }
+}
+
+impl From<OwnerFieldViolation> for BulkItemError {
+    fn from(error: OwnerFieldViolation) -> Self {
+        Self::forbidden(error.0)
+    }
+}
+
+/// The operation of a bulk item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkAction {
+    Index,
+    Create,
+    Update,
+    Delete,
+}
+
+impl BulkAction {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "index" => Some(Self::Index),
+            "create" => Some(Self::Create),
+            "update" => Some(Self::Update),
+            "delete" => Some(Self::Delete),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Index => "index",
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+/// A single action of a bulk request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BulkItem {
+    /// The action sent to OpenSearch
+    pub action: BulkAction,
+    /// The action the item is reported as, which differs if the proxy
+    /// rewrote the action
+    pub requested: BulkAction,
+    /// The target index, empty if neither the item nor the path names one
+    pub index: String,
+    pub id: Option<String>,
+    /// Further metadata of the action line
+    metadata: Map<String, Value>,
+    /// The document or update body, absent for deletes
+    pub source: Option<Value>,
+}
+
+/// A service splitting bulk requests into items and assembling their
+/// responses.
+///
+/// Every item is checked on its own before the request is forwarded.
+/// Rejected items are not sent to OpenSearch, but reported at their
+/// position in the response with an error, like items OpenSearch rejects.
+#[derive(Clone)]
+pub struct BulkService;
+
+impl BulkService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Splits the lines of a bulk body into items. Items without an index
+    /// use the index of the path.
+    pub fn parse(
+        &self,
+        index: Option<&str>,
+        lines: Vec<Value>,
+    ) -> Result<Vec<BulkItem>, InvalidBulkRequest> {
+        let mut items = Vec::new();
+        let mut lines = lines.into_iter().enumerate();
+        while let Some((position, line)) = lines.next() {
+            let invalid = |reason: &str| {
+                InvalidBulkRequest(format!(
+                    "Malformed action/metadata line [{}], {}",
+                    position + 1,
+                    reason
+                ))
+            };
+            let Value::Object(action_line) = line else {
+                return Err(invalid("expected an object"));
+            };
+            let mut entries = action_line.into_iter();
+            let (action, metadata) = match (entries.next(), entries.next()) {
+                (Some((name, Value::Object(metadata))), None) => {
+                    let action = BulkAction::parse(&name)
+                        .ok_or_else(|| invalid(&format!("unknown action [{}]", name)))?;
+                    (action, metadata)
+                }
+                _ => return Err(invalid("expected a single action with an object")),
+            };
+            let source = match action {
+                BulkAction::Delete => None,
+                _ => match lines.next() {
+                    Some((_, source @ Value::Object(_))) => Some(source),
+                    _ => return Err(invalid("expected a source object on the next line")),
+                },
+            };
+            items.push(self.item(action, metadata, index, source));
+        }
+
+        if items.is_empty() {
+            return Err(InvalidBulkRequest(
+                "Validation Failed: 1: no requests added;".to_string(),
+            ));
+        }
+        Ok(items)
+    }
+
+    fn item(
+        &self,
+        action: BulkAction,
+        mut metadata: Map<String, Value>,
+        index: Option<&str>,
+        source: Option<Value>,
+    ) -> BulkItem {
+        let item_index = match metadata.remove("_index") {
+            Some(Value::String(index)) => index,
+            _ => index.unwrap_or_default().to_string(),
+        };
+        let id = match metadata.remove("_id") {
+            Some(Value::String(id)) => Some(id),
+            Some(Value::Number(id)) => Some(id.to_string()),
+            _ => None,
+        };
+
+        BulkItem {
+            action,
+            requested: action,
+            index: item_index,
+            id,
+            metadata,
+            source,
+        }
+    }
+
+    /// Validates the metadata and body of an item.
+    pub fn validate(&self, item: &BulkItem) -> Result<(), BulkItemError> {
+        if item.index.is_empty() {
+            return Err(BulkItemError::invalid(
+                "Validation Failed: 1: index is missing;",
+            ));
+        }
+        if item.id.is_none() && matches!(item.action, BulkAction::Update | BulkAction::Delete) {
+            return Err(BulkItemError::invalid(
+                "Validation Failed: 1: id is missing;",
+            ));
+        }
+        if let Some(key) = item
+            .metadata
+            .keys()
+            .find(|key| !ACTION_METADATA.contains(&key.as_str()))
+        {
+            return Err(BulkItemError::invalid(format!(
+                "Action/metadata line contains an unsupported parameter [{}]",
+                key
+            )));
+        }
+        if item.action == BulkAction::Update
+            && let Some(key) = item
+                .source
+                .iter()
+                .filter_map(Value::as_object)
+                .flat_map(Map::keys)
+                .find(|key| !UPDATE_KEYS.contains(&key.as_str()))
+        {
+            return Err(BulkItemError::invalid(format!(
+                "Update request contains an unsupported field [{}]",
+                key
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rejects scripted updates of callers restricted by field level
+    /// security, masking or pseudonymization, since a script could copy a
+    /// hidden value into a readable field.
+    pub fn check_script(
+        &self,
+        item: &BulkItem,
+        fields: &FieldPolicy,
+        masking: &MaskingPolicy,
+        pseudonyms: &PseudonymPolicy,
+    ) -> Result<(), BulkItemError> {
+        if fields.is_unrestricted() && masking.is_empty() && pseudonyms.is_empty() {
+            return Ok(());
+        }
+        let scripted = item.action == BulkAction::Update
+            && item
+                .source
+                .as_ref()
+                .is_some_and(|body| SCRIPT_KEYS.iter().any(|key| body.get(key).is_some()));
+        if scripted {
+            return Err(BulkItemError::forbidden(
+                "scripted updates are not permitted with field restrictions",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns whether an item may only target a document the caller can
+    /// see. Index actions with an id would otherwise overwrite documents
+    /// of others.
+    pub fn requires_visibility(&self, item: &BulkItem) -> bool {
+        match item.action {
+            BulkAction::Update | BulkAction::Delete => true,
+            BulkAction::Index => item.id.is_some(),
+            BulkAction::Create => false,
+        }
+    }
+
+    /// Builds the searches returning which of the given ids are visible.
+    /// The security filter must be applied to each of them.
+    pub fn visibility_searches(&self, ids: &[&str]) -> Vec<Value> {
+        ids.chunks(MAX_VISIBILITY_IDS)
+            .map(|ids| {
+                json!({
+                    "query": { "ids": { "values": ids } },
+                    "size": ids.len(),
+                    "_source": false,
+                })
+            })
+            .collect()
+    }
+
+    /// Handles an item whose document the caller cannot see, as it does
+    /// not exist or lies outside the security filter. Deletes report the
+    /// document as not found and updates as missing. Index actions and
+    /// upserts are turned into creates, which fail if a document of
+    /// someone else exists. Returns the answer of the proxy, or `None` if
+    /// the rewritten item is to be sent.
+    pub fn invisible(&self, item: &mut BulkItem) -> Option<Value> {
+        match item.action {
+            BulkAction::Delete => return Some(self.not_found(item)),
+            BulkAction::Index | BulkAction::Create => {
+                item.action = BulkAction::Create;
+                return None;
+            }
+            BulkAction::Update => {}
+        }
+
+        let body = item.source.as_ref().and_then(Value::as_object);
+        let flag = |name: &str| {
+            body.and_then(|body| body.get(name))
+                .and_then(Value::as_bool)
+        };
+        let upsert = match (flag("scripted_upsert"), flag("doc_as_upsert")) {
+            (Some(true), _) => None,
+            (_, Some(true)) => body.and_then(|body| body.get("doc")),
+            _ => body.and_then(|body| body.get("upsert")),
+        };
+        match upsert.cloned() {
+            Some(document) => {
+                item.action = BulkAction::Create;
+                item.metadata.remove("retry_on_conflict");
+                item.source = Some(document);
+                None
+            }
+            None => {
+                let id = item.id.clone().unwrap_or_default();
+                Some(self.rejected(item, BulkItemError::document_missing(&id)))
+            }
+        }
+    }
+
+    /// Serializes items into bulk lines.
+    pub fn to_lines<'a>(&self, items: impl Iterator<Item = &'a BulkItem>) -> Vec<Value> {
+        let mut lines = Vec::new();
+        for item in items {
+            let mut metadata = item.metadata.clone();
+            metadata.insert("_index".to_string(), Value::String(item.index.clone()));
+            if let Some(id) = &item.id {
+                metadata.insert("_id".to_string(), Value::String(id.clone()));
+            }
+            lines.push(json!({ item.action.name(): metadata }));
+            if let Some(source) = &item.source {
+                lines.push(source.clone());
+            }
+        }
+        lines
+    }
+
+    /// The response item of a rejected item.
+    pub fn rejected(&self, item: &BulkItem, error: BulkItemError) -> Value {
+        tracing::warn!(
+            "Rejected bulk {} on '{}': {}",
+            item.requested.name(),
+            item.index,
+            error.reason
+        );
+        json!({
+            item.requested.name(): {
+                "_index": item.index,
+                "_id": item.id,
+                "status": error.status.as_u16(),
+                "error": { "type": error.error_type, "reason": error.reason },
+            }
+        })
+    }
+
+    /// The response item of a delete of a document the caller may not see.
+    pub fn not_found(&self, item: &BulkItem) -> Value {
+        json!({
+            item.requested.name(): {
+                "_index": item.index,
+                "_id": item.id,
+                "result": "not_found",
+                "status": StatusCode::NOT_FOUND.as_u16(),
+            }
+        })
+    }
+
+    /// Builds the item of a single document write, which is checked and
+    /// sent like a bulk of one item. Item parameters move from the query
+    /// string into the metadata of the item, and `op_type=create` turns an
+    /// index action into a create. Returns the item and the remaining
+    /// parameters.
+    pub fn single_item(
+        &self,
+        action: BulkAction,
+        index: &str,
+        id: Option<String>,
+        params: SearchParams,
+        source: Option<Value>,
+    ) -> (BulkItem, SearchParams) {
+        let mut action = action;
+        let mut metadata = Map::new();
+        let mut remaining = SearchParams::with_capacity(params.len());
+        for (name, value) in params {
+            if name == "op_type" {
+                if action == BulkAction::Index && value == "create" {
+                    action = BulkAction::Create;
+                }
+            } else if ITEM_PARAMS.contains(&name.as_str()) {
+                // Numeric metadata is sent as numbers, as in bulk bodies
+                let value = value
+                    .parse::<u64>()
+                    .map(Value::from)
+                    .unwrap_or(Value::String(value));
+                metadata.insert(name, value);
+            } else {
+                remaining.push((name, value));
+            }
+        }
+
+        let item = BulkItem {
+            action,
+            requested: action,
+            index: index.to_string(),
+            id,
+            metadata,
+            source,
+        };
+        (item, remaining)
+    }
+
+    /// Converts the bulk response of a single document write into the
+    /// response of the native API, returning its status.
+    pub fn single_response(&self, mut response: Value) -> (StatusCode, Value) {
+        let mut result = match response["items"][0].take() {
+            Value::Object(item) => item.into_iter().next().map(|(_, result)| result),
+            _ => None,
+        }
+        .unwrap_or_else(|| json!({}));
+
+        let status = result
+            .as_object_mut()
+            .and_then(|result| result.remove("status"))
+            .and_then(|status| status.as_u64())
+            .and_then(|status| StatusCode::from_u16(status as u16).ok())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        match result.get_mut("error").map(Value::take) {
+            Some(error) => (status, json!({ "error": error, "status": status.as_u16() })),
+            None => (status, result),
+        }
+    }
+
+    /// Assembles the bulk response from the answers of the proxy, where
+    /// `None` marks an item sent to OpenSearch, and the response of
+    /// OpenSearch for the sent items.
+    pub fn merge_response(
+        &self,
+        items: &[BulkItem],
+        answers: Vec<Option<Value>>,
+        upstream: Option<Value>,
+    ) -> Value {
+        let took = upstream
+            .as_ref()
+            .and_then(|response| response.get("took"))
+            .cloned()
+            .unwrap_or(json!(0));
+        let mut upstream_items = upstream
+            .and_then(|mut response| match response["items"].take() {
+                Value::Array(items) => Some(items),
+                _ => None,
+            })
+            .unwrap_or_default()
+            .into_iter();
+
+        let merged: Vec<Value> = items
+            .iter()
+            .zip(answers)
+            .map(|(item, answer)| match answer {
+                Some(answer) => answer,
+                None => {
+                    let mut response = upstream_items.next().unwrap_or_else(|| json!({}));
+                    if item.action != item.requested
+                        && let Some(result) = response
+                            .as_object_mut()
+                            .and_then(|response| response.remove(item.action.name()))
+                    {
+                        response = json!({ item.requested.name(): result });
+                    }
+                    response
+                }
+            })
+            .collect();
+        let errors = merged.iter().any(|item| {
+            item.as_object()
+                .and_then(|item| item.values().next())
+                .is_some_and(|result| result.get("error").is_some())
+        });
+
+        json!({ "took": took, "errors": errors, "items": merged })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::policy::RolePolicy;
+
+    fn lines() -> Vec<Value> {
+        vec![
+            json!({ "index": { "_id": "1" } }),
+            json!({ "title": "Rust" }),
+            json!({ "delete": { "_index": "books", "_id": 2 } }),
+            json!({ "update": { "_id": "3", "retry_on_conflict": 2 } }),
+            json!({ "doc": { "title": "Go" } }),
+        ]
+    }
+
+    #[test]
+    fn test_parse() {
+        let service = BulkService::new();
+        let items = service.parse(Some("movies"), lines()).unwrap();
+
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (item.action, item.index.as_str(), item.id.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (BulkAction::Index, "movies", Some("1")),
+                (BulkAction::Delete, "books", Some("2")),
+                (BulkAction::Update, "movies", Some("3")),
+            ]
+        );
+        assert_eq!(
+            service.to_lines(items.iter()),
+            vec![
+                json!({ "index": { "_index": "movies", "_id": "1" } }),
+                json!({ "title": "Rust" }),
+                json!({ "delete": { "_index": "books", "_id": "2" } }),
+                json!({ "update": { "_index": "movies", "_id": "3", "retry_on_conflict": 2 } }),
+                json!({ "doc": { "title": "Go" } }),
+            ]
+        );
+
+        assert!(service.parse(None, vec![json!({ "index": {} })]).is_err());
+        assert!(
+            service
+                .parse(None, vec![json!({ "upsert": {} }), json!({})])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let service = BulkService::new();
+        let items = service
+            .parse(
+                None,
+                vec![
+                    json!({ "index": {} }),
+                    json!({}),
+                    json!({ "delete": { "_index": "movies" } }),
+                    json!({ "index": { "_index": "movies", "pipeline": "stamp" } }),
+                    json!({}),
+                    json!({ "update": { "_index": "movies", "_id": "1" } }),
+                    json!({ "doc": {}, "lang": "painless" }),
+                    json!({ "update": { "_index": "movies", "_id": "1" } }),
+                    json!({ "doc": {}, "_source": true }),
+                ],
+            )
+            .unwrap();
+
+        assert!(items.iter().all(|item| service.validate(item).is_err()));
+    }
+
+    #[test]
+    fn test_check_script() {
+        let service = BulkService::new();
+        let items = service
+            .parse(
+                Some("movies"),
+                vec![
+                    json!({ "update": { "_id": "1" } }),
+                    json!({ "script": "ctx._source.title = ctx._source.salary" }),
+                    json!({ "update": { "_id": "2" } }),
+                    json!({ "doc": { "title": "Rust" } }),
+                ],
+            )
+            .unwrap();
+        let denied: RolePolicy = serde_json::from_value(json!({
+            "field_security": { "deny": ["salary"] }
+        }))
+        .unwrap();
+        let restricted = FieldPolicy::from_roles(&[&denied]);
+        let unrestricted = FieldPolicy::from_roles(&[&RolePolicy::default()]);
+        let masking = MaskingPolicy::default();
+        let pseudonyms = PseudonymPolicy::default();
+
+        assert_eq!(
+            service
+                .check_script(&items[0], &restricted, &masking, &pseudonyms)
+                .unwrap_err()
+                .status,
+            StatusCode::FORBIDDEN
+        );
+        assert!(
+            service
+                .check_script(&items[1], &restricted, &masking, &pseudonyms)
+                .is_ok()
+        );
+        assert!(
+            service
+                .check_script(&items[0], &unrestricted, &masking, &pseudonyms)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_invisible() {
+        let service = BulkService::new();
+        let mut items = service
+            .parse(
+                Some("movies"),
+                vec![
+                    json!({ "index": { "_id": "1" } }),
+                    json!({ "title": "Rust" }),
+                    json!({ "delete": { "_id": "2" } }),
+                    json!({ "update": { "_id": "3", "retry_on_conflict": 2 } }),
+                    json!({ "doc": { "title": "Go" }, "doc_as_upsert": true }),
+                    json!({ "update": { "_id": "4" } }),
+                    json!({ "doc": { "title": "C" } }),
+                ],
+            )
+            .unwrap();
+
+        assert!(service.requires_visibility(&items[0]));
+        assert_eq!(service.invisible(&mut items[0]), None);
+        assert_eq!(items[0].action, BulkAction::Create);
+        assert_eq!(
+            service.invisible(&mut items[1]).unwrap()["delete"]["result"],
+            json!("not_found")
+        );
+        assert_eq!(service.invisible(&mut items[2]), None);
+        assert_eq!(
+            service.to_lines(items[2..3].iter()),
+            vec![
+                json!({ "create": { "_index": "movies", "_id": "3" } }),
+                json!({ "title": "Go" }),
+            ]
+        );
+        assert_eq!(
+            service.invisible(&mut items[3]).unwrap()["update"]["error"]["type"],
+            json!("document_missing_exception")
+        );
+    }
+
+    #[test]
+    fn test_single_item() {
+        let service = BulkService::new();
+        let params = vec![
+            ("op_type".to_string(), "create".to_string()),
+            ("if_seq_no".to_string(), "4".to_string()),
+            ("refresh".to_string(), "true".to_string()),
+        ];
+        let (item, params) = service.single_item(
+            BulkAction::Index,
+            "movies",
+            Some("1".to_string()),
+            params,
+            Some(json!({ "title": "Rust" })),
+        );
+
+        assert_eq!(params, vec![("refresh".to_string(), "true".to_string())]);
+        assert_eq!(
+            service.to_lines(std::iter::once(&item)),
+            vec![
+                json!({ "create": { "_index": "movies", "_id": "1", "if_seq_no": 4 } }),
+                json!({ "title": "Rust" }),
+            ]
+        );
+
+        let (status, body) = service.single_response(json!({
+            "items": [{ "create": { "_id": "1", "result": "created", "status": 201 } }]
+        }));
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, json!({ "_id": "1", "result": "created" }));
+
+        let rejected = service.rejected(&item, BulkItemError::document_missing("1"));
+        let (status, body) =
+            service.single_response(service.merge_response(&[item], vec![Some(rejected)], None));
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"]["type"], json!("document_missing_exception"));
+        assert_eq!(body["status"], json!(404));
+    }
+
+    #[test]
+    fn test_merge_response() {
+        let service = BulkService::new();
+        let mut items = service.parse(Some("movies"), lines()).unwrap();
+        items[0].action = BulkAction::Create;
+
+        let response = service.merge_response(
+            &items,
+            vec![None, Some(service.not_found(&items[1])), None],
+            Some(json!({
+                "took": 5,
+                "errors": false,
+                "items": [
+                    { "create": { "_id": "1", "status": 201 } },
+                    { "update": { "_id": "3", "status": 200 } }
+                ]
+            })),
+        );
+
+        assert_eq!(response["took"], json!(5));
+        assert_eq!(response["errors"], json!(false));
+        assert_eq!(
+            response["items"][0],
+            json!({ "index": { "_id": "1", "status": 201 } })
+        );
+        assert_eq!(response["items"][1]["delete"]["result"], json!("not_found"));
+        assert_eq!(response["items"][2]["update"]["status"], json!(200));
+
+        let rejected = service.rejected(&items[2], BulkItemError::document_missing("3"));
+        let response = service.merge_response(&items[2..], vec![Some(rejected)], None);
+        assert_eq!(response["errors"], json!(true));
+        assert_eq!(response["items"][0]["update"]["status"], json!(404));
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde_json::{Value, json};
+
+use crate::auth::identity::Identity;
+use crate::handlers::index_authorization::IndexPolicy;
+use crate::models::policy::{ClusterPermission, Policy};
+
+/// Name the proxy reports for itself in place of upstream node names.
+const PROXY_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// How long the root info of the cluster is reused before it is fetched
+/// again, so that an upgraded cluster is eventually reported.
+const ROOT_INFO_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Roles reported for the proxy, so that sniffing clients send it every
+/// request instead of skipping it as a dedicated cluster manager.
+const PROXY_ROLES: &[&str] = &["data", "ingest"];
+
+/// A service answering the info APIs clients call when connecting.
+///
+/// Clients read the version of the cluster from `GET /` to pick a
+/// compatible protocol, so the proxy mirrors the upstream version and
+/// distribution while hiding the node names. Sniffing clients discover
+/// nodes through `_nodes/http`; the proxy reports itself as the only node,
+/// so that clients never bypass it by connecting to OpenSearch directly.
+///
+/// The cluster health is only shown in full to callers holding the
+/// `monitor` cluster permission, with index level detail reduced to the
+/// readable indices. Other callers only learn the status.
+#[derive(Clone)]
+pub struct ClusterInfoService {
+    root: Arc<RwLock<Option<(Instant, Value)>>>,
+}
+
+impl ClusterInfoService {
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Returns the cached root info, unless it has expired.
+    pub fn cached_root_info(&self) -> Option<Value> {
+        let root = self.root.read().expect("Root info lock poisoned");
+        root.as_ref()
+            .filter(|(fetched, _)| fetched.elapsed() < ROOT_INFO_TTL)
+            .map(|(_, info)| info.clone())
+    }
+
+    /// Builds the root info of the proxy from the upstream root info and
+    /// caches it.
+    pub fn root_info(&self, upstream: &Value) -> Value {
+        let info = json!({
+            "name": PROXY_NAME,
+            "cluster_name": upstream["cluster_name"],
+            "cluster_uuid": upstream["cluster_uuid"],
+            "version": upstream["version"],
+            "tagline": upstream["tagline"],
+        });
+        *self.root.write().expect("Root info lock poisoned") = Some((Instant::now(), info.clone()));
+        info
+    }
+
+    /// Returns whether a role of the given identity may monitor the
+    /// cluster.
+    pub fn permits_monitor(&self, policy: &Policy, identity: &Identity) -> bool {
+        policy
+            .roles_for(identity)
+            .iter()
+            .any(|role| role.cluster.contains(&ClusterPermission::Monitor))
+    }
+
+    /// Keeps the index level health of readable indices.
+    pub fn restrict_health(&self, health: &mut Value, policy: &IndexPolicy) {
+        if let Some(indices) = health.get_mut("indices").and_then(Value::as_object_mut) {
+            indices.retain(|index, _| policy.permits(index));
+        }
+    }
+
+    /// Reduces a health response to the status, for callers that may not
+    /// monitor the cluster.
+    pub fn minimal_health(&self, health: &Value) -> Value {
+        json!({ "status": health["status"] })
+    }
+
+    /// Replaces the nodes of a `_nodes/http` response by the proxy,
+    /// reachable at the given address.
+    pub fn nodes_http(&self, upstream: &Value, address: &str) -> Value {
+        let node = upstream["nodes"]
+            .as_object()
+            .and_then(|nodes| nodes.values().next())
+            .cloned()
+            .unwrap_or_default();
+
+        json!({
+            "_nodes": { "total": 1, "successful": 1, "failed": 0 },
+            "cluster_name": upstream["cluster_name"],
+            "nodes": { PROXY_NAME: {
+                "name": PROXY_NAME,
+                "version": node["version"],
+                "build_type": node["build_type"],
+                "build_hash": node["build_hash"],
+                "roles": PROXY_ROLES,
+                "http": {
+                    "bound_address": [address],
+                    "publish_address": address,
+                    "max_content_length_in_bytes": node["http"]["max_content_length_in_bytes"],
+                },
+            } },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_info_is_cached() {
+        let service = ClusterInfoService::new();
+        assert_eq!(service.cached_root_info(), None);
+
+        let info = service.root_info(&json!({
+            "name": "opensearch-node-7",
+            "cluster_name": "docker-cluster",
+            "cluster_uuid": "4x2Bq6",
+            "version": { "distribution": "opensearch", "number": "2.19.1" },
+            "tagline": "The OpenSearch Project: https://opensearch.org/"
+        }));
+
+        assert_eq!(info["name"], "opensearch-filter-proxy");
+        assert_eq!(info["version"]["number"], "2.19.1");
+        assert_eq!(service.cached_root_info(), Some(info));
+    }
+
+    #[test]
+    fn test_nodes_http_points_at_proxy() {
+        let service = ClusterInfoService::new();
+        let upstream = json!({
+            "cluster_name": "docker-cluster",
+            "nodes": {
+                "a1": {
+                    "name": "opensearch-node-1",
+                    "host": "10.0.0.7",
+                    "ip": "10.0.0.7",
+                    "version": "2.19.1",
+                    "roles": ["cluster_manager"],
+                    "http": {
+                        "bound_address": ["10.0.0.7:9200"],
+                        "publish_address": "10.0.0.7:9200",
+                        "max_content_length_in_bytes": 104857600
+                    }
+                },
+                "b2": { "name": "opensearch-node-2", "host": "10.0.0.8" }
+            }
+        });
+
+        let nodes = service.nodes_http(&upstream, "proxy.example.com:3000");
+
+        assert_eq!(nodes["_nodes"]["total"], 1);
+        let node = &nodes["nodes"]["opensearch-filter-proxy"];
+        assert_eq!(node["http"]["publish_address"], "proxy.example.com:3000");
+        assert_eq!(node["roles"], json!(["data", "ingest"]));
+        assert!(!nodes.to_string().contains("10.0.0.7"));
+    }
+
+    #[test]
+    fn test_health_for_callers() {
+        let service = ClusterInfoService::new();
+        let policy: Policy = serde_json::from_value(json!({ "roles": {
+            "ops": { "cluster": ["monitor"], "indices": { "read": ["movies"] } },
+            "guest": {}
+        } }))
+        .unwrap();
+        let identity = |role: &str| Identity {
+            user: "alice".to_string(),
+            roles: vec![role.to_string()],
+        };
+        assert!(service.permits_monitor(&policy, &identity("ops")));
+        assert!(!service.permits_monitor(&policy, &identity("guest")));
+
+        let mut health = json!({
+            "cluster_name": "docker-cluster",
+            "status": "yellow",
+            "active_shards": 7,
+            "indices": {
+                "movies": { "status": "green" },
+                "users": { "status": "yellow" }
+            }
+        });
+        assert_eq!(
+            service.minimal_health(&health),
+            json!({ "status": "yellow" })
+        );
+
+        let indices = IndexPolicy::readable(&policy.roles_for(&identity("ops")));
+        service.restrict_health(&mut health, &indices);
+        assert_eq!(
+            health["indices"],
+            json!({ "movies": { "status": "green" } })
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use axum::http::{HeaderMap, HeaderValue};
+
+use crate::auth::identity::{ROLES_HEADER, USER_HEADER};
+use crate::config::Config;
+use crate::handlers::index_authorization::IndexPolicy;
+
+/// System index of OpenSearch Dashboards used when none is configured.
+const DEFAULT_SYSTEM_INDEX: &str = ".kibana";
+
+/// Endpoints of the system index passed through for the service account,
+/// named by the path segment following the index. Requests to the index
+/// itself, such as creating it, are passed through as well.
+const SYSTEM_INDEX_ENDPOINTS: &[&str] = &[
+    "_bulk",
+    "_count",
+    "_create",
+    "_delete_by_query",
+    "_doc",
+    "_mapping",
+    "_mget",
+    "_refresh",
+    "_search",
+    "_settings",
+    "_source",
+    "_update",
+    "_update_by_query",
+];
+
+/// Largest body forwarded to the system index, matching the default
+/// `http.max_content_length` of OpenSearch.
+pub const MAX_FORWARDED_BODY: usize = 100 * 1024 * 1024;
+
+/// Who a request in Dashboards mode comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DashboardsCaller {
+    /// Dashboards acting on its own, e.g. to read saved objects
+    ServiceAccount,
+    /// Dashboards acting on behalf of a forwarded end user
+    EndUser,
+    /// Any other caller
+    Other,
+}
+
+/// A service placing the proxy between OpenSearch Dashboards and the
+/// cluster.
+///
+/// Dashboards authenticates with a single service account, so requests it
+/// sends on behalf of a user carry the end user in a trusted forwarded
+/// header. The identity headers of such requests are replaced by the
+/// forwarded user and roles before any policy applies. The forwarded
+/// headers are ignored for every other caller, so that they cannot be
+/// spoofed.
+///
+/// The system index of Dashboards is reserved to the service account
+/// acting on its own, whose requests to it are passed through unchanged.
+#[derive(Clone)]
+pub struct DashboardsService {
+    service_user: Option<String>,
+    user_header: Option<String>,
+    roles_header: Option<String>,
+    system_index: String,
+}
+
+impl DashboardsService {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            service_user: config.dashboards_service_user.clone(),
+            user_header: config.dashboards_user_header.clone(),
+            roles_header: config.dashboards_roles_header.clone(),
+            system_index: config
+                .dashboards_index
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SYSTEM_INDEX.to_string()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.service_user.is_some()
+    }
+
+    /// Identifies the caller of a request and replaces its identity by the
+    /// forwarded end user if Dashboards acts on behalf of one.
+    pub fn resolve_caller(&self, headers: &mut HeaderMap) -> DashboardsCaller {
+        let Some(service_user) = &self.service_user else {
+            return DashboardsCaller::Other;
+        };
+        let user = headers
+            .get(USER_HEADER)
+            .and_then(|user| user.to_str().ok())
+            .map(str::trim);
+        if user != Some(service_user.as_str()) {
+            return DashboardsCaller::Other;
+        }
+
+        let forwarded = |header: &Option<String>| {
+            header
+                .as_deref()
+                .and_then(|name| headers.get(name))
+                .filter(|value| value.to_str().is_ok_and(|value| !value.trim().is_empty()))
+                .cloned()
+        };
+        let Some(end_user) = forwarded(&self.user_header) else {
+            return DashboardsCaller::ServiceAccount;
+        };
+        let roles = forwarded(&self.roles_header);
+
+        headers.insert(USER_HEADER, end_user);
+        match roles {
+            Some(roles) => headers.insert(ROLES_HEADER, roles),
+            None => headers.insert(ROLES_HEADER, HeaderValue::from_static("")),
+        };
+        DashboardsCaller::EndUser
+    }
+
+    /// Returns whether a request path targets a passed through endpoint of
+    /// the system indices of Dashboards, with every index of its first
+    /// segment being a system index.
+    ///
+    /// The path is forwarded as is and resolved again by the client, so
+    /// paths with empty segments or segments that could climb out of the
+    /// index, even percent-encoded, never qualify.
+    pub fn targets_system_index(&self, path: &str) -> bool {
+        let segments: Vec<&str> = path.strip_prefix('/').unwrap_or(path).split('/').collect();
+        if !self.is_enabled() || segments.iter().any(|segment| !is_plain_segment(segment)) {
+            return false;
+        }
+        segments[0]
+            .split(',')
+            .all(|index| index.starts_with(self.system_index.as_str()))
+            && segments
+                .get(1)
+                .is_none_or(|endpoint| SYSTEM_INDEX_ENDPOINTS.contains(endpoint))
+    }
+
+    /// Reserves the system index, which is only reachable through the
+    /// passthrough of the service account.
+    pub fn reserve_system_index(&self, policy: &mut IndexPolicy) {
+        if self.is_enabled() {
+            policy.reserve(&self.system_index);
+        }
+    }
+}
+
+/// Returns whether a path segment is non-empty and, once percent-decoded,
+/// neither a dot segment nor containing a path separator.
+fn is_plain_segment(segment: &str) -> bool {
+    let mut decoded = Vec::with_capacity(segment.len());
+    let mut bytes = segment.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            decoded.push(byte);
+            continue;
+        }
+        let hex = [bytes.next(), bytes.next()];
+        let value = match hex {
+            [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match value {
+            Some(value) => decoded.push(value),
+            None => return false,
+        }
+    }
+    !decoded.is_empty()
+        && decoded != b"."
+        && decoded != b".."
+        && !decoded.iter().any(|byte| matches!(byte, b'/' | b'\\'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> DashboardsService {
+        DashboardsService {
+            service_user: Some("dashboards".to_string()),
+            user_header: Some("x-forwarded-user".to_string()),
+            roles_header: Some("x-forwarded-roles".to_string()),
+            system_index: DEFAULT_SYSTEM_INDEX.to_string(),
+        }
+    }
+
+    fn headers(entries: &[(&'static str, &'static str)]) -> HeaderMap {
+        entries
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_caller() {
+        let service = service();
+
+        let mut forwarded = headers(&[
+            (USER_HEADER, "dashboards"),
+            (ROLES_HEADER, "dashboards_server"),
+            ("x-forwarded-user", "alice"),
+            ("x-forwarded-roles", "analyst"),
+        ]);
+        assert_eq!(
+            service.resolve_caller(&mut forwarded),
+            DashboardsCaller::EndUser
+        );
+        assert_eq!(forwarded[USER_HEADER], "alice");
+        assert_eq!(forwarded[ROLES_HEADER], "analyst");
+
+        let mut own = headers(&[(USER_HEADER, "dashboards")]);
+        assert_eq!(
+            service.resolve_caller(&mut own),
+            DashboardsCaller::ServiceAccount
+        );
+
+        let mut spoofed = headers(&[(USER_HEADER, "mallory"), ("x-forwarded-user", "alice")]);
+        assert_eq!(
+            service.resolve_caller(&mut spoofed),
+            DashboardsCaller::Other
+        );
+        assert_eq!(spoofed[USER_HEADER], "mallory");
+    }
+
+    #[test]
+    fn test_targets_system_index() {
+        let service = service();
+
+        assert!(service.targets_system_index("/.kibana/_search"));
+        assert!(service.targets_system_index("/.kibana_1,.kibana_2/_doc/1"));
+        assert!(service.targets_system_index("/.kibana/_doc/config%3A2.19.1"));
+        assert!(service.targets_system_index("/.kibana_1"));
+        assert!(!service.targets_system_index("/.kibana,movies/_search"));
+        assert!(!service.targets_system_index("/.kibana/_cluster/settings"));
+        assert!(!service.targets_system_index("/_msearch"));
+        assert!(!service.targets_system_index("/"));
+    }
+
+    #[test]
+    fn test_targets_system_index_rejects_traversal() {
+        let service = service();
+
+        assert!(!service.targets_system_index("/.kibana/../_cluster/settings"));
+        assert!(!service.targets_system_index("/.kibana/%2e%2e/movies/_delete_by_query"));
+        assert!(!service.targets_system_index("/.kibana/_doc/%2E%2E"));
+        assert!(!service.targets_system_index("/.kibana/_doc/a%2F..%2F..%2F_cluster"));
+        assert!(!service.targets_system_index("/.kibana/_doc/..\\_cluster"));
+        assert!(!service.targets_system_index("/.kibana//_search"));
+        assert!(!service.targets_system_index("/.kibana/_doc/1/"));
+        assert!(!service.targets_system_index("/.kibana/_doc/%zz"));
+    }
+}
@@ -0,0 +1,290 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+use crate::auth::identity::Identity;
+use crate::handlers::error_sanitizer::{filter_secrets, mentions_secret, scrub};
+use crate::handlers::search_response::for_each_hit;
+use crate::models::policy::{DebugMode, DebugPolicy, Policy};
+
+/// Error returned when a request sets a debugging flag the caller may not
+/// use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugFlagForbidden(pub &'static str);
+
+impl IntoResponse for DebugFlagForbidden {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected request using forbidden flag '{}'", self.0);
+
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": {
+                    "type": "security_exception",
+                    "reason": format!("the [{}] option is not permitted", self.0),
+                },
+                "status": StatusCode::FORBIDDEN.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A service controlling the `explain`, `profile` and `track_scores`
+/// debugging output of searches.
+///
+/// Explanations and profiles describe the executed query, including the
+/// clauses of the injected security filter. Depending on the caller's
+/// policy, the flags are rejected, passed through, or accepted with the
+/// subtrees contributed by the filter removed from the response.
+#[derive(Clone)]
+pub struct DebugOutputService;
+
+impl DebugOutputService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Resolves the effective debug policy of the given identity, using the
+    /// least restrictive mode granted by any of its roles.
+    pub fn policy_for(&self, policy: &Policy, identity: &Identity) -> DebugPolicy {
+        policy.roles_for(identity).into_iter().fold(
+            DebugPolicy {
+                explain: DebugMode::Deny,
+                profile: DebugMode::Deny,
+                track_scores: DebugMode::Deny,
+            },
+            |effective, role| DebugPolicy {
+                explain: effective.explain.max(role.debug.explain),
+                profile: effective.profile.max(role.debug.profile),
+                track_scores: effective.track_scores.max(role.debug.track_scores),
+            },
+        )
+    }
+
+    /// Rejects forbidden debugging flags and drops `track_scores` if it is
+    /// stripped.
+    pub fn check_request(
+        &self,
+        body: &mut Value,
+        policy: &DebugPolicy,
+    ) -> Result<(), DebugFlagForbidden> {
+        if policy.explain == DebugMode::Deny && requests_explain(body) {
+            return Err(DebugFlagForbidden("explain"));
+        }
+        if policy.profile == DebugMode::Deny && is_enabled(body, "profile") {
+            return Err(DebugFlagForbidden("profile"));
+        }
+        if is_enabled(body, "track_scores") {
+            match policy.track_scores {
+                DebugMode::Deny => return Err(DebugFlagForbidden("track_scores")),
+                DebugMode::Strip => {
+                    if let Some(body) = body.as_object_mut() {
+                        body.remove("track_scores");
+                    }
+                }
+                DebugMode::Allow => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the parts of explanations and profiles contributed by the
+    /// given security filter.
+    pub fn strip_response(&self, response: &mut Value, filter: &Value, policy: &DebugPolicy) {
+        let strip_explain = policy.explain == DebugMode::Strip;
+        let strip_profile = policy.profile == DebugMode::Strip;
+        if !strip_explain && !strip_profile {
+            return;
+        }
+        let secrets = filter_secrets(filter);
+
+        if strip_explain {
+            for_each_hit(response, &mut |hit, _| {
+                if let Some(explanation) = hit.get_mut("_explanation") {
+                    strip_tree(explanation, "details", &secrets);
+                }
+            });
+        }
+
+        if strip_profile {
+            let searches = response
+                .pointer_mut("/profile/shards")
+                .and_then(Value::as_array_mut)
+                .into_iter()
+                .flatten()
+                .filter_map(|shard| shard.get_mut("searches").and_then(Value::as_array_mut))
+                .flatten();
+            for search in searches {
+                if let Some(queries) = search.get_mut("query").and_then(Value::as_array_mut) {
+                    for query in queries {
+                        strip_tree(query, "children", &secrets);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_enabled(body: &Value, flag: &str) -> bool {
+    body.get(flag).and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// Returns whether the body asks for explanations at the top level or in
+/// any `inner_hits` or `top_hits` section.
+fn requests_explain(body: &Value) -> bool {
+    fn nested(value: &Value) -> bool {
+        match value {
+            Value::Object(object) => object.iter().any(|(key, child)| {
+                ((key == "inner_hits" || key == "top_hits") && is_enabled(child, "explain"))
+                    || nested(child)
+            }),
+            Value::Array(items) => items.iter().any(nested),
+            _ => false,
+        }
+    }
+
+    is_enabled(body, "explain") || nested(body)
+}
+
+/// Prunes an explanation or profile tree. Nodes describing the security
+/// filter are removed, as are nodes left without children after pruning,
+/// and any remaining description is scrubbed. The root is always kept.
+fn strip_tree(root: &mut Value, children_key: &str, secrets: &[String]) {
+    fn prune(node: &mut Value, children_key: &str, secrets: &[String]) -> bool {
+        let mentions = node
+            .get("description")
+            .and_then(Value::as_str)
+            .is_some_and(|description| mentions_secret(description, secrets));
+        let Some(children) = node.get_mut(children_key).and_then(Value::as_array_mut) else {
+            return !mentions;
+        };
+        if children.is_empty() {
+            return !mentions;
+        }
+        children.retain_mut(|child| prune(child, children_key, secrets));
+        !children.is_empty()
+    }
+
+    fn scrub_descriptions(node: &mut Value, children_key: &str, secrets: &[String]) {
+        if let Some(Value::String(description)) = node.get_mut("description") {
+            *description = scrub(description, secrets);
+        }
+        if let Some(children) = node.get_mut(children_key).and_then(Value::as_array_mut) {
+            for child in children {
+                scrub_descriptions(child, children_key, secrets);
+            }
+        }
+    }
+
+    if let Some(children) = root.get_mut(children_key).and_then(Value::as_array_mut) {
+        children.retain_mut(|child| prune(child, children_key, secrets));
+    }
+    scrub_descriptions(root, children_key, secrets);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::policy::RolePolicy;
+
+    fn debug_policy(mode: &str) -> DebugPolicy {
+        let role: RolePolicy = serde_json::from_value(json!({
+            "debug": { "explain": mode, "profile": mode, "track_scores": mode }
+        }))
+        .unwrap();
+        role.debug
+    }
+
+    #[test]
+    fn test_check_request() {
+        let service = DebugOutputService::new();
+
+        let mut body = json!({ "explain": true });
+        assert_eq!(
+            service.check_request(&mut body, &debug_policy("deny")),
+            Err(DebugFlagForbidden("explain"))
+        );
+
+        let mut body = json!({ "aggs": { "top": { "top_hits": { "explain": true } } } });
+        assert_eq!(
+            service.check_request(&mut body, &debug_policy("deny")),
+            Err(DebugFlagForbidden("explain"))
+        );
+
+        let mut body = json!({ "profile": true, "track_scores": true });
+        assert_eq!(
+            service.check_request(&mut body, &debug_policy("strip")),
+            Ok(())
+        );
+        assert_eq!(body, json!({ "profile": true }));
+    }
+
+    #[test]
+    fn test_strip_response() {
+        let service = DebugOutputService::new();
+        let filter = json!({ "term": { "genre.keyword": "Sci-Fi" } });
+        let mut response = json!({
+            "hits": { "hits": [{
+                "_explanation": {
+                    "value": 1.2,
+                    "description": "sum of:",
+                    "details": [
+                        { "value": 1.2, "description": "weight(title:rust)", "details": [] },
+                        {
+                            "value": 0.0,
+                            "description": "match on required clause, product of:",
+                            "details": [{ "value": 1.0, "description": "genre.keyword:Sci-Fi", "details": [] }]
+                        }
+                    ]
+                }
+            }]},
+            "profile": { "shards": [{ "searches": [{ "query": [{
+                "type": "BooleanQuery",
+                "description": "+title:rust #genre.keyword:Sci-Fi",
+                "children": [
+                    { "type": "TermQuery", "description": "title:rust" },
+                    { "type": "TermQuery", "description": "genre.keyword:Sci-Fi" }
+                ]
+            }]}]}]}
+        });
+
+        service.strip_response(&mut response, &filter, &debug_policy("strip"));
+
+        assert_eq!(
+            response["hits"]["hits"][0]["_explanation"]["details"],
+            json!([{ "value": 1.2, "description": "weight(title:rust)", "details": [] }])
+        );
+        assert_eq!(
+            response["profile"]["shards"][0]["searches"][0]["query"][0],
+            json!({
+                "type": "BooleanQuery",
+                "description": "+title:rust #[filtered]:[filtered]",
+                "children": [{ "type": "TermQuery", "description": "title:rust" }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_policy_for_uses_least_restrictive_role() {
+        let service = DebugOutputService::new();
+        let policy: Policy = serde_json::from_value(json!({
+            "roles": {
+                "analyst": { "debug": { "explain": "deny", "profile": "allow" } },
+                "reader": {}
+            }
+        }))
+        .unwrap();
+        let identity = Identity {
+            user: "alice".to_string(),
+            roles: vec!["analyst".to_string(), "reader".to_string()],
+        };
+
+        let effective = service.policy_for(&policy, &identity);
+        assert_eq!(effective.explain, DebugMode::Strip);
+        assert_eq!(effective.profile, DebugMode::Allow);
+    }
+}
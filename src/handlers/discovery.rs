@@ -0,0 +1,367 @@
+use axum::{
+    Json,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+use crate::handlers::field_security::glob_match;
+use crate::handlers::index_authorization::IndexPolicy;
+use crate::handlers::search_params::SearchParams;
+
+/// Column of `_cat` rows naming the concrete index, used to reduce rows to
+/// readable indices.
+const INDEX_COLUMN: &str = "index";
+
+/// A `_cat` API listing indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatApi {
+    Indices,
+    Aliases,
+}
+
+impl CatApi {
+    /// Columns OpenSearch returns when no `h` parameter is given.
+    fn default_columns(&self) -> &'static [&'static str] {
+        match self {
+            Self::Indices => &[
+                "health",
+                "status",
+                "index",
+                "uuid",
+                "pri",
+                "rep",
+                "docs.count",
+                "docs.deleted",
+                "store.size",
+                "pri.store.size",
+            ],
+            Self::Aliases => &[
+                "alias",
+                "index",
+                "filter",
+                "routing.index",
+                "routing.search",
+                "is_write_index",
+            ],
+        }
+    }
+}
+
+/// How the client asked a `_cat` API to format its rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatRequest {
+    api: CatApi,
+    json: bool,
+    headers: bool,
+    columns: Option<Vec<String>>,
+    /// Whether the index column was only requested by the proxy
+    index_added: bool,
+}
+
+/// The response of a `_cat` API in the format requested by the client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CatBody {
+    Json(Value),
+    Text(String),
+}
+
+impl IntoResponse for CatBody {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Json(rows) => Json(rows).into_response(),
+            Self::Text(table) => {
+                ([(header::CONTENT_TYPE, "text/plain; charset=UTF-8")], table).into_response()
+            }
+        }
+    }
+}
+
+/// A service reducing the metadata returned by discovery APIs to the
+/// indices a caller may read.
+///
+/// `_cat` rows are always fetched as JSON, so that they can be filtered by
+/// their index column, and rendered as a text table by the proxy unless
+/// the client asked for JSON.
+#[derive(Clone)]
+pub struct DiscoveryService;
+
+impl DiscoveryService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Removes the formatting parameters of a `_cat` request and returns
+    /// the parameters requesting JSON rows that include the index column.
+    pub fn cat_request(&self, api: CatApi, params: SearchParams) -> (CatRequest, SearchParams) {
+        let mut request = CatRequest {
+            api,
+            json: false,
+            headers: false,
+            columns: None,
+            index_added: false,
+        };
+        let mut forwarded = SearchParams::with_capacity(params.len() + 1);
+        for (name, value) in params {
+            match name.as_str() {
+                "format" => request.json = value == "json",
+                "v" => request.headers = value != "false",
+                "h" => {
+                    request.columns = Some(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|column| !column.is_empty())
+                            .map(str::to_string)
+                            .collect(),
+                    )
+                }
+                _ => forwarded.push((name, value)),
+            }
+        }
+
+        if let Some(columns) = &request.columns {
+            let mut requested = columns.clone();
+            if !columns
+                .iter()
+                .any(|column| glob_match(column, INDEX_COLUMN))
+            {
+                requested.push(INDEX_COLUMN.to_string());
+                request.index_added = true;
+            }
+            forwarded.push(("h".to_string(), requested.join(",")));
+        }
+        forwarded.push(("format".to_string(), "json".to_string()));
+        (request, forwarded)
+    }
+
+    /// Keeps the `_cat` rows of readable indices.
+    pub fn restrict_cat_rows(&self, rows: &mut Value, policy: &IndexPolicy) {
+        if let Some(rows) = rows.as_array_mut() {
+            rows.retain(|row| {
+                row[INDEX_COLUMN]
+                    .as_str()
+                    .is_some_and(|index| policy.permits(index))
+            });
+        }
+    }
+
+    /// Formats `_cat` rows as requested by the client.
+    pub fn cat_body(&self, request: &CatRequest, mut rows: Value) -> CatBody {
+        let rows = match rows.as_array_mut() {
+            Some(rows) => std::mem::take(rows),
+            None => Vec::new(),
+        };
+        let rows: Vec<_> = rows
+            .into_iter()
+            .filter_map(|row| match row {
+                Value::Object(mut row) => {
+                    if request.index_added {
+                        row.remove(INDEX_COLUMN);
+                    }
+                    Some(row)
+                }
+                _ => None,
+            })
+            .collect();
+        if request.json {
+            return CatBody::Json(json!(rows));
+        }
+
+        // JSON rows do not keep the column order, so requested columns are
+        // restored from `h`, and wildcards expand in alphabetical order
+        let columns: Vec<String> = match &request.columns {
+            None => request
+                .api
+                .default_columns()
+                .iter()
+                .map(|column| column.to_string())
+                .collect(),
+            Some(columns) => {
+                let mut expanded = Vec::new();
+                for column in columns {
+                    if !column.contains('*') {
+                        expanded.push(column.clone());
+                    } else if let Some(row) = rows.first() {
+                        expanded.extend(
+                            row.keys()
+                                .filter(|key| glob_match(column, key) && !expanded.contains(key))
+                                .cloned()
+                                .collect::<Vec<_>>(),
+                        );
+                    }
+                }
+                expanded
+            }
+        };
+
+        let mut table: Vec<Vec<String>> = Vec::with_capacity(rows.len() + 1);
+        if request.headers {
+            table.push(columns.clone());
+        }
+        for row in &rows {
+            table.push(
+                columns
+                    .iter()
+                    .map(|column| match row.get(column) {
+                        None | Some(Value::Null) => String::new(),
+                        Some(Value::String(value)) => value.clone(),
+                        Some(value) => value.to_string(),
+                    })
+                    .collect(),
+            );
+        }
+
+        let widths: Vec<usize> = (0..columns.len())
+            .map(|i| {
+                table
+                    .iter()
+                    .map(|line| line[i].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let mut text = String::new();
+        for line in table {
+            let cells: Vec<String> = line
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            text.push_str(cells.join(" ").trim_end());
+            text.push('\n');
+        }
+        CatBody::Text(text)
+    }
+
+    /// Keeps the entries of a response keyed by index name, such as a
+    /// `_mapping` response, that belong to readable indices.
+    pub fn restrict_index_keys(&self, response: &mut Value, policy: &IndexPolicy) {
+        if let Some(indices) = response.as_object_mut() {
+            indices.retain(|index, _| policy.permits(index));
+        }
+    }
+
+    /// Reduces a `_resolve/index` response to readable indices. Aliases are
+    /// only listed with the readable indices they point to, and data
+    /// streams only if they are readable themselves.
+    pub fn restrict_resolved(&self, response: &mut Value, policy: &IndexPolicy) {
+        let permitted = |name: &Value| name.as_str().is_some_and(|name| policy.permits(name));
+
+        if let Some(indices) = response.get_mut("indices").and_then(Value::as_array_mut) {
+            indices.retain(|index| permitted(&index["name"]));
+        }
+        if let Some(aliases) = response.get_mut("aliases").and_then(Value::as_array_mut) {
+            aliases.retain_mut(|alias| {
+                let Some(indices) = alias.get_mut("indices").and_then(Value::as_array_mut) else {
+                    return false;
+                };
+                indices.retain(permitted);
+                !indices.is_empty()
+            });
+        }
+        if let Some(data_streams) = response
+            .get_mut("data_streams")
+            .and_then(Value::as_array_mut)
+        {
+            data_streams.retain(|data_stream| permitted(&data_stream["name"]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::policy::RolePolicy;
+
+    fn read_policy(read: Value) -> IndexPolicy {
+        let role: RolePolicy =
+            serde_json::from_value(json!({ "indices": { "read": read } })).unwrap();
+        IndexPolicy::readable(&[&role])
+    }
+
+    #[test]
+    fn test_cat_rows_as_text() {
+        let service = DiscoveryService::new();
+        let params = vec![
+            ("h".to_string(), "health,i*,docs.count".to_string()),
+            ("v".to_string(), "true".to_string()),
+            ("s".to_string(), "index".to_string()),
+        ];
+        let (request, forwarded) = service.cat_request(CatApi::Indices, params);
+        assert_eq!(
+            forwarded,
+            vec![
+                ("s".to_string(), "index".to_string()),
+                ("h".to_string(), "health,i*,docs.count".to_string()),
+                ("format".to_string(), "json".to_string()),
+            ]
+        );
+
+        let mut rows = json!([
+            { "health": "green", "index": "movies", "docs.count": "12" },
+            { "health": "yellow", "index": "users", "docs.count": "3" }
+        ]);
+        service.restrict_cat_rows(&mut rows, &read_policy(json!(["movies*"])));
+
+        assert_eq!(
+            service.cat_body(&request, rows),
+            CatBody::Text("health index  docs.count\ngreen  movies 12\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cat_rows_as_json() {
+        let service = DiscoveryService::new();
+        let params = vec![
+            ("h".to_string(), "alias".to_string()),
+            ("format".to_string(), "json".to_string()),
+        ];
+        let (request, forwarded) = service.cat_request(CatApi::Aliases, params);
+        assert_eq!(
+            forwarded,
+            vec![
+                ("h".to_string(), "alias,index".to_string()),
+                ("format".to_string(), "json".to_string()),
+            ]
+        );
+
+        let mut rows = json!([
+            { "alias": "films", "index": "movies" },
+            { "alias": "people", "index": "users" }
+        ]);
+        service.restrict_cat_rows(&mut rows, &read_policy(json!(["movies"])));
+
+        assert_eq!(
+            service.cat_body(&request, rows),
+            CatBody::Json(json!([{ "alias": "films" }]))
+        );
+    }
+
+    #[test]
+    fn test_restrict_resolved() {
+        let service = DiscoveryService::new();
+        let mut response = json!({
+            "indices": [
+                { "name": "movies", "aliases": ["media"], "attributes": ["open"] },
+                { "name": "users", "aliases": ["media"], "attributes": ["open"] }
+            ],
+            "aliases": [
+                { "name": "media", "indices": ["movies", "users"] },
+                { "name": "people", "indices": ["users"] }
+            ],
+            "data_streams": [{ "name": "logs", "backing_indices": [".ds-logs-000001"] }]
+        });
+
+        service.restrict_resolved(&mut response, &read_policy(json!(["movies"])));
+
+        assert_eq!(
+            response,
+            json!({
+                "indices": [{ "name": "movies", "aliases": ["media"], "attributes": ["open"] }],
+                "aliases": [{ "name": "media", "indices": ["movies"] }],
+                "data_streams": []
+            })
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+use crate::handlers::search_params::SearchParams;
+
+/// Query string parameters selecting the `_source` of fetched documents.
+const SOURCE_PARAMS: &[&str] = &["_source", "_source_includes", "_source_excludes"];
+
+/// Metadata of a hit that is kept in get responses.
+const DOCUMENT_METADATA: &[&str] = &[
+    "_id",
+    "_index",
+    "_primary_term",
+    "_routing",
+    "_seq_no",
+    "_version",
+];
+
+/// Keys accepted in the `docs` entries of an `_mget` body.
+const MGET_DOC_KEYS: &[&str] = &["_id", "_index", "_source", "routing"];
+
+/// Error returned for malformed `_mget` bodies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidMgetRequest(pub String);
+
+impl IntoResponse for InvalidMgetRequest {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected invalid mget request: {}", self.0);
+
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": {
+                    "type": "action_request_validation_exception",
+                    "reason": format!("Validation Failed: 1: {};", self.0),
+                },
+                "status": StatusCode::BAD_REQUEST.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A single document requested by a get or `_mget` request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentRequest {
+    pub index: String,
+    pub id: String,
+    /// Source filtering as accepted by the `_source` option of a search
+    pub source: Option<Value>,
+    pub routing: Option<String>,
+}
+
+/// A service implementing document retrieval as filtered searches.
+///
+/// Get requests ignore queries, so they cannot be sent to OpenSearch with
+/// the security filter attached. Every requested document is instead
+/// fetched by an `ids` search, which the security filter applies to like
+/// to any other search, and the hit is shaped like a native get response.
+/// Documents excluded by the filter are reported as not found.
+#[derive(Clone)]
+pub struct DocumentService;
+
+impl DocumentService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Removes the `_source`, `_source_includes` and `_source_excludes`
+    /// parameters and converts them into a `_source` search option.
+    pub fn take_source_filter(&self, params: &mut SearchParams) -> Option<Value> {
+        let mut source = None;
+        let mut includes = None;
+        let mut excludes = None;
+        for (name, value) in
+            params.extract_if(.., |(name, _)| SOURCE_PARAMS.contains(&name.as_str()))
+        {
+            match name.as_str() {
+                "_source" => source = Some(value),
+                "_source_includes" => includes = Some(split_list(&value)),
+                _ => excludes = Some(split_list(&value)),
+            }
+        }
+
+        let source = source.map(|value| match value.as_str() {
+            "" | "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            fields => split_list(fields),
+        });
+        if includes.is_none() && excludes.is_none() {
+            return source;
+        }
+
+        // Explicit includes take precedence over a field list in `_source`
+        let includes = includes.or(source.filter(Value::is_array));
+        let mut filter = Map::new();
+        if let Some(includes) = includes {
+            filter.insert("includes".to_string(), includes);
+        }
+        if let Some(excludes) = excludes {
+            filter.insert("excludes".to_string(), excludes);
+        }
+        Some(Value::Object(filter))
+    }
+
+    /// Parses the documents of an `_mget` body, which either lists `ids` of
+    /// the index of the request or `docs` with their own index.
+    pub fn parse_mget(
+        &self,
+        index: &str,
+        body: &Value,
+        source: Option<&Value>,
+    ) -> Result<Vec<DocumentRequest>, InvalidMgetRequest> {
+        let request = |index: &str, id: &str| DocumentRequest {
+            index: index.to_string(),
+            id: id.to_string(),
+            source: source.cloned(),
+            routing: None,
+        };
+
+        let documents = match (body.get("ids"), body.get("docs")) {
+            (Some(Value::Array(ids)), None) => ids
+                .iter()
+                .map(|id| match id {
+                    Value::String(id) => Ok(request(index, id)),
+                    Value::Number(id) => Ok(request(index, &id.to_string())),
+                    _ => Err(InvalidMgetRequest("ids must be strings".to_string())),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            (None, Some(Value::Array(docs))) => docs
+                .iter()
+                .map(|doc| self.parse_mget_doc(index, doc, source))
+                .collect::<Result<Vec<_>, _>>()?,
+            (None, None) => Vec::new(),
+            _ => {
+                return Err(InvalidMgetRequest(
+                    "either [ids] or [docs] must be provided".to_string(),
+                ));
+            }
+        };
+
+        if documents.is_empty() {
+            return Err(InvalidMgetRequest("no documents to get".to_string()));
+        }
+        Ok(documents)
+    }
+
+    fn parse_mget_doc(
+        &self,
+        index: &str,
+        doc: &Value,
+        source: Option<&Value>,
+    ) -> Result<DocumentRequest, InvalidMgetRequest> {
+        let Some(object) = doc.as_object() else {
+            return Err(InvalidMgetRequest("docs must be objects".to_string()));
+        };
+        if let Some(key) = object
+            .keys()
+            .find(|key| !MGET_DOC_KEYS.contains(&key.as_str()))
+        {
+            return Err(InvalidMgetRequest(format!("unsupported key [{}]", key)));
+        }
+        let id = match object.get("_id") {
+            Some(Value::String(id)) => id.clone(),
+            Some(Value::Number(id)) => id.to_string(),
+            _ => return Err(InvalidMgetRequest("id is missing".to_string())),
+        };
+
+        Ok(DocumentRequest {
+            index: object
+                .get("_index")
+                .and_then(Value::as_str)
+                .unwrap_or(index)
+                .to_string(),
+            id,
+            source: object.get("_source").or(source).cloned(),
+            routing: object
+                .get("routing")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        })
+    }
+
+    /// Builds the `ids` search fetching a single document.
+    pub fn search_body(&self, document: &DocumentRequest) -> Value {
+        let mut body = json!({
+            "query": { "ids": { "values": [document.id] } },
+            "size": 1,
+            "version": true,
+            "seq_no_primary_term": true,
+        });
+        if let Some(source) = &document.source {
+            body["_source"] = source.clone();
+        }
+        body
+    }
+
+    /// Builds the `_msearch` header line fetching a single document.
+    pub fn search_header(&self, document: &DocumentRequest, params: &SearchParams) -> Value {
+        let mut header = json!({ "index": document.index });
+        for (name, value) in params {
+            header[name] = Value::String(value.clone());
+        }
+        if let Some(routing) = &document.routing {
+            header["routing"] = Value::String(routing.clone());
+        }
+        header
+    }
+
+    /// Converts the response of an `ids` search into a get response, or
+    /// `None` if the document was not found.
+    pub fn document_from_search(&self, response: &Value) -> Option<Value> {
+        let hit = response.pointer("/hits/hits/0")?.as_object()?;
+
+        let mut document: Map<String, Value> = DOCUMENT_METADATA
+            .iter()
+            .filter_map(|key| hit.get(*key).map(|value| (key.to_string(), value.clone())))
+            .collect();
+        document.insert("found".to_string(), Value::Bool(true));
+        for key in ["_source", "fields"] {
+            if let Some(value) = hit.get(key) {
+                document.insert(key.to_string(), value.clone());
+            }
+        }
+        Some(Value::Object(document))
+    }
+
+    /// The get response of a document that does not exist or is excluded
+    /// by the security filter.
+    pub fn not_found(&self, document: &DocumentRequest) -> Value {
+        json!({
+            "_index": document.index,
+            "_id": document.id,
+            "found": false,
+        })
+    }
+
+    /// The error returned by the `_source` API for missing documents.
+    pub fn source_not_found(&self, document: &DocumentRequest) -> Value {
+        json!({
+            "error": {
+                "type": "resource_not_found_exception",
+                "reason": format!("Document not found [{}]/[{}]", document.index, document.id),
+            },
+            "status": StatusCode::NOT_FOUND.as_u16(),
+        })
+    }
+}
+
+fn split_list(value: &str) -> Value {
+    Value::Array(
+        value
+            .split(',')
+            .map(|field| Value::String(field.trim().to_string()))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> SearchParams {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_take_source_filter() {
+        let service = DocumentService::new();
+
+        let mut request = params(&[("_source", "false"), ("routing", "a")]);
+        assert_eq!(service.take_source_filter(&mut request), Some(json!(false)));
+        assert_eq!(request, params(&[("routing", "a")]));
+
+        let mut request = params(&[("_source", "title,year")]);
+        assert_eq!(
+            service.take_source_filter(&mut request),
+            Some(json!(["title", "year"]))
+        );
+
+        let mut request = params(&[
+            ("_source_includes", "user.*"),
+            ("_source_excludes", "user.email"),
+        ]);
+        assert_eq!(
+            service.take_source_filter(&mut request),
+            Some(json!({ "includes": ["user.*"], "excludes": ["user.email"] }))
+        );
+
+        assert_eq!(service.take_source_filter(&mut params(&[])), None);
+    }
+
+    #[test]
+    fn test_parse_mget() {
+        let service = DocumentService::new();
+
+        let documents = service
+            .parse_mget("movies", &json!({ "ids": ["1", 2] }), None)
+            .unwrap();
+        assert_eq!(
+            documents
+                .iter()
+                .map(|doc| doc.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["1", "2"]
+        );
+
+        let documents = service
+            .parse_mget(
+                "movies",
+                &json!({ "docs": [{ "_index": "books", "_id": "3", "_source": false, "routing": "r" }] }),
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            documents,
+            vec![DocumentRequest {
+                index: "books".to_string(),
+                id: "3".to_string(),
+                source: Some(json!(false)),
+                routing: Some("r".to_string()),
+            }]
+        );
+
+        assert!(
+            service
+                .parse_mget("movies", &json!({ "ids": [] }), None)
+                .is_err()
+        );
+        assert!(
+            service
+                .parse_mget(
+                    "movies",
+                    &json!({ "docs": [{ "_id": "1", "stored_fields": ["a"] }] }),
+                    None
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_document_from_search() {
+        let service = DocumentService::new();
+        let response = json!({
+            "hits": { "hits": [{
+                "_index": "movies",
+                "_id": "1",
+                "_version": 2,
+                "_seq_no": 5,
+                "_primary_term": 1,
+                "_score": 1.0,
+                "_source": { "title": "Rust" }
+            }]}
+        });
+
+        assert_eq!(
+            service.document_from_search(&response),
+            Some(json!({
+                "_index": "movies",
+                "_id": "1",
+                "_version": 2,
+                "_seq_no": 5,
+                "_primary_term": 1,
+                "found": true,
+                "_source": { "title": "Rust" }
+            }))
+        );
+        assert_eq!(
+            service.document_from_search(&json!({ "hits": { "hits": [] } })),
+            None
+        );
+    }
+}
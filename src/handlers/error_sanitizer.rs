@@ -0,0 +1,268 @@
+use serde_json::{Value, json};
+
+use crate::handlers::query_inspector::QueryInspectorService;
+
+/// Replacement for filter values found in upstream error messages.
+pub const FILTERED: &str = "[filtered]";
+
+/// Minimum length of a filter value to be scrubbed from error messages, so
+/// that short values do not mangle unrelated text.
+const MIN_SECRET_LENGTH: usize = 3;
+
+/// Keys of upstream error objects that are dropped entirely.
+const DROPPED_KEYS: &[&str] = &["stack_trace", "header"];
+
+/// Broad classification of an upstream OpenSearch error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamErrorKind {
+    /// The request or its query could not be parsed or executed
+    InvalidQuery,
+    /// The index or resource does not exist
+    NotFound,
+    /// OpenSearch refused the request
+    Unauthorized,
+    /// OpenSearch is overloaded or unavailable
+    Unavailable,
+    /// Any other failure
+    Internal,
+}
+
+impl UpstreamErrorKind {
+    /// Classifies an error by its HTTP status, falling back to the error
+    /// type if the status is missing.
+    pub fn classify(error_type: &str, status: Option<u64>) -> Self {
+        match status {
+            Some(400) => Self::InvalidQuery,
+            Some(401 | 403) => Self::Unauthorized,
+            Some(404) => Self::NotFound,
+            Some(429 | 503 | 504) => Self::Unavailable,
+            Some(_) => Self::Internal,
+            None => match error_type {
+                "index_not_found_exception" | "resource_not_found_exception" => Self::NotFound,
+                "security_exception" => Self::Unauthorized,
+                t if t.contains("pars") || t.contains("illegal_argument") => Self::InvalidQuery,
+                t if t.contains("query_shard") || t.contains("validation") => Self::InvalidQuery,
+                _ => Self::Internal,
+            },
+        }
+    }
+
+    /// Reason used when nothing of the upstream reason can be kept.
+    fn generic_reason(&self) -> &'static str {
+        match self {
+            Self::InvalidQuery => "the search request could not be executed",
+            Self::NotFound => "the requested resource does not exist",
+            Self::Unauthorized => "the request was refused by the cluster",
+            Self::Unavailable => "the cluster is currently unavailable",
+            Self::Internal => "an internal error occurred",
+        }
+    }
+}
+
+/// A service sanitizing upstream OpenSearch errors before they are returned
+/// to clients.
+///
+/// Error bodies may contain the rewritten query, including the injected
+/// security filter and its claim values. Query dumps are cut from every
+/// reason and any remaining filter value is replaced, while the original
+/// error is logged internally.
+#[derive(Clone)]
+pub struct ErrorSanitizerService;
+
+impl ErrorSanitizerService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Returns whether a response body is an OpenSearch error.
+    pub fn is_error(&self, response: &Value) -> bool {
+        response.get("error").is_some()
+    }
+
+    /// Sanitizes an OpenSearch error body, scrubbing any trace of the given
+    /// security filter.
+    pub fn sanitize(&self, mut response: Value, filter: &Value) -> Value {
+        let status = response.get("status").and_then(Value::as_u64);
+        let error_type = response
+            .pointer("/error/type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let kind = UpstreamErrorKind::classify(error_type, status);
+        tracing::error!(?kind, "Upstream OpenSearch error: {}", response);
+
+        let secrets = filter_secrets(filter);
+        let mut error = response["error"].take();
+        match &mut error {
+            // Legacy errors are plain strings
+            Value::String(reason) => {
+                error = json!({
+                    "type": "exception",
+                    "reason": sanitize_text(reason, &secrets, kind),
+                })
+            }
+            value => sanitize_value(value, &secrets, kind),
+        }
+
+        let mut sanitized = json!({ "error": error });
+        if let Some(status) = status {
+            sanitized["status"] = json!(status);
+        }
+        sanitized
+    }
+}
+
+/// Collects the values and field names of a security filter that must not
+/// appear in error messages or debugging output.
+pub(crate) fn filter_secrets(filter: &Value) -> Vec<String> {
+    fn collect_values(value: &Value, secrets: &mut Vec<String>) {
+        match value {
+            Value::String(s) => secrets.push(s.clone()),
+            Value::Number(n) => secrets.push(n.to_string()),
+            Value::Array(items) => items.iter().for_each(|item| collect_values(item, secrets)),
+            Value::Object(object) => object
+                .values()
+                .for_each(|child| collect_values(child, secrets)),
+            _ => {}
+        }
+    }
+
+    let mut secrets = QueryInspectorService::new().referenced_fields(&json!({ "query": filter }));
+    collect_values(filter, &mut secrets);
+    secrets.retain(|secret| secret.len() >= MIN_SECRET_LENGTH);
+    // Replace longer secrets first so that overlapping values are fully scrubbed
+    secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    secrets.dedup();
+    secrets
+}
+
+fn sanitize_value(value: &mut Value, secrets: &[String], kind: UpstreamErrorKind) {
+    match value {
+        Value::Object(object) => {
+            object.retain(|key, _| !DROPPED_KEYS.contains(&key.as_str()));
+            for child in object.values_mut() {
+                sanitize_value(child, secrets, kind);
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| sanitize_value(item, secrets, kind)),
+        Value::String(text) => *text = sanitize_text(text, secrets, kind),
+        _ => {}
+    }
+}
+
+/// Cuts any embedded query dump from a message and replaces remaining
+/// filter values.
+fn sanitize_text(text: &str, secrets: &[String], kind: UpstreamErrorKind) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let text = match text.find('{') {
+        Some(position) => text[..position]
+            .trim_end()
+            .trim_end_matches(':')
+            .to_string(),
+        None => text.to_string(),
+    };
+    let text = scrub(&text, secrets);
+
+    if text.is_empty() {
+        kind.generic_reason().to_string()
+    } else {
+        text
+    }
+}
+
+/// Replaces every filter value in a text.
+pub(crate) fn scrub(text: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret.as_str(), FILTERED)
+    })
+}
+
+/// Returns whether a text mentions any filter value.
+pub(crate) fn mentions_secret(text: &str, secrets: &[String]) -> bool {
+    secrets.iter().any(|secret| text.contains(secret.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            UpstreamErrorKind::classify("parsing_exception", Some(400)),
+            UpstreamErrorKind::InvalidQuery
+        );
+        assert_eq!(
+            UpstreamErrorKind::classify("index_not_found_exception", None),
+            UpstreamErrorKind::NotFound
+        );
+        assert_eq!(
+            UpstreamErrorKind::classify("es_rejected_execution_exception", Some(429)),
+            UpstreamErrorKind::Unavailable
+        );
+    }
+
+    #[test]
+    fn test_sanitize_strips_filter_from_error() {
+        let service = ErrorSanitizerService::new();
+        let filter = json!({ "term": { "tenant.keyword": "acme-corp" } });
+        let response = json!({
+            "error": {
+                "root_cause": [{
+                    "type": "query_shard_exception",
+                    "reason": "failed to create query: {\"bool\":{\"filter\":{\"term\":{\"tenant.keyword\":\"acme-corp\"}}}}",
+                    "index": "movies"
+                }],
+                "type": "search_phase_execution_exception",
+                "reason": "all shards failed",
+                "caused_by": {
+                    "type": "illegal_argument_exception",
+                    "reason": "no value for tenant.keyword matching acme-corp",
+                    "stack_trace": "java.lang.IllegalArgumentException..."
+                }
+            },
+            "status": 400
+        });
+
+        let result = service.sanitize(response, &filter);
+
+        assert_eq!(
+            result,
+            json!({
+                "error": {
+                    "root_cause": [{
+                        "type": "query_shard_exception",
+                        "reason": "failed to create query",
+                        "index": "movies"
+                    }],
+                    "type": "search_phase_execution_exception",
+                    "reason": "all shards failed",
+                    "caused_by": {
+                        "type": "illegal_argument_exception",
+                        "reason": "no value for [filtered] matching [filtered]"
+                    }
+                },
+                "status": 400
+            })
+        );
+    }
+
+    #[test]
+    fn test_sanitize_replaces_empty_reason() {
+        let service = ErrorSanitizerService::new();
+        let response = json!({ "error": "{\"query\":{}}", "status": 500 });
+
+        let result = service.sanitize(response, &json!({ "match_all": {} }));
+
+        assert_eq!(
+            result,
+            json!({
+                "error": { "type": "exception", "reason": "an internal error occurred" },
+                "status": 500
+            })
+        );
+    }
+}
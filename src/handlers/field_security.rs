@@ -0,0 +1,645 @@
+use serde_json::{Map, Value, json};
+
+use crate::auth::identity::Identity;
+use crate::handlers::search_response::for_each_hit;
+use crate::models::policy::{FieldSecurity, Policy, RolePolicy};
+
+/// Metadata fields of every document, which are always readable.
+const METADATA_FIELDS: &[&str] = &[
+    "_doc",
+    "_id",
+    "_ignored",
+    "_index",
+    "_nested",
+    "_primary_term",
+    "_routing",
+    "_score",
+    "_seq_no",
+    "_shard_doc",
+    "_source",
+    "_version",
+];
+
+/// The effective field level security of a caller.
+///
+/// Combines the field security of every role the caller holds: a field is
+/// readable if at least one role permits it. Roles without field security
+/// make the caller unrestricted, while a caller without any known role may
+/// read no fields at all.
+#[derive(Debug, Clone, Default)]
+pub struct FieldPolicy {
+    unrestricted: bool,
+    rules: Vec<FieldSecurity>,
+}
+
+impl FieldPolicy {
+    pub fn from_roles(roles: &[&RolePolicy]) -> Self {
+        let unrestricted = roles.iter().any(|role| role.field_security.is_none());
+        let rules = roles
+            .iter()
+            .filter_map(|role| role.field_security.clone())
+            .collect();
+
+        Self {
+            unrestricted,
+            rules,
+        }
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self.unrestricted
+    }
+
+    /// Returns whether the given field path may be read.
+    ///
+    /// Metadata fields such as `_id` or `_index` are always readable, while
+    /// other fields starting with `_` are checked like any field.
+    pub fn permits(&self, field: &str) -> bool {
+        if self.unrestricted || METADATA_FIELDS.contains(&field) {
+            return true;
+        }
+        self.rules.iter().any(|rule| {
+            let allowed = rule
+                .allow
+                .as_ref()
+                .is_none_or(|allow| allow.iter().any(|p| pattern_covers(p, field)));
+            allowed && !rule.deny.iter().any(|p| pattern_covers(p, field))
+        })
+    }
+
+    /// Returns whether every field a wildcard pattern may expand to can be
+    /// read.
+    ///
+    /// Since the mapping is unknown, a wildcard is only accepted if a role
+    /// without deny patterns explicitly allows it.
+    pub fn permits_pattern(&self, pattern: &str) -> bool {
+        if !pattern.contains('*') {
+            return self.permits(pattern);
+        }
+        self.unrestricted
+            || self.rules.iter().any(|rule| {
+                rule.deny.is_empty()
+                    && rule
+                        .allow
+                        .as_ref()
+                        .is_some_and(|allow| allow.iter().any(|p| pattern_covers(p, pattern)))
+            })
+    }
+
+    /// Include patterns approximating the policy for `_source` filtering.
+    ///
+    /// Returns `None` if any role allows every field.
+    fn source_includes(&self) -> Option<Vec<String>> {
+        let mut includes = Vec::new();
+        for rule in &self.rules {
+            includes.extend(rule.allow.clone()?);
+        }
+        Some(includes)
+    }
+
+    /// Exclude patterns shared by every role, safe to send for `_source`
+    /// filtering without hiding fields another role permits.
+    fn source_excludes(&self) -> Vec<String> {
+        let Some((first, rest)) = self.rules.split_first() else {
+            return Vec::new();
+        };
+        first
+            .deny
+            .iter()
+            .filter(|p| rest.iter().all(|rule| rule.deny.contains(p)))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Matches a field path against a pattern containing `*` wildcards.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut remaining) = text.strip_prefix(prefix) else {
+        return false;
+    };
+
+    let mut parts = rest.split('*').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return remaining.ends_with(part);
+        }
+        match remaining.find(part) {
+            Some(position) => remaining = &remaining[position + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Returns whether a pattern matches the field path or one of its parents.
+pub(crate) fn pattern_covers(pattern: &str, field: &str) -> bool {
+    glob_match(pattern, field)
+        || field
+            .match_indices('.')
+            .any(|(position, _)| glob_match(pattern, &field[..position]))
+}
+
+/// Returns whether a referenced field or wildcard pattern may touch a field
+/// covered by a policy pattern. Wildcards are compared by their literal
+/// prefix, since the fields they expand to are unknown.
+pub(crate) fn pattern_overlaps(reference: &str, pattern: &str) -> bool {
+    let literal = |value: &str| value.split('*').next().unwrap_or("").to_string();
+    if !reference.contains('*') {
+        return pattern_covers(pattern, reference)
+            || literal(pattern).starts_with(&format!("{}.", reference));
+    }
+    let (reference, pattern) = (literal(reference), literal(pattern));
+    reference.starts_with(&pattern) || pattern.starts_with(&reference)
+}
+
+/// A service enforcing field level security on search requests and
+/// responses.
+///
+/// Requests are rewritten so that OpenSearch only returns permitted fields,
+/// and responses are stripped of any disallowed field as defense in depth.
+#[derive(Clone)]
+pub struct FieldSecurityService;
+
+impl FieldSecurityService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Resolves the effective field policy of the given identity.
+    pub fn policy_for(&self, policy: &Policy, identity: &Identity) -> FieldPolicy {
+        FieldPolicy::from_roles(&policy.roles_for(identity))
+    }
+
+    /// Rewrites `_source`, `docvalue_fields`, `fields`, `stored_fields` and
+    /// `highlight` of a search body so that only permitted fields are
+    /// requested.
+    pub fn restrict_request(&self, mut body: Value, policy: &FieldPolicy) -> Value {
+        if policy.is_unrestricted() {
+            return body;
+        }
+        let Some(object) = body.as_object_mut() else {
+            return body;
+        };
+
+        let source = self.restrict_source_param(object.remove("_source"), policy);
+        object.insert("_source".to_string(), source);
+
+        for key in ["docvalue_fields", "fields", "stored_fields"] {
+            if let Some(fields) = object.get_mut(key) {
+                self.retain_requested_fields(fields, policy);
+            }
+        }
+
+        if let Some(highlight_fields) = object
+            .get_mut("highlight")
+            .and_then(|highlight| highlight.get_mut("fields"))
+        {
+            self.retain_highlight_fields(highlight_fields, policy);
+        }
+
+        body
+    }
+
+    /// Removes disallowed fields from every hit of a search response,
+    /// including hits returned by `top_hits` aggregations.
+    pub fn restrict_response(&self, response: &mut Value, policy: &FieldPolicy) {
+        if policy.is_unrestricted() {
+            return;
+        }
+        for_each_hit(response, &mut |hit, path| {
+            if let Some(source) = hit.get_mut("_source").and_then(Value::as_object_mut) {
+                self.restrict_object(source, path, policy);
+            }
+            for key in ["fields", "highlight"] {
+                if let Some(fields) = hit.get_mut(key).and_then(Value::as_object_mut) {
+                    fields.retain(|field, _| policy.permits(field));
+                }
+            }
+        });
+    }
+
+    /// Removes disallowed fields from a `_field_caps` response, so that
+    /// callers cannot learn which fields they may not read.
+    pub fn restrict_field_caps(&self, response: &mut Value, policy: &FieldPolicy) {
+        if policy.is_unrestricted() {
+            return;
+        }
+        if let Some(fields) = response.get_mut("fields").and_then(Value::as_object_mut) {
+            fields.retain(|field, _| policy.permits(field));
+        }
+    }
+
+    /// Removes disallowed fields, including multi-fields, from the
+    /// `properties` of an index mapping. Object fields are kept as long as
+    /// one of their sub-fields is readable.
+    pub fn restrict_mapping(&self, mapping: &mut Value, policy: &FieldPolicy) {
+        if policy.is_unrestricted() {
+            return;
+        }
+        if let Some(properties) = mapping.get_mut("properties").and_then(Value::as_object_mut) {
+            self.restrict_properties(properties, "", policy);
+        }
+    }
+
+    fn restrict_properties(
+        &self,
+        properties: &mut Map<String, Value>,
+        prefix: &str,
+        policy: &FieldPolicy,
+    ) {
+        properties.retain(|name, definition| {
+            let path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            if let Some(multi_fields) = definition.get_mut("fields").and_then(Value::as_object_mut)
+            {
+                multi_fields
+                    .retain(|sub_field, _| policy.permits(&format!("{}.{}", path, sub_field)));
+            }
+            match definition
+                .get_mut("properties")
+                .and_then(Value::as_object_mut)
+            {
+                Some(children) => {
+                    self.restrict_properties(children, &path, policy);
+                    !children.is_empty() || policy.permits(&path)
+                }
+                None => policy.permits(&path),
+            }
+        });
+    }
+
+    fn restrict_source_param(&self, source: Option<Value>, policy: &FieldPolicy) -> Value {
+        let policy_excludes = policy.source_excludes();
+        let (includes, mut requested_excludes) = match source {
+            Some(Value::Bool(false)) => return Value::Bool(false),
+            None | Some(Value::Bool(true)) => (None, Vec::new()),
+            Some(Value::String(field)) => (Some(json!([field])), Vec::new()),
+            Some(Value::Array(fields)) => (Some(Value::Array(fields)), Vec::new()),
+            Some(Value::Object(mut object)) => {
+                let includes = object.remove("includes").or(object.remove("include"));
+                let excludes = match object.remove("excludes").or(object.remove("exclude")) {
+                    Some(Value::Array(values)) => values,
+                    Some(value @ Value::String(_)) => vec![value],
+                    _ => Vec::new(),
+                };
+                (includes, excludes)
+            }
+            Some(other) => (Some(other), Vec::new()),
+        };
+
+        // OpenSearch reads empty includes as every field, so a caller who may
+        // read no field is sent no source at all
+        let includes = includes
+            .filter(|includes| includes.as_array().is_none_or(|fields| !fields.is_empty()))
+            .or_else(|| policy.source_includes().map(|fields| json!(fields)))
+            .unwrap_or_else(|| json!(["*"]));
+        if includes.as_array().is_some_and(Vec::is_empty) {
+            return Value::Bool(false);
+        }
+        requested_excludes.extend(policy_excludes.into_iter().map(Value::String));
+
+        json!({
+            "includes": includes,
+            "excludes": requested_excludes,
+        })
+    }
+
+    /// Keeps only permitted entries of a `fields` style parameter. Wildcard
+    /// entries are kept and enforced on the response instead.
+    fn retain_requested_fields(&self, fields: &mut Value, policy: &FieldPolicy) {
+        if let Value::String(field) = fields {
+            *fields = json!([field.clone()]);
+        }
+        if let Some(entries) = fields.as_array_mut() {
+            entries.retain(|entry| {
+                let field = entry
+                    .as_str()
+                    .or_else(|| entry.get("field").and_then(Value::as_str));
+                field.is_none_or(|field| field.contains('*') || policy.permits(field))
+            });
+        }
+    }
+
+    fn retain_highlight_fields(&self, fields: &mut Value, policy: &FieldPolicy) {
+        let permitted = |field: &String| field.contains('*') || policy.permits(field);
+        match fields {
+            Value::Object(object) => object.retain(|field, _| permitted(field)),
+            Value::Array(entries) => entries.retain(|entry| {
+                entry
+                    .as_object()
+                    .is_none_or(|object| object.keys().all(permitted))
+            }),
+            _ => {}
+        }
+    }
+
+    /// Recursively removes disallowed fields from a `_source` object.
+    fn restrict_object(&self, object: &mut Map<String, Value>, prefix: &str, policy: &FieldPolicy) {
+        object.retain(|key, value| {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            self.restrict_value(value, &path, policy)
+        });
+    }
+
+    /// Restricts a single `_source` value and returns whether it should be
+    /// kept.
+    fn restrict_value(&self, value: &mut Value, path: &str, policy: &FieldPolicy) -> bool {
+        match value {
+            Value::Object(object) if !object.is_empty() => {
+                self.restrict_object(object, path, policy);
+                !object.is_empty()
+            }
+            Value::Array(items) if items.iter().any(Value::is_object) => {
+                items.retain_mut(|item| self.restrict_value(item, path, policy));
+                !items.is_empty()
+            }
+            _ => policy.permits(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn field_policy(field_security: Value) -> FieldPolicy {
+        let role: RolePolicy =
+            serde_json::from_value(json!({ "field_security": field_security })).unwrap();
+        FieldPolicy::from_roles(&[&role])
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("salary", "salary"));
+        assert!(glob_match("user.*", "user.email"));
+        assert!(glob_match("*_id", "tenant_id"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("user.*", "username"));
+        assert!(!glob_match("salary", "salary2"));
+    }
+
+    #[test]
+    fn test_pattern_covers_sub_fields() {
+        assert!(pattern_covers("user", "user.email"));
+        assert!(pattern_covers("name", "name.keyword"));
+        assert!(!pattern_covers("user", "username"));
+    }
+
+    #[test]
+    fn test_pattern_overlaps() {
+        assert!(pattern_overlaps("ssn", "ssn"));
+        assert!(pattern_overlaps("user.email", "user"));
+        assert!(pattern_overlaps("user", "user.email"));
+        assert!(pattern_overlaps("*", "ssn"));
+        assert!(pattern_overlaps("user.*", "user.email"));
+        assert!(pattern_overlaps("s*", "ssn"));
+        assert!(!pattern_overlaps("title*", "ssn"));
+        assert!(!pattern_overlaps("username", "user"));
+    }
+
+    #[test]
+    fn test_permits_with_allow_and_deny() {
+        let policy = field_policy(json!({ "allow": ["title", "user"], "deny": ["user.email"] }));
+
+        assert!(policy.permits("title"));
+        assert!(policy.permits("user.name"));
+        assert!(policy.permits("_id"));
+        assert!(!policy.permits("_salary"));
+        assert!(!policy.permits("user.email"));
+        assert!(!policy.permits("salary"));
+    }
+
+    #[test]
+    fn test_permits_pattern() {
+        let policy = field_policy(json!({ "allow": ["title*", "user"] }));
+        assert!(policy.permits_pattern("title*"));
+        assert!(policy.permits_pattern("user.*"));
+        assert!(!policy.permits_pattern("*"));
+
+        let policy = field_policy(json!({ "deny": ["salary", "_internal"] }));
+        assert!(!policy.permits_pattern("*"));
+        assert!(policy.permits_pattern("title"));
+        assert!(!policy.permits_pattern("_internal"));
+    }
+
+    #[test]
+    fn test_permits_is_union_of_roles() {
+        let restricted: RolePolicy =
+            serde_json::from_value(json!({ "field_security": { "deny": ["salary"] } })).unwrap();
+        let payroll: RolePolicy =
+            serde_json::from_value(json!({ "field_security": { "allow": ["salary"] } })).unwrap();
+
+        let policy = FieldPolicy::from_roles(&[&restricted, &payroll]);
+        assert!(policy.permits("salary"));
+        assert!(policy.permits("title"));
+
+        let unrestricted = RolePolicy::default();
+        assert!(FieldPolicy::from_roles(&[&restricted, &unrestricted]).is_unrestricted());
+        assert!(!FieldPolicy::from_roles(&[]).permits("title"));
+    }
+
+    #[test]
+    fn test_restrict_request_rewrites_field_parameters() {
+        let service = FieldSecurityService::new();
+        let policy = field_policy(json!({ "deny": ["salary", "email"] }));
+        let body = json!({
+            "query": { "match_all": {} },
+            "_source": { "excludes": ["notes"] },
+            "docvalue_fields": ["salary", { "field": "year" }],
+            "fields": ["email", "title*"],
+            "stored_fields": "salary",
+            "highlight": { "fields": { "email": {}, "title": {} } }
+        });
+
+        let result = service.restrict_request(body, &policy);
+
+        assert_eq!(
+            result,
+            json!({
+                "query": { "match_all": {} },
+                "_source": { "includes": ["*"], "excludes": ["notes", "salary", "email"] },
+                "docvalue_fields": [{ "field": "year" }],
+                "fields": ["title*"],
+                "stored_fields": [],
+                "highlight": { "fields": { "title": {} } }
+            })
+        );
+    }
+
+    #[test]
+    fn test_restrict_request_uses_allow_list_as_includes() {
+        let service = FieldSecurityService::new();
+        let policy = field_policy(json!({ "allow": ["title", "year"] }));
+
+        let result = service.restrict_request(json!({ "query": { "match_all": {} } }), &policy);
+        assert_eq!(
+            result["_source"],
+            json!({ "includes": ["title", "year"], "excludes": [] })
+        );
+
+        let result = service.restrict_request(json!({ "_source": false }), &policy);
+        assert_eq!(result["_source"], json!(false));
+
+        let result = service.restrict_request(json!({ "_source": [] }), &policy);
+        assert_eq!(
+            result["_source"],
+            json!({ "includes": ["title", "year"], "excludes": [] })
+        );
+
+        let result = service.restrict_request(json!({}), &FieldPolicy::from_roles(&[]));
+        assert_eq!(result["_source"], json!(false));
+    }
+
+    #[test]
+    fn test_restrict_response_strips_disallowed_fields() {
+        let service = FieldSecurityService::new();
+        let policy = field_policy(json!({ "deny": ["salary", "*email"] }));
+        let mut response = json!({
+            "hits": { "hits": [{
+                "_id": "1",
+                "_source": {
+                    "title": "Rust",
+                    "salary": 100,
+                    "user": { "name": "alice", "email": "a@example.com" },
+                    "contacts": [{ "user": { "email": "b@example.com" } }]
+                },
+                "fields": { "salary": [100], "title.keyword": ["Rust"] },
+                "highlight": { "user.email": ["<em>a</em>"] }
+            }]}
+        });
+
+        service.restrict_response(&mut response, &policy);
+
+        assert_eq!(
+            response,
+            json!({
+                "hits": { "hits": [{
+                    "_id": "1",
+                    "_source": { "title": "Rust", "user": { "name": "alice" } },
+                    "fields": { "title.keyword": ["Rust"] },
+                    "highlight": {}
+                }]}
+            })
+        );
+    }
+
+    #[test]
+    fn test_restrict_response_strips_nested_inner_hits() {
+        let service = FieldSecurityService::new();
+        let policy = field_policy(json!({ "deny": ["comments.email", "comments.replies.email"] }));
+        let inner_hit = |nested: Value| {
+            json!({ "hits": { "hits": [{
+                "_nested": nested,
+                "_source": { "text": "Great", "email": "a@example.com" }
+            }]}})
+        };
+        let mut response = json!({
+            "hits": { "hits": [{
+                "_source": { "title": "Rust" },
+                "inner_hits": {
+                    "comments": inner_hit(json!({ "field": "comments", "offset": 0 })),
+                    "replies": inner_hit(json!({
+                        "field": "comments",
+                        "offset": 0,
+                        "_nested": { "field": "replies", "offset": 1 }
+                    }))
+                }
+            }]}
+        });
+
+        service.restrict_response(&mut response, &policy);
+
+        for name in ["comments", "replies"] {
+            assert_eq!(
+                response["hits"]["hits"][0]["inner_hits"][name]["hits"]["hits"][0]["_source"],
+                json!({ "text": "Great" })
+            );
+        }
+    }
+
+    #[test]
+    fn test_restrict_response_strips_top_hits() {
+        let service = FieldSecurityService::new();
+        let policy = field_policy(json!({ "deny": ["salary"] }));
+        let mut response = json!({
+            "aggregations": { "by_genre": { "buckets": [{
+                "key": "Sci-Fi",
+                "top": { "hits": { "hits": [{ "_source": { "title": "Rust", "salary": 1 } }] } }
+            }]}}
+        });
+
+        service.restrict_response(&mut response, &policy);
+
+        assert_eq!(
+            response.pointer("/aggregations/by_genre/buckets/0/top/hits/hits/0/_source"),
+            Some(&json!({ "title": "Rust" }))
+        );
+    }
+
+    #[test]
+    fn test_restrict_field_caps() {
+        let service = FieldSecurityService::new();
+        let policy = field_policy(json!({ "deny": ["salary", "user.email"] }));
+        let mut response = json!({
+            "indices": ["employees"],
+            "fields": {
+                "_id": { "_id": { "type": "_id" } },
+                "salary": { "long": { "type": "long" } },
+                "user.email": { "keyword": { "type": "keyword" } },
+                "user.name": { "keyword": { "type": "keyword" } }
+            }
+        });
+
+        service.restrict_field_caps(&mut response, &policy);
+
+        assert_eq!(
+            response,
+            json!({
+                "indices": ["employees"],
+                "fields": {
+                    "_id": { "_id": { "type": "_id" } },
+                    "user.name": { "keyword": { "type": "keyword" } }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_restrict_mapping() {
+        let service = FieldSecurityService::new();
+        let policy = field_policy(json!({ "allow": ["title", "user.name"] }));
+        let mut mapping = json!({
+            "properties": {
+                "salary": { "type": "long" },
+                "title": { "type": "text", "fields": { "keyword": { "type": "keyword" } } },
+                "user": { "properties": {
+                    "email": { "type": "keyword" },
+                    "name": { "type": "keyword" }
+                } }
+            }
+        });
+
+        service.restrict_mapping(&mut mapping, &policy);
+
+        assert_eq!(
+            mapping,
+            json!({
+                "properties": {
+                    "title": { "type": "text", "fields": { "keyword": { "type": "keyword" } } },
+                    "user": { "properties": { "name": { "type": "keyword" } } }
+                }
+            })
+        );
+    }
+}
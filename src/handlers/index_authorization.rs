@@ -0,0 +1,297 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+use crate::auth::identity::Identity;
+use crate::handlers::field_security::glob_match;
+use crate::models::policy::{Policy, RolePolicy};
+
+/// Error returned when a request targets an index the caller may not
+/// access.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForbiddenIndex(pub String);
+
+impl IntoResponse for ForbiddenIndex {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected request targeting forbidden index '{}'", self.0);
+
+        (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": {
+                    "type": "security_exception",
+                    "reason": format!("no permissions for index [{}]", self.0),
+                },
+                "status": StatusCode::FORBIDDEN.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// The effective index permissions of a caller for one kind of access.
+///
+/// Permissions are the union of every role of the caller. A role without
+/// an index list grants access to every index, while a caller without any
+/// known role may access no index. Reserved index prefixes are never
+/// permitted, whatever the roles grant.
+#[derive(Debug, Clone, Default)]
+pub struct IndexPolicy {
+    unrestricted: bool,
+    patterns: Vec<String>,
+    reserved: Vec<String>,
+}
+
+impl IndexPolicy {
+    /// Builds the read permissions of the given roles.
+    pub fn readable(roles: &[&RolePolicy]) -> Self {
+        Self::from_lists(roles.iter().map(|role| role.indices.read.as_deref()))
+    }
+
+    /// Builds the write permissions of the given roles.
+    pub fn writable(roles: &[&RolePolicy]) -> Self {
+        Self::from_lists(roles.iter().map(|role| Some(role.indices.write.as_slice())))
+    }
+
+    fn from_lists<'a>(lists: impl Iterator<Item = Option<&'a [String]>>) -> Self {
+        let mut policy = Self::default();
+        for list in lists {
+            match list {
+                None => policy.unrestricted = true,
+                Some(patterns) => policy.patterns.extend(patterns.iter().cloned()),
+            }
+        }
+        policy
+    }
+
+    /// Denies every index starting with the given prefix.
+    pub fn reserve(&mut self, prefix: &str) {
+        self.reserved.push(prefix.to_string());
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self.unrestricted && self.reserved.is_empty()
+    }
+
+    /// Returns whether the policy permits an index name or pattern. A
+    /// requested pattern is only permitted if a single allowed pattern
+    /// covers every index it could expand to, and none of them can be
+    /// reserved.
+    pub fn permits(&self, index: &str) -> bool {
+        // The literal start of a wildcard may be shorter than a reserved
+        // prefix, as in `*` or `.k*`, and still expand to reserved indices
+        let literal = index.split('*').next().unwrap_or("");
+        if self.reserved.iter().any(|prefix| {
+            index.starts_with(prefix.as_str())
+                || (index.contains('*') && prefix.starts_with(literal))
+        }) {
+            return false;
+        }
+        self.unrestricted
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, index))
+    }
+}
+
+/// A service authorizing the indices targeted by requests.
+///
+/// Index expressions are checked name by name. Wildcard expressions and
+/// `_all` are only permitted if an allowed pattern covers them entirely,
+/// and date math expressions require unrestricted access, since they can
+/// not be resolved without the cluster.
+#[derive(Clone)]
+pub struct IndexAuthorizationService;
+
+impl IndexAuthorizationService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Resolves the read permissions of the given identity.
+    pub fn read_policy_for(&self, policy: &Policy, identity: &Identity) -> IndexPolicy {
+        IndexPolicy::readable(&policy.roles_for(identity))
+    }
+
+    /// Resolves the write permissions of the given identity.
+    pub fn write_policy_for(&self, policy: &Policy, identity: &Identity) -> IndexPolicy {
+        IndexPolicy::writable(&policy.roles_for(identity))
+    }
+
+    /// Checks every index of a comma separated index expression.
+    pub fn authorize(&self, expression: &str, policy: &IndexPolicy) -> Result<(), ForbiddenIndex> {
+        if policy.is_unrestricted() {
+            return Ok(());
+        }
+        for name in expression.split(',').map(str::trim) {
+            // Exclusions only narrow an expression
+            if name.is_empty() || name.starts_with('-') {
+                continue;
+            }
+            let name = if name == "_all" { "*" } else { name };
+            if name.starts_with('<') || !policy.permits(name) {
+                return Err(ForbiddenIndex(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the index expression of a request listing indices, where a
+    /// missing expression lists every index.
+    ///
+    /// Unlike [`Self::authorize`], wildcard expressions that are not
+    /// covered by an allowed pattern are accepted, since the listed indices
+    /// can be reduced to the readable ones. Returns whether this is needed.
+    pub fn authorize_listing(
+        &self,
+        expression: Option<&str>,
+        policy: &IndexPolicy,
+    ) -> Result<bool, ForbiddenIndex> {
+        if policy.is_unrestricted() {
+            return Ok(false);
+        }
+        let mut prune = false;
+        for name in expression.unwrap_or("*").split(',').map(str::trim) {
+            if name.is_empty() || name.starts_with('-') {
+                continue;
+            }
+            let name = if name == "_all" { "*" } else { name };
+            if name.starts_with('<') {
+                return Err(ForbiddenIndex(name.to_string()));
+            }
+            if !policy.permits(name) {
+                if !name.contains('*') {
+                    return Err(ForbiddenIndex(name.to_string()));
+                }
+                prune = true;
+            }
+        }
+        Ok(prune)
+    }
+
+    /// Checks the `index` of an `_msearch` header line, which is either a
+    /// string or a list of strings.
+    pub fn authorize_header(
+        &self,
+        header: &Value,
+        policy: &IndexPolicy,
+    ) -> Result<(), ForbiddenIndex> {
+        match header.get("index") {
+            None => Ok(()),
+            Some(Value::String(expression)) => self.authorize(expression, policy),
+            Some(Value::Array(items)) => items.iter().try_for_each(|item| match item {
+                Value::String(expression) => self.authorize(expression, policy),
+                other => Err(ForbiddenIndex(other.to_string())),
+            }),
+            Some(other) => Err(ForbiddenIndex(other.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn read_policy(read: Value) -> IndexPolicy {
+        let role: RolePolicy =
+            serde_json::from_value(json!({ "indices": { "read": read } })).unwrap();
+        IndexPolicy::readable(&[&role])
+    }
+
+    #[test]
+    fn test_authorize() {
+        let service = IndexAuthorizationService::new();
+        let policy = read_policy(json!(["movies*", "books"]));
+
+        assert_eq!(service.authorize("movies", &policy), Ok(()));
+        assert_eq!(service.authorize("movies-2024,books", &policy), Ok(()));
+        assert_eq!(service.authorize("movies-*,-movies-old", &policy), Ok(()));
+        assert_eq!(
+            service.authorize("books,users", &policy),
+            Err(ForbiddenIndex("users".to_string()))
+        );
+        assert_eq!(
+            service.authorize("*", &policy),
+            Err(ForbiddenIndex("*".to_string()))
+        );
+        assert_eq!(
+            service.authorize("_all", &policy),
+            Err(ForbiddenIndex("*".to_string()))
+        );
+        assert_eq!(
+            service.authorize("<movies-{now/d}>", &policy),
+            Err(ForbiddenIndex("<movies-{now/d}>".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_authorize_listing() {
+        let service = IndexAuthorizationService::new();
+        let policy = read_policy(json!(["movies*"]));
+
+        assert_eq!(
+            service.authorize_listing(Some("movies-*"), &policy),
+            Ok(false)
+        );
+        assert_eq!(service.authorize_listing(Some("m*"), &policy), Ok(true));
+        assert_eq!(service.authorize_listing(None, &policy), Ok(true));
+        assert_eq!(
+            service.authorize_listing(Some("m*,users"), &policy),
+            Err(ForbiddenIndex("users".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_authorize_header() {
+        let service = IndexAuthorizationService::new();
+        let policy = read_policy(json!(["movies"]));
+
+        assert_eq!(service.authorize_header(&json!({}), &policy), Ok(()));
+        assert_eq!(
+            service.authorize_header(&json!({ "index": ["movies", "users"] }), &policy),
+            Err(ForbiddenIndex("users".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_policy_is_union_of_roles() {
+        let restricted: RolePolicy =
+            serde_json::from_value(json!({ "indices": { "read": ["movies"] } })).unwrap();
+        let unrestricted = RolePolicy::default();
+
+        assert!(IndexPolicy::readable(&[&restricted, &unrestricted]).is_unrestricted());
+        assert!(!IndexPolicy::readable(&[]).permits("movies"));
+        assert!(!IndexPolicy::writable(&[&restricted, &unrestricted]).permits("movies"));
+    }
+
+    #[test]
+    fn test_reserved_indices() {
+        let service = IndexAuthorizationService::new();
+        let mut policy = IndexPolicy::readable(&[&RolePolicy::default()]);
+        policy.reserve(".kibana");
+
+        assert!(!policy.is_unrestricted());
+        assert_eq!(service.authorize("movies,logs-*", &policy), Ok(()));
+        assert_eq!(
+            service.authorize(".kibana_1", &policy),
+            Err(ForbiddenIndex(".kibana_1".to_string()))
+        );
+        for expression in ["movies,*", "_all", ".k*", "*ibana*"] {
+            assert!(service.authorize(expression, &policy).is_err());
+        }
+        assert_eq!(
+            service.authorize_listing(Some("movies"), &policy),
+            Ok(false)
+        );
+        assert_eq!(service.authorize_listing(None, &policy), Ok(true));
+        assert_eq!(
+            service.authorize_listing(Some("logs-*"), &policy),
+            Ok(false)
+        );
+    }
+}
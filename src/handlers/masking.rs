@@ -0,0 +1,404 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use hmac::{Hmac, Mac};
+use serde_json::{Map, Value, json};
+use sha2::Sha256;
+
+use crate::auth::identity::Identity;
+use crate::config::Config;
+use crate::handlers::field_security::{FieldPolicy, pattern_covers, pattern_overlaps};
+use crate::handlers::query_inspector::QueryInspectorService;
+use crate::handlers::search_response::for_each_hit;
+use crate::models::policy::{MaskingAction, MaskingRule, Policy, RolePolicy};
+
+/// Marker replacing redacted values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// The effective masking rules of a caller.
+///
+/// A field is only masked if every role of the caller that may read it
+/// masks it, mirroring field level security where any role granting access
+/// wins. Roles that cannot read a field grant no access to it and do not
+/// lift its masking. The action of the first role masking a field is used.
+#[derive(Debug, Clone, Default)]
+pub struct MaskingPolicy {
+    rules: Vec<MaskingRule>,
+}
+
+impl MaskingPolicy {
+    pub fn from_roles(roles: &[&RolePolicy]) -> Self {
+        let mut rules: Vec<MaskingRule> = Vec::new();
+        for rule in roles.iter().flat_map(|role| &role.masking) {
+            let masked_by_readers = roles.iter().all(|role| {
+                role.masking.iter().any(|other| other.field == rule.field)
+                    || !FieldPolicy::from_roles(&[role]).permits_pattern(&rule.field)
+            });
+            if masked_by_readers && !rules.iter().any(|kept| kept.field == rule.field) {
+                rules.push(rule.clone());
+            }
+        }
+
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns whether a referenced field or pattern may touch a masked
+    /// field.
+    fn touches(&self, reference: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| pattern_overlaps(reference, &rule.field))
+    }
+
+    fn action_for(&self, field: &str) -> Option<&MaskingAction> {
+        self.rules
+            .iter()
+            .find(|rule| pattern_covers(&rule.field, field))
+            .map(|rule| &rule.action)
+    }
+}
+
+/// Error returned when a masked field is used in a way that could reveal
+/// its real values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaskedField(pub String);
+
+impl IntoResponse for MaskedField {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected request revealing masked field '{}'", self.0);
+
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": {
+                    "type": "security_exception",
+                    "reason": format!(
+                        "field [{}] is masked and can not be queried, sorted or aggregated",
+                        self.0
+                    ),
+                },
+                "status": StatusCode::FORBIDDEN.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A service masking field values in search responses.
+///
+/// Masking is applied to `_source`, `fields` and `highlight` of every hit,
+/// so fields can stay visible while their values are redacted, hashed,
+/// truncated or replaced. Queries, sorts and aggregations on masked fields
+/// would return or confirm the real values, so they are rejected.
+#[derive(Clone)]
+pub struct MaskingService {
+    key: Option<Arc<[u8]>>,
+}
+
+impl MaskingService {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            key: config
+                .masking_key
+                .as_ref()
+                .map(|key| Arc::from(key.as_bytes())),
+        }
+    }
+
+    /// Resolves the effective masking policy of the given identity.
+    pub fn policy_for(&self, policy: &Policy, identity: &Identity) -> MaskingPolicy {
+        MaskingPolicy::from_roles(&policy.roles_for(identity))
+    }
+
+    /// Rejects a search body referencing a masked field anywhere but in the
+    /// names of highlighted fields, whose fragments are masked in the
+    /// response. The `highlight_query` and `matched_fields` of a highlighted
+    /// field are checked, since whether a fragment is returned reveals
+    /// whether they matched.
+    pub fn check_request(&self, body: &Value, policy: &MaskingPolicy) -> Result<(), MaskedField> {
+        if policy.is_empty() {
+            return Ok(());
+        }
+        let mut inspected = body.clone();
+        if let Some(fields) = inspected.pointer_mut("/highlight/fields") {
+            // Unnamed entries keep their options but reference no field
+            let entries: Vec<Value> = match fields.take() {
+                Value::Object(entries) => entries.into_values().collect(),
+                Value::Array(items) => items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        Value::Object(entries) => Some(entries.into_values()),
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect(),
+                _ => Vec::new(),
+            };
+            *fields = entries
+                .into_iter()
+                .map(|options| json!({ "": options }))
+                .collect();
+        }
+        match QueryInspectorService::new()
+            .referenced_fields(&inspected)
+            .into_iter()
+            .find(|field| policy.touches(field))
+        {
+            Some(field) => Err(MaskedField(field)),
+            None => Ok(()),
+        }
+    }
+
+    /// Masks every hit of a search response according to the policy.
+    pub fn mask_response(&self, response: &mut Value, policy: &MaskingPolicy) {
+        if policy.is_empty() {
+            return;
+        }
+        for_each_hit(response, &mut |hit, path| {
+            if let Some(source) = hit.get_mut("_source").and_then(Value::as_object_mut) {
+                self.mask_object(source, path, policy);
+            }
+            for key in ["fields", "highlight"] {
+                if let Some(fields) = hit.get_mut(key).and_then(Value::as_object_mut) {
+                    for (field, value) in fields.iter_mut() {
+                        if let Some(action) = policy.action_for(field) {
+                            self.mask_value(value, action);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn mask_object(&self, object: &mut Map<String, Value>, prefix: &str, policy: &MaskingPolicy) {
+        for (key, value) in object.iter_mut() {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            if let Some(action) = policy.action_for(&path) {
+                self.mask_value(value, action);
+            } else if let Value::Object(child) = value {
+                self.mask_object(child, &path, policy);
+            } else if let Value::Array(items) = value {
+                for child in items.iter_mut().filter_map(Value::as_object_mut) {
+                    self.mask_object(child, &path, policy);
+                }
+            }
+        }
+    }
+
+    /// Masks a value in place. Arrays and objects have each of their
+    /// leaves masked.
+    fn mask_value(&self, value: &mut Value, action: &MaskingAction) {
+        match value {
+            Value::Null => {}
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.mask_value(item, action)),
+            Value::Object(object) => object
+                .values_mut()
+                .for_each(|child| self.mask_value(child, action)),
+            _ => *value = self.mask_scalar(value, action),
+        }
+    }
+
+    fn mask_scalar(&self, value: &Value, action: &MaskingAction) -> Value {
+        match action {
+            MaskingAction::Redact => Value::String(REDACTED.to_string()),
+            MaskingAction::Replace { value } => value.clone(),
+            MaskingAction::Hash => match self.hash(&scalar_to_string(value)) {
+                Some(hash) => Value::String(hash),
+                None => {
+                    tracing::warn!("No MASKING_KEY configured, redacting hashed field instead");
+                    Value::String(REDACTED.to_string())
+                }
+            },
+            MaskingAction::Truncate {
+                keep_first,
+                keep_last,
+            } => Value::String(truncate(&scalar_to_string(value), *keep_first, *keep_last)),
+        }
+    }
+
+    /// Returns the hex encoded HMAC-SHA256 of the input, or `None` if no
+    /// key is configured.
+    fn hash(&self, input: &str) -> Option<String> {
+        self.key.as_ref().map(|key| hmac_hex(key, input))
+    }
+}
+
+/// Returns the hex encoded HMAC-SHA256 of the input under the given key.
+pub(crate) fn hmac_hex(key: &[u8], input: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(input.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub(crate) fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Keeps the first and last characters of a value and masks the rest with
+/// `*`. Values too short to hide anything are masked entirely.
+fn truncate(value: &str, keep_first: usize, keep_last: usize) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= keep_first + keep_last {
+        return "*".repeat(chars.len());
+    }
+    let hidden = chars.len() - keep_first - keep_last;
+
+    chars[..keep_first]
+        .iter()
+        .chain(std::iter::repeat_n(&'*', hidden))
+        .chain(&chars[chars.len() - keep_last..])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn service(key: Option<&str>) -> MaskingService {
+        MaskingService {
+            key: key.map(|key| Arc::from(key.as_bytes())),
+        }
+    }
+
+    fn masking_policy(masking: Value) -> MaskingPolicy {
+        let role: RolePolicy = serde_json::from_value(json!({ "masking": masking })).unwrap();
+        MaskingPolicy::from_roles(&[&role])
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("1234567890", 0, 4), "******7890");
+        assert_eq!(truncate("1234567890", 2, 2), "12******90");
+        assert_eq!(truncate("123", 0, 4), "***");
+    }
+
+    #[test]
+    fn test_hash_is_keyed_and_deterministic() {
+        let a = service(Some("secret")).hash("alice@example.com").unwrap();
+        let b = service(Some("secret")).hash("alice@example.com").unwrap();
+        let c = service(Some("other")).hash("alice@example.com").unwrap();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.len(), 64);
+        assert!(service(None).hash("alice@example.com").is_none());
+    }
+
+    #[test]
+    fn test_mask_response() {
+        let service = service(Some("secret"));
+        let policy = masking_policy(json!([
+            { "field": "account", "type": "truncate", "keep_last": 4 },
+            { "field": "user.email", "type": "hash" },
+            { "field": "ssn", "type": "redact" },
+            { "field": "phone", "type": "replace", "value": "hidden" }
+        ]));
+        let mut response = json!({
+            "hits": { "hits": [{
+                "_source": {
+                    "title": "Rust",
+                    "account": 12345678,
+                    "user": { "email": "a@example.com" },
+                    "ssn": ["111", "222"],
+                    "phone": "555"
+                },
+                "fields": { "account": ["12345678"] },
+                "highlight": { "ssn": ["<em>111</em>"] }
+            }]}
+        });
+
+        service.mask_response(&mut response, &policy);
+
+        let hit = &response["hits"]["hits"][0];
+        assert_eq!(hit["_source"]["title"], "Rust");
+        assert_eq!(hit["_source"]["account"], "****5678");
+        assert_eq!(
+            hit["_source"]["user"]["email"],
+            service.hash("a@example.com").unwrap()
+        );
+        assert_eq!(hit["_source"]["ssn"], json!([REDACTED, REDACTED]));
+        assert_eq!(hit["_source"]["phone"], "hidden");
+        assert_eq!(hit["fields"]["account"], json!(["****5678"]));
+        assert_eq!(hit["highlight"]["ssn"], json!([REDACTED]));
+    }
+
+    #[test]
+    fn test_check_request() {
+        let service = service(None);
+        let policy = masking_policy(json!([{ "field": "ssn", "type": "redact" }]));
+
+        let probe = json!({ "query": { "range": { "ssn": { "gte": "100" } } } });
+        assert_eq!(
+            service.check_request(&probe, &policy),
+            Err(MaskedField("ssn".to_string()))
+        );
+        let aggregated = json!({ "aggs": { "ssns": { "terms": { "field": "ssn.keyword" } } } });
+        assert!(service.check_request(&aggregated, &policy).is_err());
+        let sorted = json!({ "sort": [{ "ssn": "asc" }] });
+        assert!(service.check_request(&sorted, &policy).is_err());
+        let all_fields = json!({ "query": { "query_string": { "query": "111" } } });
+        assert!(service.check_request(&all_fields, &policy).is_err());
+
+        let highlighted = json!({
+            "query": { "match": { "title": "rust" } },
+            "highlight": { "fields": { "*": {} } }
+        });
+        assert_eq!(service.check_request(&highlighted, &policy), Ok(()));
+        let highlighted = json!({
+            "query": { "match": { "title": "rust" } },
+            "highlight": { "fields": [{ "ssn": {} }, { "title": {} }] }
+        });
+        assert_eq!(service.check_request(&highlighted, &policy), Ok(()));
+
+        let oracle = json!({
+            "query": { "match": { "title": "rust" } },
+            "highlight": { "fields": { "ssn": {
+                "highlight_query": { "prefix": { "ssn": "11" } }
+            } } }
+        });
+        assert_eq!(
+            service.check_request(&oracle, &policy),
+            Err(MaskedField("ssn".to_string()))
+        );
+        let matched = json!({
+            "query": { "match": { "title": "rust" } },
+            "highlight": { "fields": [{ "title": { "matched_fields": ["title", "ssn"] } }] }
+        });
+        assert!(service.check_request(&matched, &policy).is_err());
+    }
+
+    #[test]
+    fn test_masking_requires_every_reading_role() {
+        let masked: RolePolicy =
+            serde_json::from_value(json!({ "masking": [{ "field": "email", "type": "redact" }] }))
+                .unwrap();
+        let unmasked = RolePolicy::default();
+        let denied: RolePolicy =
+            serde_json::from_value(json!({ "field_security": { "deny": ["email"] } })).unwrap();
+
+        assert!(MaskingPolicy::from_roles(&[&masked, &unmasked]).is_empty());
+        assert!(!MaskingPolicy::from_roles(&[&masked, &masked]).is_empty());
+        // Access to the field only comes from the masking role
+        let policy = MaskingPolicy::from_roles(&[&denied, &masked]);
+        assert!(policy.action_for("email").is_some());
+        assert!(!MaskingPolicy::from_roles(&[&masked, &denied]).is_empty());
+    }
+}
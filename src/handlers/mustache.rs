@@ -0,0 +1,341 @@
+use serde_json::Value;
+
+/// Error returned for templates that cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MustacheError(pub String);
+
+/// A parsed template element.
+#[derive(Debug, Clone, PartialEq)]
+enum Node<'a> {
+    Text(&'a str),
+    /// `{{name}}`, or `{{{name}}}` and `{{&name}}` without escaping
+    Variable {
+        path: &'a str,
+        escape: bool,
+    },
+    /// `{{#name}}...{{/name}}`, or `{{^name}}...{{/name}}` if inverted
+    Section {
+        path: &'a str,
+        inverted: bool,
+        children: Vec<Node<'a>>,
+    },
+    /// `{{#toJson}}name{{/toJson}}`
+    ToJson(&'a str),
+    /// `{{#join}}name{{/join}}`, optionally with `delimiter='...'`
+    Join {
+        path: &'a str,
+        delimiter: &'a str,
+    },
+}
+
+/// Renders a mustache template with the given parameters.
+///
+/// The subset of mustache supported by OpenSearch search templates is
+/// implemented: variables with dotted paths and array indices, sections,
+/// inverted sections, comments and the `toJson` and `join` functions.
+/// Escaped variables are escaped for use inside JSON strings. Partials and
+/// delimiter changes are rejected.
+pub fn render(template: &str, params: &Value) -> Result<String, MustacheError> {
+    let mut parser = Parser {
+        template,
+        position: 0,
+    };
+    let nodes = parser.parse(None)?;
+
+    let mut output = String::with_capacity(template.len());
+    render_nodes(&nodes, &mut vec![params], &mut output);
+    Ok(output)
+}
+
+struct Parser<'a> {
+    template: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses nodes until the closing tag of the given section, or until
+    /// the end of the template at the top level.
+    fn parse(&mut self, section: Option<&str>) -> Result<Vec<Node<'a>>, MustacheError> {
+        let mut nodes = Vec::new();
+        loop {
+            let rest = &self.template[self.position..];
+            let Some(start) = rest.find("{{") else {
+                if let Some(name) = section {
+                    return Err(MustacheError(format!("unclosed section [{}]", name)));
+                }
+                if !rest.is_empty() {
+                    nodes.push(Node::Text(rest));
+                }
+                self.position = self.template.len();
+                return Ok(nodes);
+            };
+            if start > 0 {
+                nodes.push(Node::Text(&rest[..start]));
+            }
+
+            let (tag, triple) = self.read_tag(self.position + start)?;
+            if triple {
+                nodes.push(Node::Variable {
+                    path: tag,
+                    escape: false,
+                });
+                continue;
+            }
+            let (sigil, name) = match tag.chars().next() {
+                Some(sigil @ ('!' | '#' | '^' | '/' | '&' | '>' | '=')) => {
+                    (Some(sigil), tag[1..].trim())
+                }
+                _ => (None, tag),
+            };
+
+            match sigil {
+                None => nodes.push(Node::Variable {
+                    path: name,
+                    escape: true,
+                }),
+                Some('&') => nodes.push(Node::Variable {
+                    path: name,
+                    escape: false,
+                }),
+                Some('!') => {}
+                Some('/') => {
+                    return match section {
+                        Some(open) if open == name => Ok(nodes),
+                        _ => Err(MustacheError(format!("unexpected closing tag [{}]", name))),
+                    };
+                }
+                Some('#') if name == "toJson" => {
+                    nodes.push(Node::ToJson(self.function_body(name)?))
+                }
+                Some('#') if name == "join" || name.starts_with("join ") => {
+                    let delimiter = join_delimiter(name)?;
+                    let path = self.function_body(name)?;
+                    nodes.push(Node::Join { path, delimiter });
+                }
+                Some(sigil @ ('#' | '^')) => {
+                    let children = self.parse(Some(name))?;
+                    nodes.push(Node::Section {
+                        path: name,
+                        inverted: sigil == '^',
+                        children,
+                    });
+                }
+                _ => {
+                    return Err(MustacheError(format!("unsupported tag [{}]", tag)));
+                }
+            }
+        }
+    }
+
+    /// Reads the tag starting at the given offset and returns its trimmed
+    /// content and whether it is a triple mustache.
+    fn read_tag(&mut self, start: usize) -> Result<(&'a str, bool), MustacheError> {
+        let triple = self.template[start..].starts_with("{{{");
+        let (open, close) = if triple { (3, "}}}") } else { (2, "}}") };
+        let content_start = start + open;
+        let Some(length) = self.template[content_start..].find(close) else {
+            return Err(MustacheError("unclosed tag".to_string()));
+        };
+        self.position = content_start + length + close.len();
+        Ok((
+            self.template[content_start..content_start + length].trim(),
+            triple,
+        ))
+    }
+
+    /// Returns the variable name inside a function section, up to the
+    /// closing tag of the function.
+    fn function_body(&mut self, name: &str) -> Result<&'a str, MustacheError> {
+        let function = name.split_whitespace().next().unwrap_or(name);
+        let rest = &self.template[self.position..];
+        let Some(end) = rest.find("{{/") else {
+            return Err(MustacheError(format!("unclosed section [{}]", function)));
+        };
+        let body = rest[..end].trim();
+        let (closing, _) = self.read_tag(self.position + end)?;
+        if closing[1..].split_whitespace().next() != Some(function) {
+            return Err(MustacheError(format!(
+                "unexpected closing tag [{}]",
+                closing[1..].trim()
+            )));
+        }
+        Ok(body)
+    }
+}
+
+/// Reads the delimiter of a `join` function, which defaults to a comma.
+fn join_delimiter(name: &str) -> Result<&str, MustacheError> {
+    let options = name["join".len()..].trim();
+    if options.is_empty() {
+        return Ok(",");
+    }
+    options
+        .strip_prefix("delimiter=")
+        .and_then(|value| value.strip_prefix('\''))
+        .and_then(|value| value.strip_suffix('\''))
+        .ok_or_else(|| MustacheError(format!("invalid join options [{}]", options)))
+}
+
+fn render_nodes(nodes: &[Node], stack: &mut Vec<&Value>, output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Variable { path, escape } => {
+                if let Some(value) = lookup(stack, path) {
+                    let text = to_text(value);
+                    if *escape {
+                        output.push_str(&escape_json(&text));
+                    } else {
+                        output.push_str(&text);
+                    }
+                }
+            }
+            Node::ToJson(path) => {
+                if let Some(value) = lookup(stack, path) {
+                    output.push_str(&value.to_string());
+                }
+            }
+            Node::Join { path, delimiter } => match lookup(stack, path) {
+                Some(Value::Array(items)) => {
+                    let items: Vec<String> = items.iter().map(to_text).collect();
+                    output.push_str(&escape_json(&items.join(delimiter)));
+                }
+                Some(value) => output.push_str(&escape_json(&to_text(value))),
+                None => {}
+            },
+            Node::Section {
+                path,
+                inverted,
+                children,
+            } => {
+                let value = lookup(stack, path);
+                let truthy = value.is_some_and(is_truthy);
+                if *inverted {
+                    if !truthy {
+                        render_nodes(children, stack, output);
+                    }
+                    continue;
+                }
+                let items = match value {
+                    Some(Value::Array(items)) => items.iter().collect(),
+                    Some(value) if truthy => vec![value],
+                    _ => Vec::new(),
+                };
+                // Each item is the innermost context while rendering
+                for item in items {
+                    stack.push(item);
+                    render_nodes(children, stack, output);
+                    stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Resolves a dotted path, looking up its first segment from the innermost
+/// context outwards. `.` refers to the innermost context.
+fn lookup<'v>(stack: &[&'v Value], path: &str) -> Option<&'v Value> {
+    if path == "." {
+        return stack.last().copied();
+    }
+    let mut segments = path.split('.');
+    let first = segments.next()?;
+    let mut value = stack
+        .iter()
+        .rev()
+        .find_map(|context| child(context, first))?;
+    for segment in segments {
+        value = child(value, segment)?;
+    }
+    Some(value)
+}
+
+fn child<'v>(value: &'v Value, key: &str) -> Option<&'v Value> {
+    match value {
+        Value::Object(object) => object.get(key),
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(false) => false,
+        Value::Array(items) => !items.is_empty(),
+        _ => true,
+    }
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Escapes text for use inside a JSON string.
+fn escape_json(text: &str) -> String {
+    let quoted = Value::String(text.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_variables() {
+        let params = json!({ "title": "say \"hi\"", "size": 10, "user": { "tags": ["a", "b"] } });
+
+        assert_eq!(
+            render(
+                r#"{"q": "{{title}}", "size": {{size}}, "tag": "{{user.tags.1}}", "raw": "{{{title}}}"}"#,
+                &params
+            )
+            .unwrap(),
+            r#"{"q": "say \"hi\"", "size": 10, "tag": "b", "raw": "say "hi""}"#
+        );
+        assert_eq!(render("[{{missing}}]", &params).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_render_sections() {
+        let params = json!({ "genres": ["Drama", "Sci-Fi"], "from": null });
+
+        assert_eq!(
+            render(
+                r#"[{{#genres}}"{{.}}",{{/genres}}]{{#from}}x{{/from}}{{^from}}none{{/from}}{{! comment }}"#,
+                &params
+            )
+            .unwrap(),
+            r#"["Drama","Sci-Fi",]none"#
+        );
+    }
+
+    #[test]
+    fn test_render_functions() {
+        let params = json!({ "genres": ["Drama", "Sci-Fi"] });
+
+        assert_eq!(
+            render(
+                "{{#toJson}}genres{{/toJson}} {{#join}}genres{{/join}} {{#join delimiter='||'}}genres{{/join delimiter='||'}}",
+                &params
+            )
+            .unwrap(),
+            r#"["Drama","Sci-Fi"] Drama,Sci-Fi Drama||Sci-Fi"#
+        );
+    }
+
+    #[test]
+    fn test_render_rejects_invalid_templates() {
+        let params = json!({});
+
+        assert!(render("{{#open}}", &params).is_err());
+        assert!(render("{{/close}}", &params).is_err());
+        assert!(render("{{> partial}}", &params).is_err());
+        assert!(render("{{=<% %>=}}", &params).is_err());
+        assert!(render("{{unclosed", &params).is_err());
+    }
+}
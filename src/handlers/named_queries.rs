@@ -0,0 +1,456 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+use crate::auth::identity::Identity;
+use crate::models::named_query::{NamedQuery, QueryParamKind};
+use crate::models::policy::Policy;
+use crate::repositories::named_query::NamedQueryRepository;
+
+/// Units of date math and rounding, e.g. `now-1d/d`.
+const DATE_MATH_UNITS: &[char] = &['y', 'M', 'w', 'd', 'h', 'H', 'm', 's'];
+
+/// Reasons a named query request is rejected before it reaches OpenSearch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamedQueryRejection {
+    /// The query does not exist or is not allowed for the caller
+    Unknown(String),
+    /// The request body is not an object with `params`
+    InvalidBody,
+    /// A parameter without a default was not given
+    MissingParam(String),
+    /// A parameter is not declared by the query
+    UnknownParam(String),
+    /// The value of a parameter does not match its type
+    InvalidParam { name: String, reason: String },
+}
+
+impl IntoResponse for NamedQueryRejection {
+    fn into_response(self) -> Response {
+        let (status, error_type, reason) = match self {
+            Self::Unknown(name) => {
+                tracing::warn!("Rejected unknown or unlisted named query '{}'", name);
+                (
+                    StatusCode::NOT_FOUND,
+                    "resource_not_found_exception",
+                    format!("named query [{}] not found", name),
+                )
+            }
+            Self::InvalidBody => (
+                StatusCode::BAD_REQUEST,
+                "illegal_argument_exception",
+                "the request body may only contain [params], which must be an object".to_string(),
+            ),
+            Self::MissingParam(name) => (
+                StatusCode::BAD_REQUEST,
+                "illegal_argument_exception",
+                format!("missing required parameter [{}]", name),
+            ),
+            Self::UnknownParam(name) => (
+                StatusCode::BAD_REQUEST,
+                "illegal_argument_exception",
+                format!("unknown parameter [{}]", name),
+            ),
+            Self::InvalidParam { name, reason } => (
+                StatusCode::BAD_REQUEST,
+                "illegal_argument_exception",
+                format!("invalid value for parameter [{}]: {}", name, reason),
+            ),
+        };
+
+        (
+            status,
+            Json(json!({
+                "error": { "type": error_type, "reason": reason },
+                "status": status.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// The named queries a caller may run.
+#[derive(Debug, Clone, Default)]
+pub struct NamedQueryPolicy {
+    queries: Vec<String>,
+}
+
+/// A service running named queries from the catalogue.
+///
+/// Clients send only the name of a query and its parameters. Every value is
+/// validated against the declared type and bound into the search body of
+/// the query as JSON, so clients cannot inject DSL. The bound body takes
+/// the same path as any other search, including the security filter.
+#[derive(Clone)]
+pub struct NamedQueryService {
+    queries: NamedQueryRepository,
+}
+
+impl NamedQueryService {
+    pub fn new(queries: NamedQueryRepository) -> Self {
+        Self { queries }
+    }
+
+    /// Resolves the named queries of the given identity, combining the
+    /// queries of all its roles.
+    pub fn policy_for(&self, policy: &Policy, identity: &Identity) -> NamedQueryPolicy {
+        NamedQueryPolicy {
+            queries: policy
+                .roles_for(identity)
+                .into_iter()
+                .flat_map(|role| role.named_queries.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// Returns a named query the caller may run. Queries that exist but are
+    /// not allowed are reported like unknown ones.
+    pub fn query(
+        &self,
+        name: &str,
+        policy: &NamedQueryPolicy,
+    ) -> Result<&NamedQuery, NamedQueryRejection> {
+        self.queries
+            .get(name)
+            .filter(|_| policy.queries.iter().any(|query| query == name))
+            .ok_or_else(|| NamedQueryRejection::Unknown(name.to_string()))
+    }
+
+    /// Validates the parameters of a request body and binds them into the
+    /// search body of the query.
+    pub fn bind(&self, query: &NamedQuery, body: &Value) -> Result<Value, NamedQueryRejection> {
+        let empty = Map::new();
+        let given = match body.as_object() {
+            Some(object) if object.keys().all(|key| key == "params") => {
+                match object.get("params") {
+                    None => &empty,
+                    Some(Value::Object(params)) => params,
+                    Some(_) => return Err(NamedQueryRejection::InvalidBody),
+                }
+            }
+            _ => return Err(NamedQueryRejection::InvalidBody),
+        };
+        if let Some(name) = given.keys().find(|name| !query.params.contains_key(*name)) {
+            return Err(NamedQueryRejection::UnknownParam(name.clone()));
+        }
+
+        let mut values = Map::new();
+        for (name, param) in &query.params {
+            let value = given
+                .get(name)
+                .or(param.default.as_ref())
+                .ok_or_else(|| NamedQueryRejection::MissingParam(name.clone()))?;
+            validate(&param.kind, value).map_err(|reason| NamedQueryRejection::InvalidParam {
+                name: name.clone(),
+                reason,
+            })?;
+            values.insert(name.clone(), value.clone());
+        }
+
+        Ok(substitute(&query.body, &values))
+    }
+}
+
+fn validate(kind: &QueryParamKind, value: &Value) -> Result<(), String> {
+    match (kind, value) {
+        (QueryParamKind::String { max_length }, Value::String(text)) => match max_length {
+            Some(max) if text.chars().count() > *max => {
+                Err(format!("longer than {} characters", max))
+            }
+            _ => Ok(()),
+        },
+        (QueryParamKind::Number { min, max }, Value::Number(number)) => {
+            let number = number.as_f64().unwrap_or(f64::NAN);
+            if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                Err("out of range".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        (QueryParamKind::Date, Value::String(date)) if is_date(date) => Ok(()),
+        (QueryParamKind::Enum { values }, Value::String(text)) => {
+            if values.contains(text) {
+                Ok(())
+            } else {
+                Err(format!("expected one of [{}]", values.join(", ")))
+            }
+        }
+        (QueryParamKind::List { items, max_items }, Value::Array(values)) => {
+            if let Some(max) = max_items
+                && values.len() > *max
+            {
+                return Err(format!("more than {} items", max));
+            }
+            values.iter().try_for_each(|value| validate(items, value))
+        }
+        (QueryParamKind::String { .. }, _) => Err("expected a string".to_string()),
+        (QueryParamKind::Number { .. }, _) => Err("expected a number".to_string()),
+        (QueryParamKind::Date, _) => Err("expected a date".to_string()),
+        (QueryParamKind::Enum { .. }, _) => Err("expected a string".to_string()),
+        (QueryParamKind::List { .. }, _) => Err("expected a list".to_string()),
+    }
+}
+
+/// Accepts ISO 8601 dates such as `2024-01-31` or `2024-01-31T12:00:00Z`
+/// and date math relative to `now`, such as `now-7d/d`.
+fn is_date(value: &str) -> bool {
+    if let Some(math) = value.strip_prefix("now") {
+        return is_date_math(math);
+    }
+
+    let (date, time) = value.split_once('T').unwrap_or((value, ""));
+    let parts: Vec<&str> = date.split('-').collect();
+    let is_calendar_date = matches!(
+        parts.as_slice(),
+        [year, month, day]
+            if year.len() == 4
+                && month.parse::<u8>().is_ok_and(|month| (1..=12).contains(&month))
+                && day.parse::<u8>().is_ok_and(|day| (1..=31).contains(&day))
+                && year.chars().all(|c| c.is_ascii_digit())
+    );
+    is_calendar_date && (time.is_empty() || is_time_of_day(time))
+}
+
+/// Accepts a time of day with optional seconds, fraction and offset.
+fn is_time_of_day(time: &str) -> bool {
+    let (time, offset) = match time.find(['Z', '+', '-']) {
+        Some(start) => time.split_at(start),
+        None => (time, ""),
+    };
+    let time = time.split_once('.').map_or(time, |(time, fraction)| {
+        if fraction.chars().all(|c| c.is_ascii_digit()) {
+            time
+        } else {
+            ""
+        }
+    });
+    let is_clock = |value: &str, min_parts: usize| {
+        let parts: Vec<&str> = value.split(':').collect();
+        parts.len() >= min_parts
+            && parts.len() <= 3
+            && parts
+                .iter()
+                .all(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_digit()))
+    };
+
+    is_clock(time, 2) && (offset.is_empty() || offset == "Z" || is_clock(&offset[1..], 2))
+}
+
+/// Accepts the operations following `now`, e.g. `+1d-2h/d`.
+fn is_date_math(mut math: &str) -> bool {
+    while let Some(operator) = math.chars().next() {
+        let rest = &math[1..];
+        match operator {
+            '+' | '-' => {
+                let unit_start = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+                let mut units = rest[unit_start..].chars();
+                match units.next() {
+                    Some(unit) if unit_start > 0 && DATE_MATH_UNITS.contains(&unit) => {
+                        math = units.as_str();
+                    }
+                    _ => return false,
+                }
+            }
+            '/' => {
+                let mut units = rest.chars();
+                return units
+                    .next()
+                    .is_some_and(|unit| DATE_MATH_UNITS.contains(&unit))
+                    && units.as_str().is_empty();
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Replaces the `{{name}}` placeholders of a search body. A string that is
+/// a single placeholder is replaced by the JSON value of the parameter,
+/// placeholders within longer strings and object keys by its text.
+fn substitute(template: &Value, values: &Map<String, Value>) -> Value {
+    match template {
+        Value::String(text) => {
+            let name = text
+                .strip_prefix("{{")
+                .and_then(|rest| rest.strip_suffix("}}"))
+                .map(str::trim);
+            match name.and_then(|name| values.get(name)) {
+                Some(value) => value.clone(),
+                None => Value::String(interpolate(text, values)),
+            }
+        }
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|item| substitute(item, values)).collect())
+        }
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| (interpolate(key, values), substitute(value, values)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Replaces the placeholders within a string by the text of the values.
+/// Lists are joined with commas and unknown placeholders are kept.
+fn interpolate(text: &str, values: &Map<String, Value>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + length + 2];
+        output.push_str(&rest[..start]);
+        match values.get(placeholder[2..placeholder.len() - 2].trim()) {
+            Some(value) => output.push_str(&to_text(value)),
+            None => output.push_str(placeholder),
+        }
+        rest = &rest[start + length + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(to_text).collect::<Vec<_>>().join(","),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::named_query::NamedQueryCatalogue;
+
+    fn service() -> NamedQueryService {
+        let catalogue: NamedQueryCatalogue = serde_json::from_value(json!({
+            "queries": {
+                "movies-by-genre": {
+                    "index": "movies",
+                    "params": {
+                        "genres": { "type": "list", "items": { "type": "enum", "values": ["Drama", "Sci-Fi"] } },
+                        "since": { "type": "date", "default": "now-10y/y" },
+                        "size": { "type": "number", "min": 1, "max": 50, "default": 10 },
+                        "sort": { "type": "enum", "values": ["year", "rating"], "default": "year" }
+                    },
+                    "body": {
+                        "query": { "bool": { "filter": [
+                            { "terms": { "genre.keyword": "{{genres}}" } },
+                            { "range": { "release_date": { "gte": "{{since}}" } } }
+                        ] } },
+                        "size": "{{size}}",
+                        "sort": [{ "{{sort}}": "desc" }]
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        NamedQueryService::new(NamedQueryRepository::from_catalogue(catalogue))
+    }
+
+    fn policy(queries: &[&str]) -> NamedQueryPolicy {
+        NamedQueryPolicy {
+            queries: queries.iter().map(|query| query.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_query_requires_role_access() {
+        let service = service();
+
+        assert!(
+            service
+                .query("movies-by-genre", &policy(&["movies-by-genre"]))
+                .is_ok()
+        );
+        assert_eq!(
+            service.query("movies-by-genre", &policy(&[])).unwrap_err(),
+            NamedQueryRejection::Unknown("movies-by-genre".to_string())
+        );
+        assert!(service.query("missing", &policy(&["missing"])).is_err());
+    }
+
+    #[test]
+    fn test_bind_params() {
+        let service = service();
+        let query = service
+            .query("movies-by-genre", &policy(&["movies-by-genre"]))
+            .unwrap();
+
+        assert_eq!(
+            service
+                .bind(
+                    query,
+                    &json!({ "params": { "genres": ["Drama"], "since": "2020-01-01", "sort": "rating" } })
+                )
+                .unwrap(),
+            json!({
+                "query": { "bool": { "filter": [
+                    { "terms": { "genre.keyword": ["Drama"] } },
+                    { "range": { "release_date": { "gte": "2020-01-01" } } }
+                ] } },
+                "size": 10,
+                "sort": [{ "rating": "desc" }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_bind_rejects_invalid_params() {
+        let service = service();
+        let query = service
+            .query("movies-by-genre", &policy(&["movies-by-genre"]))
+            .unwrap();
+        let bind = |params: Value| service.bind(query, &json!({ "params": params }));
+
+        assert_eq!(
+            bind(json!({})),
+            Err(NamedQueryRejection::MissingParam("genres".to_string()))
+        );
+        assert_eq!(
+            bind(json!({ "genres": [], "query": {} })),
+            Err(NamedQueryRejection::UnknownParam("query".to_string()))
+        );
+        assert!(bind(json!({ "genres": ["Horror"] })).is_err());
+        assert!(bind(json!({ "genres": "Drama" })).is_err());
+        assert!(bind(json!({ "genres": [], "size": 100 })).is_err());
+        assert!(bind(json!({ "genres": [], "since": "yesterday" })).is_err());
+        assert!(
+            service
+                .bind(query, &json!({ "query": { "match_all": {} } }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_is_date() {
+        for date in [
+            "2024-01-31",
+            "2024-01-31T12:30",
+            "2024-01-31T12:30:00.123Z",
+            "2024-01-31T12:30:00+02:00",
+            "now",
+            "now-7d/d",
+            "now+1M-2h",
+        ] {
+            assert!(is_date(date), "{}", date);
+        }
+        for date in [
+            "2024-13-01",
+            "24-01-01",
+            "2024-01-31T25",
+            "now-d",
+            "now/dd",
+            "today",
+        ] {
+            assert!(!is_date(date), "{}", date);
+        }
+    }
+}
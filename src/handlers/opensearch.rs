@@ -1,84 +1,2387 @@
+use std::collections::{BTreeMap, HashSet};
+
 use axum::{
     Json,
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::to_bytes,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, Uri, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
 };
+use opensearch::http::Method;
 use serde_json::Value;
 use tracing::{debug, error, instrument};
 
-use crate::body::ndjson::NdjsonBody;
+use crate::auth::identity::Identity;
+use crate::body::json::OptionalJsonBody;
+use crate::body::ndjson::{NdjsonBody, NdjsonError, parse_ndjson_lines, to_ndjson_bytes};
+use crate::handlers::{
+    async_search::{AsyncSearchContext, AsyncSearchRejection},
+    bulk::{BulkAction, BulkItem, BulkItemError},
+    dashboards::{DashboardsCaller, MAX_FORWARDED_BODY},
+    debug_output::DebugFlagForbidden,
+    discovery::CatApi,
+    documents::{DocumentRequest, InvalidMgetRequest},
+    field_security::FieldPolicy,
+    index_authorization::{ForbiddenIndex, IndexPolicy},
+    masking::{MaskedField, MaskingPolicy},
+    named_queries::{NamedQueryPolicy, NamedQueryRejection},
+    owner_fields::{OwnerFieldViolation, OwnerPolicy},
+    point_in_time::{PitContext, PitRejection},
+    pseudonymization::{PseudonymPolicy, PseudonymizedField},
+    query_inspector::{ForbiddenField, UnfilteredRead},
+    scroll::{ScrollContext, ScrollRejection, ScrollRequest},
+    search_params::{
+        ParamPolicy, ParamRejection, SearchEndpoint, SearchParams, format_time, parse_time,
+    },
+    search_template::{Template, TemplatePolicy, TemplateRejection},
+    sql::{SqlLanguage, SqlRejection, SqlRequest},
+    tasks::TaskMissing,
+    terms_enum::InvalidTermsEnumRequest,
+};
+use crate::models::policy::DebugPolicy;
+use crate::repositories::opensearch::RepositoryError;
 use crate::state::OpenSearchRouterState;
 
+/// The policies of a caller that apply along the search path.
+struct CallerPolicies {
+    indices: IndexPolicy,
+    writes: IndexPolicy,
+    owner: OwnerPolicy,
+    fields: FieldPolicy,
+    masking: MaskingPolicy,
+    pseudonyms: PseudonymPolicy,
+    debug: DebugPolicy,
+    params: ParamPolicy,
+    templates: TemplatePolicy,
+    named_queries: NamedQueryPolicy,
+}
+
+impl CallerPolicies {
+    fn resolve(state: &OpenSearchRouterState, identity: &Identity) -> Self {
+        let policy = state.policy_repository.get_policy();
+        let mut indices = state
+            .index_authorization_service
+            .read_policy_for(policy, identity);
+        let mut writes = state
+            .index_authorization_service
+            .write_policy_for(policy, identity);
+        state.dashboards_service.reserve_system_index(&mut indices);
+        state.dashboards_service.reserve_system_index(&mut writes);
+
+        Self {
+            indices,
+            writes,
+            owner: state.owner_field_service.policy_for(policy, identity),
+            fields: state.field_security_service.policy_for(policy, identity),
+            masking: state.masking_service.policy_for(policy, identity),
+            pseudonyms: state.pseudonymization_service.policy_for(policy, identity),
+            debug: state.debug_output_service.policy_for(policy, identity),
+            params: state.search_params_service.policy_for(policy, identity),
+            templates: state.search_template_service.policy_for(policy, identity),
+            named_queries: state.named_query_service.policy_for(policy, identity),
+        }
+    }
+}
+
+/// Reasons a search body is rejected before it reaches OpenSearch.
+enum SearchRejection {
+    Index(ForbiddenIndex),
+    Forbidden(ForbiddenField),
+    Unfiltered(UnfilteredRead),
+    Masked(MaskedField),
+    Pseudonymized(PseudonymizedField),
+    DebugFlag(DebugFlagForbidden),
+    Param(ParamRejection),
+    Mget(InvalidMgetRequest),
+    Scroll(ScrollRejection),
+    Pit(PitRejection),
+    AsyncSearch(AsyncSearchRejection),
+    Template(TemplateRejection),
+    NamedQuery(NamedQueryRejection),
+    Task(TaskMissing),
+    Sql(SqlRejection),
+    TermsEnum(InvalidTermsEnumRequest),
+    Owner(OwnerFieldViolation),
+    /// A lookup needed to prepare the request failed
+    Repository(RepositoryError),
+}
+
+impl From<ForbiddenIndex> for SearchRejection {
+    fn from(error: ForbiddenIndex) -> Self {
+        Self::Index(error)
+    }
+}
+
+impl From<ForbiddenField> for SearchRejection {
+    fn from(error: ForbiddenField) -> Self {
+        Self::Forbidden(error)
+    }
+}
+
+impl From<UnfilteredRead> for SearchRejection {
+    fn from(error: UnfilteredRead) -> Self {
+        Self::Unfiltered(error)
+    }
+}
+
+impl From<MaskedField> for SearchRejection {
+    fn from(error: MaskedField) -> Self {
+        Self::Masked(error)
+    }
+}
+
+impl From<PseudonymizedField> for SearchRejection {
+    fn from(error: PseudonymizedField) -> Self {
+        Self::Pseudonymized(error)
+    }
+}
+
+impl From<DebugFlagForbidden> for SearchRejection {
+    fn from(error: DebugFlagForbidden) -> Self {
+        Self::DebugFlag(error)
+    }
+}
+
+impl From<ParamRejection> for SearchRejection {
+    fn from(error: ParamRejection) -> Self {
+        Self::Param(error)
+    }
+}
+
+impl From<InvalidMgetRequest> for SearchRejection {
+    fn from(error: InvalidMgetRequest) -> Self {
+        Self::Mget(error)
+    }
+}
+
+impl From<ScrollRejection> for SearchRejection {
+    fn from(error: ScrollRejection) -> Self {
+        Self::Scroll(error)
+    }
+}
+
+impl From<PitRejection> for SearchRejection {
+    fn from(error: PitRejection) -> Self {
+        Self::Pit(error)
+    }
+}
+
+impl From<AsyncSearchRejection> for SearchRejection {
+    fn from(error: AsyncSearchRejection) -> Self {
+        Self::AsyncSearch(error)
+    }
+}
+
+impl From<TemplateRejection> for SearchRejection {
+    fn from(error: TemplateRejection) -> Self {
+        Self::Template(error)
+    }
+}
+
+impl From<NamedQueryRejection> for SearchRejection {
+    fn from(error: NamedQueryRejection) -> Self {
+        Self::NamedQuery(error)
+    }
+}
+
+impl From<TaskMissing> for SearchRejection {
+    fn from(error: TaskMissing) -> Self {
+        Self::Task(error)
+    }
+}
+
+impl From<SqlRejection> for SearchRejection {
+    fn from(error: SqlRejection) -> Self {
+        Self::Sql(error)
+    }
+}
+
+impl From<InvalidTermsEnumRequest> for SearchRejection {
+    fn from(error: InvalidTermsEnumRequest) -> Self {
+        Self::TermsEnum(error)
+    }
+}
+
+impl From<OwnerFieldViolation> for SearchRejection {
+    fn from(error: OwnerFieldViolation) -> Self {
+        Self::Owner(error)
+    }
+}
+
+impl IntoResponse for SearchRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Index(error) => error.into_response(),
+            Self::Forbidden(error) => error.into_response(),
+            Self::Unfiltered(error) => error.into_response(),
+            Self::Masked(error) => error.into_response(),
+            Self::Pseudonymized(error) => error.into_response(),
+            Self::DebugFlag(error) => error.into_response(),
+            Self::Param(error) => error.into_response(),
+            Self::Mget(error) => error.into_response(),
+            Self::Scroll(error) => error.into_response(),
+            Self::Pit(error) => error.into_response(),
+            Self::AsyncSearch(error) => error.into_response(),
+            Self::Template(error) => error.into_response(),
+            Self::NamedQuery(error) => error.into_response(),
+            Self::Task(error) => error.into_response(),
+            Self::Sql(error) => error.into_response(),
+            Self::TermsEnum(error) => error.into_response(),
+            Self::Owner(error) => error.into_response(),
+            Self::Repository(error) => error.into_response(),
+        }
+    }
+}
+
+/// Authorizes the indices of a request and validates its query string. A
+/// URI search replaces the query of the body, as in OpenSearch.
+fn prepare_request(
+    state: &OpenSearchRouterState,
+    endpoint: SearchEndpoint,
+    index: &str,
+    mut params: SearchParams,
+    body: &mut Value,
+    policies: &CallerPolicies,
+) -> Result<SearchParams, SearchRejection> {
+    state
+        .index_authorization_service
+        .authorize(index, &policies.indices)?;
+    if endpoint.supports_uri_search()
+        && let Some(query) = state.search_params_service.take_uri_query(&mut params)?
+    {
+        body["query"] = query;
+    }
+
+    Ok(state
+        .search_params_service
+        .filter(endpoint, params, &policies.params)?)
+}
+
+/// Checks the query of a body against the caller's policies and translates
+/// pseudonyms back to real values. Indices read by lookups in the query
+/// must be readable as well, and reads bypassing the security filter are
+/// rejected unless it matches every document.
+fn prepare_query_body(
+    state: &OpenSearchRouterState,
+    body: Value,
+    policies: &CallerPolicies,
+) -> Result<Value, SearchRejection> {
+    state.search_params_service.check_body(&body)?;
+    state
+        .query_inspector_service
+        .inspect(&body, &policies.fields)?;
+    for index in state.query_inspector_service.referenced_indices(&body) {
+        state
+            .index_authorization_service
+            .authorize(&index, &policies.indices)?;
+    }
+    if !state
+        .security_filter_service
+        .is_match_all(&state.filter_repository.get_filter().0)
+    {
+        state
+            .query_inspector_service
+            .check_unfiltered_reads(&body)?;
+    }
+    state
+        .masking_service
+        .check_request(&body, &policies.masking)?;
+
+    Ok(state
+        .pseudonymization_service
+        .translate_request(body, &policies.pseudonyms)?)
+}
+
+/// Checks a search body against the caller's policies and rewrites it so
+/// that only permitted fields are requested and pseudonyms are translated
+/// back to real values.
+fn prepare_search_body(
+    state: &OpenSearchRouterState,
+    body: Value,
+    policies: &CallerPolicies,
+) -> Result<Value, SearchRejection> {
+    let mut body = prepare_query_body(state, body, policies)?;
+    state
+        .debug_output_service
+        .check_request(&mut body, &policies.debug)?;
+
+    Ok(state
+        .field_security_service
+        .restrict_request(body, &policies.fields))
+}
+
+/// Applies field level security, pseudonymization and masking to the
+/// response of the given search body, and strips debugging output of the
+/// injected security filter.
+fn transform_search_response(
+    state: &OpenSearchRouterState,
+    response: &mut Value,
+    request: &Value,
+    filter: &Value,
+    policies: &CallerPolicies,
+) {
+    state
+        .debug_output_service
+        .strip_response(response, filter, &policies.debug);
+    state
+        .field_security_service
+        .restrict_response(response, &policies.fields);
+    state
+        .pseudonymization_service
+        .pseudonymize_response(response, request, &policies.pseudonyms);
+    state
+        .masking_service
+        .mask_response(response, &policies.masking);
+}
+
+/// Converts a repository error into a response. Upstream error bodies are
+/// sanitized of the given security filter and keep their status.
+fn repository_error_response(
+    state: &OpenSearchRouterState,
+    error: RepositoryError,
+    filter: &Value,
+) -> Response {
+    match error {
+        RepositoryError::Upstream { status, body } => (
+            status,
+            Json(state.error_sanitizer_service.sanitize(body, filter)),
+        )
+            .into_response(),
+        error => error.into_response(),
+    }
+}
+
 pub async fn handle_search(
     State(state): State<OpenSearchRouterState>,
+    identity: Identity,
     Path(index): Path<String>,
-    Json(payload): Json<Value>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(mut payload): OptionalJsonBody,
 ) -> impl IntoResponse {
     // For demonstration, we use a fake filter. In a real application,
     // this would be another api call or derived from user context.
     let fake_filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let prepared = prepare_request(
+        &state,
+        SearchEndpoint::Search,
+        &index,
+        params,
+        &mut payload,
+        &policies,
+    )
+    .and_then(|params| Ok((params, prepare_search_body(&state, payload, &policies)?)));
+    let (params, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    execute_search(
+        &state,
+        &identity,
+        &index,
+        params,
+        payload,
+        &fake_filter.0,
+        &policies,
+    )
+    .await
+}
 
-    let query_with_security_filter = state.security_filter_service.apply(payload, fake_filter.0);
+/// Sends a prepared search with the security filter applied and transforms
+/// the response. A scroll opened by the search is bound to the caller.
+async fn execute_search(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    index: &str,
+    params: SearchParams,
+    payload: Value,
+    filter: &Value,
+    policies: &CallerPolicies,
+) -> Response {
+    let query_with_security_filter = state
+        .security_filter_service
+        .apply(payload.clone(), filter.clone());
 
     match state
         .opensearch_repo
-        .search(&index, query_with_security_filter)
+        .search(index, query_with_security_filter, &params)
         .await
     {
-        Ok(result) => Json(result).into_response(),
-        Err(e) => {
-            eprintln!("Search error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e.to_string()})),
-            )
-                .into_response()
+        Ok(mut result) => {
+            transform_search_response(state, &mut result, &payload, filter, policies);
+            if let Some(keep_alive) = params
+                .iter()
+                .find(|(name, _)| name == "scroll")
+                .and_then(|(_, value)| parse_time(value))
+            {
+                let context = ScrollContext {
+                    request: payload,
+                    keep_alive,
+                };
+                state.scroll_service.register(identity, &result, context);
+            }
+            Json(result).into_response()
         }
+        Err(e) => repository_error_response(state, e, filter),
     }
 }
 
-#[instrument(skip(state, ndjson_body), fields(index = %index, body_size = ndjson_body.0.len()))]
-pub async fn handle_msearch(
+/// Renders a search template body into a search body. Stored templates
+/// must be allowed for the caller and are fetched from OpenSearch.
+async fn render_search_template(
+    state: &OpenSearchRouterState,
+    body: &Value,
+    policies: &CallerPolicies,
+) -> Result<Value, SearchRejection> {
+    let request = state.search_template_service.parse_request(body)?;
+    let source = match &request.template {
+        Template::Inline(source) => source.clone(),
+        Template::Stored(id) => {
+            state
+                .search_template_service
+                .authorize(id, &policies.templates)?;
+            match state.opensearch_repo.get_script(id).await {
+                Ok(script) => state.search_template_service.stored_source(id, &script)?,
+                Err(RepositoryError::Upstream {
+                    status: StatusCode::NOT_FOUND,
+                    ..
+                }) => return Err(TemplateRejection::UnknownTemplate(id.clone()).into()),
+                Err(e) => return Err(SearchRejection::Repository(e)),
+            }
+        }
+    };
+
+    Ok(state.search_template_service.render(&source, &request)?)
+}
+
+pub async fn handle_search_template(
     State(state): State<OpenSearchRouterState>,
+    identity: Identity,
     Path(index): Path<String>,
-    ndjson_body: NdjsonBody,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
 ) -> impl IntoResponse {
-    let NdjsonBody(ndjson_bytes) = ndjson_body;
-    debug!(
-        "Received msearch request for index '{}' with {} bytes of NDJSON data",
-        index,
-        ndjson_bytes.len()
-    );
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let prepared = render_search_template(&state, &payload, &policies)
+        .await
+        .and_then(|mut payload| {
+            let params = prepare_request(
+                &state,
+                SearchEndpoint::SearchTemplate,
+                &index,
+                params,
+                &mut payload,
+                &policies,
+            )?;
+            Ok((params, prepare_search_body(&state, payload, &policies)?))
+        });
+    let (params, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    execute_search(
+        &state, &identity, &index, params, payload, &filter.0, &policies,
+    )
+    .await
+}
+
+pub async fn handle_named_query(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(name): Path<String>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let prepared = state
+        .named_query_service
+        .query(&name, &policies.named_queries)
+        .map_err(SearchRejection::from)
+        .and_then(|query| {
+            let mut payload = state.named_query_service.bind(query, &payload)?;
+            let params = prepare_request(
+                &state,
+                SearchEndpoint::Search,
+                &query.index,
+                SearchParams::new(),
+                &mut payload,
+                &policies,
+            )?;
+            let payload = prepare_search_body(&state, payload, &policies)?;
+            Ok((query.index.clone(), params, payload))
+        });
+    let (index, params, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
 
-    match state.opensearch_repo.msearch(&index, ndjson_bytes).await {
+    execute_search(
+        &state, &identity, &index, params, payload, &filter.0, &policies,
+    )
+    .await
+}
+
+pub async fn handle_create_pit(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let params = state
+        .index_authorization_service
+        .authorize(&index, &policies.indices)
+        .map_err(SearchRejection::from)
+        .and_then(|_| {
+            Ok(state.search_params_service.filter(
+                SearchEndpoint::PointInTime,
+                params,
+                &policies.params,
+            )?)
+        });
+    let params = match params {
+        Ok(params) => params,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    match state.opensearch_repo.create_pit(&index, &params).await {
         Ok(result) => {
-            debug!("MSearch request successful for index '{}'", index);
+            if let Some(keep_alive) = params
+                .iter()
+                .find(|(name, _)| name == "keep_alive")
+                .and_then(|(_, value)| parse_time(value))
+            {
+                let context = PitContext { index, keep_alive };
+                state
+                    .point_in_time_service
+                    .register(&identity, &result, context);
+            }
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_delete_pits(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    delete_pits(&state, &identity, Some(&payload)).await
+}
+
+pub async fn handle_delete_all_pits(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+) -> impl IntoResponse {
+    delete_pits(&state, &identity, None).await
+}
+
+/// Deletes the requested points in time of the caller, or all of them if
+/// no body is given. Points in time of other identities are reported like
+/// unknown ones.
+async fn delete_pits(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    body: Option<&Value>,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let pit_ids = state.point_in_time_service.owned_pit_ids(identity, body);
+    if pit_ids.is_empty() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "pits": [] })),
+        )
+            .into_response();
+    }
+
+    let result = state
+        .opensearch_repo
+        .delete_pits(serde_json::json!({ "pit_id": pit_ids }))
+        .await;
+    state.point_in_time_service.forget(&pit_ids);
+    match result {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => repository_error_response(state, e, &filter.0),
+    }
+}
+
+/// Resolves the point in time of an index-less search, which must have
+/// been created by the caller, and prepares the search against the indices
+/// of the point in time.
+fn prepare_pit_search(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    params: SearchParams,
+    mut body: Value,
+    policies: &CallerPolicies,
+) -> Result<(SearchParams, Value, String, PitContext), SearchRejection> {
+    let pit_id = state.point_in_time_service.pit_id(&body)?.to_string();
+    let mut context = state.point_in_time_service.context(identity, &pit_id)?;
+    if let Some(keep_alive) = body.pointer("/pit/keep_alive").and_then(Value::as_str) {
+        context.keep_alive =
+            state
+                .search_params_service
+                .keep_alive("keep_alive", keep_alive, &policies.params)?;
+        body["pit"]["keep_alive"] = Value::String(format_time(context.keep_alive));
+    }
+
+    let params = prepare_request(
+        state,
+        SearchEndpoint::Search,
+        &context.index,
+        params,
+        &mut body,
+        policies,
+    )?;
+    let body = prepare_search_body(state, body, policies)?;
+    Ok((params, body, pit_id, context))
+}
+
+pub async fn handle_pit_search(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let (params, payload, pit_id, context) =
+        match prepare_pit_search(&state, &identity, params, payload, &policies) {
+            Ok(prepared) => prepared,
+            Err(rejection) => return rejection.into_response(),
+        };
+    let query_with_security_filter = state
+        .security_filter_service
+        .apply(payload.clone(), filter.0.clone());
+
+    match state
+        .opensearch_repo
+        .search_all(query_with_security_filter, &params)
+        .await
+    {
+        Ok(mut result) => {
+            transform_search_response(&state, &mut result, &payload, &filter.0, &policies);
+            state
+                .point_in_time_service
+                .searched(&identity, &pit_id, &result, context);
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_submit_async_search(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(mut params): Query<SearchParams>,
+    OptionalJsonBody(mut payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let prepared = state
+        .async_search_service
+        .take_index(&mut params)
+        .map_err(SearchRejection::from)
+        .and_then(|index| {
+            let mut params = prepare_request(
+                &state,
+                SearchEndpoint::AsyncSearch,
+                &index,
+                params,
+                &mut payload,
+                &policies,
+            )?;
+            params.push(("index".to_string(), index));
+            Ok((params, prepare_search_body(&state, payload, &policies)?))
+        });
+    let (params, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let query_with_security_filter = state
+        .security_filter_service
+        .apply(payload.clone(), filter.0.clone());
+
+    match state
+        .opensearch_repo
+        .submit_async_search(query_with_security_filter, &params)
+        .await
+    {
+        Ok(mut result) => {
+            transform_async_search_response(&state, &mut result, &payload, &filter.0, &policies);
+            if let Some(keep_alive) = state.async_search_service.keep_alive(&params) {
+                let context = AsyncSearchContext { request: payload };
+                state
+                    .async_search_service
+                    .register(&identity, &result, keep_alive, context);
+            }
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_get_async_search(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(id): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let prepared = state
+        .async_search_service
+        .context(&identity, &id)
+        .map_err(SearchRejection::from)
+        .and_then(|context| {
+            let params = state.search_params_service.filter(
+                SearchEndpoint::AsyncSearchResult,
+                params,
+                &policies.params,
+            )?;
+            Ok((params, context))
+        });
+    let (params, context) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    match state.opensearch_repo.get_async_search(&id, &params).await {
+        Ok(mut result) => {
+            transform_async_search_response(
+                &state,
+                &mut result,
+                &context.request,
+                &filter.0,
+                &policies,
+            );
+            if let Some(keep_alive) = state.async_search_service.keep_alive(&params) {
+                state.async_search_service.extend(&id, keep_alive);
+            }
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_delete_async_search(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    if let Err(rejection) = state.async_search_service.context(&identity, &id) {
+        return rejection.into_response();
+    }
+
+    match state.opensearch_repo.delete_async_search(&id).await {
+        Ok(result) => {
+            state.async_search_service.forget(&id);
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+/// Transforms the search response contained in an asynchronous search
+/// response and sanitizes the error of a failed search.
+fn transform_async_search_response(
+    state: &OpenSearchRouterState,
+    result: &mut Value,
+    request: &Value,
+    filter: &Value,
+    policies: &CallerPolicies,
+) {
+    if let Some(response) = result.get_mut("response") {
+        transform_search_response(state, response, request, filter, policies);
+    }
+    if state.error_sanitizer_service.is_error(result) {
+        let error = serde_json::json!({ "error": result["error"].take() });
+        result["error"] = state.error_sanitizer_service.sanitize(error, filter)["error"].take();
+    }
+}
+
+/// Validates a scroll continuation and resolves the scroll it continues,
+/// which must have been created by the caller.
+fn prepare_scroll(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    params: SearchParams,
+    body: &Value,
+    policies: &CallerPolicies,
+) -> Result<(ScrollRequest, ScrollContext), SearchRejection> {
+    let params =
+        state
+            .search_params_service
+            .filter(SearchEndpoint::Scroll, params, &policies.params)?;
+    let request = state.scroll_service.parse_request(&params, body)?;
+    let mut context = state.scroll_service.context(identity, &request.scroll_id)?;
+    if let Some(scroll) = &request.scroll {
+        context.keep_alive =
+            state
+                .search_params_service
+                .keep_alive("scroll", scroll, &policies.params)?;
+    }
+    Ok((request, context))
+}
+
+pub async fn handle_scroll(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let (request, context) = match prepare_scroll(&state, &identity, params, &payload, &policies) {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let body = serde_json::json!({
+        "scroll_id": request.scroll_id,
+        "scroll": format_time(context.keep_alive),
+    });
+
+    match state.opensearch_repo.scroll(body).await {
+        Ok(mut result) => {
+            transform_search_response(&state, &mut result, &context.request, &filter.0, &policies);
+            state
+                .scroll_service
+                .continued(&identity, &request.scroll_id, &result, context);
             Json(result).into_response()
         }
         Err(e) => {
-            error!("MSearch error for index '{}': {}", index, e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e.to_string()})),
-            )
-                .into_response()
+            if e.status() == StatusCode::NOT_FOUND {
+                state.scroll_service.forget(&[request.scroll_id]);
+            }
+            repository_error_response(&state, e, &filter.0)
         }
     }
 }
 
-pub async fn handle_cluster_health(
+pub async fn handle_clear_scroll(
     State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
 ) -> impl IntoResponse {
-    match state.opensearch_repo.cluster_health().await {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let params =
+        state
+            .search_params_service
+            .filter(SearchEndpoint::Scroll, params, &policies.params);
+    let params = match params {
+        Ok(params) => params,
+        Err(rejection) => return rejection.into_response(),
+    };
+    // Scrolls of other identities are reported like unknown ones
+    let scroll_ids = state
+        .scroll_service
+        .owned_scroll_ids(&identity, &params, &payload);
+    if scroll_ids.is_empty() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "succeeded": true, "num_freed": 0 })),
+        )
+            .into_response();
+    }
+
+    let body = serde_json::json!({ "scroll_id": scroll_ids });
+    let result = state.opensearch_repo.clear_scroll(body).await;
+    state.scroll_service.forget(&scroll_ids);
+    match result {
         Ok(result) => Json(result).into_response(),
-        Err(e) => {
-            eprintln!("Cluster health error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e.to_string()})),
-            )
-                .into_response()
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_count(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(mut payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let prepared = prepare_request(
+        &state,
+        SearchEndpoint::Count,
+        &index,
+        params,
+        &mut payload,
+        &policies,
+    )
+    .and_then(|params| Ok((params, prepare_query_body(&state, payload, &policies)?)));
+    let (params, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let query_with_security_filter = state
+        .security_filter_service
+        .apply(payload, filter.0.clone());
+
+    match state
+        .opensearch_repo
+        .count(&index, query_with_security_filter, &params)
+        .await
+    {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+/// Answers `_terms_enum` with a filtered `terms` aggregation, so that only
+/// terms of documents matching the security filter are returned.
+pub async fn handle_terms_enum(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(mut payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let prepared = prepare_request(
+        &state,
+        SearchEndpoint::TermsEnum,
+        &index,
+        params,
+        &mut payload,
+        &policies,
+    )
+    .and_then(|params| {
+        let request = state.terms_enum_service.parse_request(&payload)?;
+        let body = state.terms_enum_service.search_body(&request);
+        Ok((
+            params,
+            request,
+            prepare_search_body(&state, body, &policies)?,
+        ))
+    });
+    let (params, request, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let query_with_security_filter = state
+        .security_filter_service
+        .apply(payload.clone(), filter.0.clone());
+
+    match state
+        .opensearch_repo
+        .search(&index, query_with_security_filter, &params)
+        .await
+    {
+        Ok(mut result) => {
+            transform_search_response(&state, &mut result, &payload, &filter.0, &policies);
+            Json(state.terms_enum_service.response(&request, result)).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+/// Returns the capabilities of the fields of an index, limited to the
+/// fields the caller may read.
+pub async fn handle_field_caps(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let params = match prepare_request(
+        &state,
+        SearchEndpoint::FieldCaps,
+        &index,
+        params,
+        &mut Value::Null,
+        &policies,
+    ) {
+        Ok(params) => params,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    match state.opensearch_repo.field_caps(&index, &params).await {
+        Ok(mut result) => {
+            state
+                .field_security_service
+                .restrict_field_caps(&mut result, &policies.fields);
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+/// Authorizes the index expression of a request listing indices and
+/// validates its query string. Returns whether the listed indices must be
+/// reduced to the readable ones.
+fn prepare_listing(
+    state: &OpenSearchRouterState,
+    endpoint: SearchEndpoint,
+    index: Option<&str>,
+    params: SearchParams,
+    policies: &CallerPolicies,
+) -> Result<(bool, SearchParams), SearchRejection> {
+    let prune = state
+        .index_authorization_service
+        .authorize_listing(index, &policies.indices)?;
+
+    Ok((
+        prune,
+        state
+            .search_params_service
+            .filter(endpoint, params, &policies.params)?,
+    ))
+}
+
+pub async fn handle_mapping(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    get_mapping(&state, &identity, None, params).await
+}
+
+pub async fn handle_index_mapping(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    get_mapping(&state, &identity, Some(index), params).await
+}
+
+/// Returns the mappings of readable indices, limited to the fields the
+/// caller may read.
+async fn get_mapping(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    index: Option<String>,
+    params: SearchParams,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(state, identity);
+
+    let (prune, params) = match prepare_listing(
+        state,
+        SearchEndpoint::Mapping,
+        index.as_deref(),
+        params,
+        &policies,
+    ) {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    match state
+        .opensearch_repo
+        .get_mapping(index.as_deref(), &params)
+        .await
+    {
+        Ok(mut result) => {
+            if prune {
+                state
+                    .discovery_service
+                    .restrict_index_keys(&mut result, &policies.indices);
+            }
+            if let Some(indices) = result.as_object_mut() {
+                for mapping in indices
+                    .values_mut()
+                    .filter_map(|index| index.get_mut("mappings"))
+                {
+                    state
+                        .field_security_service
+                        .restrict_mapping(mapping, &policies.fields);
+                }
+            }
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(state, e, &filter.0),
+    }
+}
+
+pub async fn handle_cat_indices(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    cat(&state, &identity, CatApi::Indices, None, params).await
+}
+
+pub async fn handle_cat_named_indices(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    cat(&state, &identity, CatApi::Indices, Some(index), params).await
+}
+
+pub async fn handle_cat_aliases(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    cat(&state, &identity, CatApi::Aliases, None, params).await
+}
+
+pub async fn handle_cat_named_aliases(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(alias): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    cat(&state, &identity, CatApi::Aliases, Some(alias), params).await
+}
+
+/// Lists indices or aliases through a `_cat` API, reduced to readable
+/// indices and formatted as requested by the client.
+async fn cat(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    api: CatApi,
+    name: Option<String>,
+    params: SearchParams,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(state, identity);
+    let endpoint = match api {
+        CatApi::Indices => SearchEndpoint::CatIndices,
+        CatApi::Aliases => SearchEndpoint::CatAliases,
+    };
+
+    let (prune, params) = match prepare_listing(state, endpoint, name.as_deref(), params, &policies)
+    {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let (request, params) = state.discovery_service.cat_request(api, params);
+
+    let result = match api {
+        CatApi::Indices => {
+            state
+                .opensearch_repo
+                .cat_indices(name.as_deref(), &params)
+                .await
+        }
+        CatApi::Aliases => {
+            state
+                .opensearch_repo
+                .cat_aliases(name.as_deref(), &params)
+                .await
+        }
+    };
+    match result {
+        Ok(mut rows) => {
+            if prune {
+                state
+                    .discovery_service
+                    .restrict_cat_rows(&mut rows, &policies.indices);
+            }
+            state
+                .discovery_service
+                .cat_body(&request, rows)
+                .into_response()
+        }
+        Err(e) => repository_error_response(state, e, &filter.0),
+    }
+}
+
+pub async fn handle_resolve_index(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(name): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let (prune, params) = match prepare_listing(
+        &state,
+        SearchEndpoint::ResolveIndex,
+        Some(&name),
+        params,
+        &policies,
+    ) {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    match state.opensearch_repo.resolve_index(&name, &params).await {
+        Ok(mut result) => {
+            if prune {
+                state
+                    .discovery_service
+                    .restrict_resolved(&mut result, &policies.indices);
+            }
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_get_document(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path((index, id)): Path<(String, String)>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    get_document(&state, &identity, index, id, params, false).await
+}
+
+pub async fn handle_get_source(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path((index, id)): Path<(String, String)>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    get_document(&state, &identity, index, id, params, true).await
+}
+
+/// Fetches a single document by a filtered `ids` search and returns it in
+/// the shape of the get API, or of the `_source` API if `source_only` is
+/// set.
+async fn get_document(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    index: String,
+    id: String,
+    mut params: SearchParams,
+    source_only: bool,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(state, identity);
+
+    let document = DocumentRequest {
+        source: state.document_service.take_source_filter(&mut params),
+        index,
+        id,
+        routing: None,
+    };
+    let mut payload = state.document_service.search_body(&document);
+    let prepared = prepare_request(
+        state,
+        SearchEndpoint::Get,
+        &document.index,
+        params,
+        &mut payload,
+        &policies,
+    )
+    .and_then(|params| Ok((params, prepare_search_body(state, payload, &policies)?)));
+    let (params, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let query_with_security_filter = state
+        .security_filter_service
+        .apply(payload.clone(), filter.0.clone());
+
+    let mut result = match state
+        .opensearch_repo
+        .search(&document.index, query_with_security_filter, &params)
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => return repository_error_response(state, e, &filter.0),
+    };
+    transform_search_response(state, &mut result, &payload, &filter.0, &policies);
+
+    match state.document_service.document_from_search(&result) {
+        Some(mut found) if source_only => Json(found["_source"].take()).into_response(),
+        Some(found) => Json(found).into_response(),
+        None if source_only => (
+            StatusCode::NOT_FOUND,
+            Json(state.document_service.source_not_found(&document)),
+        )
+            .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(state.document_service.not_found(&document)),
+        )
+            .into_response(),
+    }
+}
+
+/// Authorizes the documents of an `_mget` request and builds the header
+/// and prepared search body of each document.
+fn prepare_mget(
+    state: &OpenSearchRouterState,
+    documents: &[DocumentRequest],
+    params: SearchParams,
+    policies: &CallerPolicies,
+) -> Result<Vec<(Value, Value)>, SearchRejection> {
+    let params =
+        state
+            .search_params_service
+            .filter(SearchEndpoint::Mget, params, &policies.params)?;
+
+    documents
+        .iter()
+        .map(|document| {
+            state
+                .index_authorization_service
+                .authorize(&document.index, &policies.indices)?;
+            let body = state.document_service.search_body(document);
+            let mut header = state.document_service.search_header(document, &params);
+            state
+                .search_params_service
+                .restrict_msearch_header(&mut header, &policies.params)?;
+            Ok((header, prepare_search_body(state, body, policies)?))
+        })
+        .collect()
+}
+
+pub async fn handle_mget(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(mut params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let source = state.document_service.take_source_filter(&mut params);
+    let prepared = state
+        .index_authorization_service
+        .authorize(&index, &policies.indices)
+        .map_err(SearchRejection::from)
+        .and_then(|_| {
+            Ok(state
+                .document_service
+                .parse_mget(&index, &payload, source.as_ref())?)
+        })
+        .and_then(|documents| {
+            let searches = prepare_mget(&state, &documents, params, &policies)?;
+            Ok((documents, searches))
+        });
+    let (documents, searches) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let lines: Vec<Value> = searches
+        .iter()
+        .flat_map(|(header, body)| {
+            [
+                header.clone(),
+                state
+                    .security_filter_service
+                    .apply(body.clone(), filter.0.clone()),
+            ]
+        })
+        .collect();
+    let mut result = match state
+        .opensearch_repo
+        .msearch(&index, to_ndjson_bytes(&lines), &[])
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => return repository_error_response(&state, e, &filter.0),
+    };
+
+    let responses = result
+        .get_mut("responses")
+        .and_then(Value::as_array_mut)
+        .map(std::mem::take)
+        .unwrap_or_default();
+    let docs: Vec<Value> = responses
+        .into_iter()
+        .zip(&documents)
+        .zip(&searches)
+        .map(|((mut response, document), (_, body))| {
+            if state.error_sanitizer_service.is_error(&response) {
+                let sanitized = state.error_sanitizer_service.sanitize(response, &filter.0);
+                return serde_json::json!({
+                    "_index": document.index,
+                    "_id": document.id,
+                    "error": sanitized["error"],
+                });
+            }
+            transform_search_response(&state, &mut response, body, &filter.0, &policies);
+            state
+                .document_service
+                .document_from_search(&response)
+                .unwrap_or_else(|| state.document_service.not_found(document))
+        })
+        .collect();
+
+    Json(serde_json::json!({ "docs": docs })).into_response()
+}
+
+#[instrument(skip(state, identity, ndjson_body), fields(index = %index, user = %identity.user, body_size = ndjson_body.0.len()))]
+pub async fn handle_msearch(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+    ndjson_body: NdjsonBody,
+) -> impl IntoResponse {
+    let NdjsonBody(ndjson_bytes) = ndjson_body;
+    debug!(
+        "Received msearch request for index '{}' with {} bytes of NDJSON data",
+        index,
+        ndjson_bytes.len()
+    );
+
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+    let params =
+        state
+            .search_params_service
+            .filter(SearchEndpoint::Msearch, params, &policies.params);
+    let params = match params {
+        Ok(params) => params,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(rejection) = state
+        .index_authorization_service
+        .authorize(&index, &policies.indices)
+    {
+        return rejection.into_response();
+    }
+
+    // The body was validated by the extractor, so parsing cannot fail here
+    let lines = match parse_ndjson_lines(&ndjson_bytes) {
+        Ok(lines) => lines,
+        Err(e) => return NdjsonError(e).into_response(),
+    };
+
+    execute_msearch(&state, &index, params, lines, &filter.0, &policies).await
+}
+
+/// Runs an `_msearch` without a default index. Searches whose header names
+/// no index target every index, which must be readable.
+pub async fn handle_msearch_all(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+    NdjsonBody(ndjson_bytes): NdjsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+    let params =
+        match state
+            .search_params_service
+            .filter(SearchEndpoint::Msearch, params, &policies.params)
+        {
+            Ok(params) => params,
+            Err(rejection) => return rejection.into_response(),
+        };
+
+    // The body was validated by the extractor, so parsing cannot fail here
+    let mut lines = match parse_ndjson_lines(&ndjson_bytes) {
+        Ok(lines) => lines,
+        Err(e) => return NdjsonError(e).into_response(),
+    };
+    for header in lines.iter_mut().step_by(2) {
+        if let Some(header) = header.as_object_mut() {
+            header
+                .entry("index")
+                .or_insert_with(|| Value::String("_all".to_string()));
+        }
+    }
+
+    execute_msearch(&state, "_all", params, lines, &filter.0, &policies).await
+}
+
+/// Authorizes and prepares the searches of an `_msearch` body, sends them
+/// with the security filter applied and transforms every response.
+async fn execute_msearch(
+    state: &OpenSearchRouterState,
+    index: &str,
+    params: SearchParams,
+    mut lines: Vec<Value>,
+    filter: &Value,
+    policies: &CallerPolicies,
+) -> Response {
+    // Lines alternate between a header and a search body
+    for header in lines.iter_mut().step_by(2) {
+        if let Err(rejection) = state
+            .index_authorization_service
+            .authorize_header(header, &policies.indices)
+        {
+            return rejection.into_response();
+        }
+        if let Err(rejection) = state
+            .search_params_service
+            .restrict_msearch_header(header, &policies.params)
+        {
+            return rejection.into_response();
+        }
+    }
+    for body in lines.iter_mut().skip(1).step_by(2) {
+        *body = match prepare_search_body(state, body.take(), policies) {
+            Ok(body) => body,
+            Err(rejection) => return rejection.into_response(),
+        };
+    }
+    let filtered_lines: Vec<Value> = lines
+        .iter()
+        .enumerate()
+        .map(|(position, line)| match position % 2 {
+            0 => line.clone(),
+            _ => state
+                .security_filter_service
+                .apply(line.clone(), filter.clone()),
+        })
+        .collect();
+
+    match state
+        .opensearch_repo
+        .msearch(index, to_ndjson_bytes(&filtered_lines), &params)
+        .await
+    {
+        Ok(mut result) => {
+            debug!("MSearch request successful for index '{}'", index);
+            if let Some(responses) = result.get_mut("responses").and_then(Value::as_array_mut) {
+                let bodies = lines.iter().skip(1).step_by(2);
+                for (response, body) in responses.iter_mut().zip(bodies) {
+                    if state.error_sanitizer_service.is_error(response) {
+                        *response = state
+                            .error_sanitizer_service
+                            .sanitize(response.take(), filter);
+                    } else {
+                        transform_search_response(state, response, body, filter, policies);
+                    }
+                }
+            }
+            Json(result).into_response()
+        }
+        Err(e) => {
+            error!("MSearch error for index '{}': {}", index, e);
+            repository_error_response(state, e, filter)
+        }
+    }
+}
+
+#[instrument(skip(state, identity, ndjson_body), fields(index = %index, user = %identity.user, body_size = ndjson_body.0.len()))]
+pub async fn handle_msearch_template(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+    ndjson_body: NdjsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+    let params =
+        state
+            .search_params_service
+            .filter(SearchEndpoint::Msearch, params, &policies.params);
+    let params = match params {
+        Ok(params) => params,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(rejection) = state
+        .index_authorization_service
+        .authorize(&index, &policies.indices)
+    {
+        return rejection.into_response();
+    }
+
+    // The body was validated by the extractor, so parsing cannot fail here
+    let mut lines = match parse_ndjson_lines(&ndjson_body.0) {
+        Ok(lines) => lines,
+        Err(e) => return NdjsonError(e).into_response(),
+    };
+    // Every template is rendered into the search body of a plain _msearch
+    for body in lines.iter_mut().skip(1).step_by(2) {
+        *body = match render_search_template(&state, body, &policies).await {
+            Ok(body) => body,
+            Err(rejection) => return rejection.into_response(),
+        };
+    }
+
+    execute_msearch(&state, &index, params, lines, &filter.0, &policies).await
+}
+
+#[instrument(skip(state, identity, ndjson_body), fields(user = %identity.user, body_size = ndjson_body.0.len()))]
+pub async fn handle_bulk(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+    ndjson_body: NdjsonBody,
+) -> impl IntoResponse {
+    bulk(&state, &identity, None, params, ndjson_body).await
+}
+
+#[instrument(skip(state, identity, ndjson_body), fields(index = %index, user = %identity.user, body_size = ndjson_body.0.len()))]
+pub async fn handle_index_bulk(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+    ndjson_body: NdjsonBody,
+) -> impl IntoResponse {
+    bulk(&state, &identity, Some(&index), params, ndjson_body).await
+}
+
+/// Parses a bulk request and executes its items.
+async fn bulk(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    index: Option<&str>,
+    params: SearchParams,
+    ndjson_body: NdjsonBody,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(state, identity);
+
+    let params =
+        match state
+            .search_params_service
+            .filter(SearchEndpoint::Bulk, params, &policies.params)
+        {
+            Ok(params) => params,
+            Err(rejection) => return rejection.into_response(),
+        };
+    // The body was validated by the extractor, so parsing cannot fail here
+    let lines = match parse_ndjson_lines(&ndjson_body.0) {
+        Ok(lines) => lines,
+        Err(e) => return NdjsonError(e).into_response(),
+    };
+    let items = match state.bulk_service.parse(index, lines) {
+        Ok(items) => items,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    match execute_bulk(state, items, &params, &filter.0, &policies).await {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => repository_error_response(state, e, &filter.0),
+    }
+}
+
+/// Checks every bulk item on its own and sends the accepted items.
+/// Rejected items are reported in the bulk response at their position,
+/// next to the results of OpenSearch.
+async fn execute_bulk(
+    state: &OpenSearchRouterState,
+    mut items: Vec<BulkItem>,
+    params: &SearchParams,
+    filter: &Value,
+    policies: &CallerPolicies,
+) -> Result<Value, RepositoryError> {
+    let mut answers: Vec<Option<Value>> = items
+        .iter_mut()
+        .map(|item| {
+            check_bulk_item(state, item, policies)
+                .err()
+                .map(|error| state.bulk_service.rejected(item, error))
+        })
+        .collect();
+    let visible = visible_documents(state, &items, &answers, filter).await?;
+    for (item, answer) in items.iter_mut().zip(answers.iter_mut()) {
+        if answer.is_some() || !state.bulk_service.requires_visibility(item) {
+            continue;
+        }
+        if let Some(id) = &item.id
+            && !visible.contains(&(item.index.clone(), id.clone()))
+        {
+            *answer = state.bulk_service.invisible(item);
+        }
+    }
+
+    let accepted = items
+        .iter()
+        .zip(&answers)
+        .filter(|(_, answer)| answer.is_none())
+        .map(|(item, _)| item);
+    let lines = state.bulk_service.to_lines(accepted);
+    let upstream = if lines.is_empty() {
+        None
+    } else {
+        Some(
+            state
+                .opensearch_repo
+                .bulk(to_ndjson_bytes(&lines), params)
+                .await?,
+        )
+    };
+
+    Ok(state.bulk_service.merge_response(&items, answers, upstream))
+}
+
+pub async fn handle_index_document(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path((index, id)): Path<(String, String)>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    write_document(
+        &state,
+        &identity,
+        BulkAction::Index,
+        &index,
+        Some(id),
+        Some(payload),
+        params,
+    )
+    .await
+}
+
+pub async fn handle_index_new_document(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    write_document(
+        &state,
+        &identity,
+        BulkAction::Index,
+        &index,
+        None,
+        Some(payload),
+        params,
+    )
+    .await
+}
+
+pub async fn handle_create_document(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path((index, id)): Path<(String, String)>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    write_document(
+        &state,
+        &identity,
+        BulkAction::Create,
+        &index,
+        Some(id),
+        Some(payload),
+        params,
+    )
+    .await
+}
+
+pub async fn handle_update_document(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path((index, id)): Path<(String, String)>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    write_document(
+        &state,
+        &identity,
+        BulkAction::Update,
+        &index,
+        Some(id),
+        Some(payload),
+        params,
+    )
+    .await
+}
+
+pub async fn handle_delete_document(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path((index, id)): Path<(String, String)>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    write_document(
+        &state,
+        &identity,
+        BulkAction::Delete,
+        &index,
+        Some(id),
+        None,
+        params,
+    )
+    .await
+}
+
+/// Writes a single document as a bulk of one item, so that it is checked
+/// exactly like a bulk action, and answers like the native API.
+async fn write_document(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    action: BulkAction,
+    index: &str,
+    id: Option<String>,
+    source: Option<Value>,
+    params: SearchParams,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(state, identity);
+
+    let params =
+        match state
+            .search_params_service
+            .filter(SearchEndpoint::Write, params, &policies.params)
+        {
+            Ok(params) => params,
+            Err(rejection) => return rejection.into_response(),
+        };
+    let (item, params) = state
+        .bulk_service
+        .single_item(action, index, id, params, source);
+
+    match execute_bulk(state, vec![item], &params, &filter.0, &policies).await {
+        Ok(result) => {
+            let (status, body) = state.bulk_service.single_response(result);
+            (status, Json(body)).into_response()
+        }
+        Err(e) => repository_error_response(state, e, &filter.0),
+    }
+}
+
+/// Validates a bulk item, authorizes its index for writes and enforces
+/// the owner fields and field restrictions of the caller on its document.
+fn check_bulk_item(
+    state: &OpenSearchRouterState,
+    item: &mut BulkItem,
+    policies: &CallerPolicies,
+) -> Result<(), BulkItemError> {
+    state.bulk_service.validate(item)?;
+    state.bulk_service.check_script(
+        item,
+        &policies.fields,
+        &policies.masking,
+        &policies.pseudonyms,
+    )?;
+    state
+        .index_authorization_service
+        .authorize(&item.index, &policies.writes)?;
+    match (item.action, item.source.as_mut()) {
+        (BulkAction::Index | BulkAction::Create, Some(document)) => state
+            .owner_field_service
+            .check_document(document, &policies.owner)?,
+        (BulkAction::Update, Some(body)) => state
+            .owner_field_service
+            .check_update(body, &policies.owner)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Looks up which documents targeted by the unanswered items are visible
+/// through the security filter, as pairs of index and id. Missing indices
+/// have no visible documents.
+async fn visible_documents(
+    state: &OpenSearchRouterState,
+    items: &[BulkItem],
+    answers: &[Option<Value>],
+    filter: &Value,
+) -> Result<HashSet<(String, String)>, RepositoryError> {
+    let mut ids: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (item, _) in items
+        .iter()
+        .zip(answers)
+        .filter(|(item, answer)| answer.is_none() && state.bulk_service.requires_visibility(item))
+    {
+        if let Some(id) = &item.id {
+            ids.entry(&item.index).or_default().push(id);
+        }
+    }
+
+    let mut visible = HashSet::new();
+    for (index, ids) in ids {
+        for search in state.bulk_service.visibility_searches(&ids) {
+            let search = state.security_filter_service.apply(search, filter.clone());
+            let result = match state.opensearch_repo.search(index, search, &[]).await {
+                Ok(result) => result,
+                Err(RepositoryError::Upstream {
+                    status: StatusCode::NOT_FOUND,
+                    ..
+                }) => continue,
+                Err(e) => return Err(e),
+            };
+            let hits = result.pointer("/hits/hits").and_then(Value::as_array);
+            visible.extend(
+                hits.into_iter()
+                    .flatten()
+                    .filter_map(|hit| hit.get("_id").and_then(Value::as_str))
+                    .map(|id| (index.to_string(), id.to_string())),
+            );
+        }
+    }
+    Ok(visible)
+}
+
+/// Operations changing every document matched by a query.
+#[derive(Debug, Clone, Copy)]
+enum ByQueryOperation {
+    Update,
+    Delete,
+}
+
+pub async fn handle_update_by_query(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    by_query(
+        &state,
+        &identity,
+        ByQueryOperation::Update,
+        &index,
+        params,
+        payload,
+    )
+    .await
+}
+
+pub async fn handle_delete_by_query(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    by_query(
+        &state,
+        &identity,
+        ByQueryOperation::Delete,
+        &index,
+        params,
+        payload,
+    )
+    .await
+}
+
+/// Runs an update or delete by query on the documents of the security
+/// filter. The caller needs write permission on the index, and a task
+/// started with `wait_for_completion=false` is bound to the caller. Callers
+/// with owner fields may not run update scripts, and scripts count as
+/// referencing any field for field restrictions.
+async fn by_query(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    operation: ByQueryOperation,
+    index: &str,
+    params: SearchParams,
+    payload: Value,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(state, identity);
+    let endpoint = match operation {
+        ByQueryOperation::Update => SearchEndpoint::UpdateByQuery,
+        ByQueryOperation::Delete => SearchEndpoint::DeleteByQuery,
+    };
+
+    let prepared = state
+        .index_authorization_service
+        .authorize(index, &policies.writes)
+        .map_err(SearchRejection::from)
+        .and_then(|_| {
+            Ok(state
+                .search_params_service
+                .filter(endpoint, params, &policies.params)?)
+        })
+        .and_then(|params| {
+            if matches!(operation, ByQueryOperation::Update) {
+                state
+                    .owner_field_service
+                    .check_update_by_query(&payload, &policies.owner)?;
+            }
+            Ok((params, prepare_query_body(state, payload, &policies)?))
+        });
+    let (params, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let query_with_security_filter = state
+        .security_filter_service
+        .apply(payload, filter.0.clone());
+
+    let repo = &state.opensearch_repo;
+    let result = match operation {
+        ByQueryOperation::Update => {
+            repo.update_by_query(index, query_with_security_filter, &params)
+                .await
+        }
+        ByQueryOperation::Delete => {
+            repo.delete_by_query(index, query_with_security_filter, &params)
+                .await
+        }
+    };
+    match result {
+        Ok(result) => {
+            state.task_service.register(identity, &result);
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(state, e, &filter.0),
+    }
+}
+
+pub async fn handle_sql(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    sql(&state, &identity, SqlLanguage::Sql, params, payload).await
+}
+
+pub async fn handle_ppl(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    sql(&state, &identity, SqlLanguage::Ppl, params, payload).await
+}
+
+/// Runs an SQL or PPL query on the documents of the security filter. The
+/// indices of the query are authorized, and cursors of paginated queries
+/// are bound to the caller.
+async fn sql(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    language: SqlLanguage,
+    params: SearchParams,
+    payload: Value,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(state, identity);
+
+    let prepared = state
+        .sql_service
+        .authorize_caller(&policies.fields, &policies.masking, &policies.pseudonyms)
+        .map_err(SearchRejection::from)
+        .and_then(|_| {
+            Ok(state
+                .search_params_service
+                .filter(SearchEndpoint::Sql, params, &policies.params)?)
+        })
+        .and_then(|params| {
+            let payload = match state.sql_service.parse_request(language, payload)? {
+                SqlRequest::Cursor(cursor) => {
+                    state.sql_service.authorize_cursor(identity, &cursor)?;
+                    serde_json::json!({ "cursor": cursor })
+                }
+                SqlRequest::Query { indices, body } => {
+                    for index in &indices {
+                        state
+                            .index_authorization_service
+                            .authorize(index, &policies.indices)?;
+                    }
+                    state.sql_service.apply_filter(language, body, &filter.0)?
+                }
+            };
+            Ok((params, payload))
+        });
+    let (params, payload) = match prepared {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let result = match language {
+        SqlLanguage::Sql => state.opensearch_repo.sql(payload, &params).await,
+        SqlLanguage::Ppl => state.opensearch_repo.ppl(payload, &params).await,
+    };
+    match result {
+        Ok(result) => {
+            state.sql_service.register_cursor(identity, &result);
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(state, e, &filter.0),
+    }
+}
+
+pub async fn handle_close_sql_cursor(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    OptionalJsonBody(payload): OptionalJsonBody,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let cursor = match state.sql_service.parse_request(SqlLanguage::Sql, payload) {
+        Ok(SqlRequest::Cursor(cursor)) => cursor,
+        Ok(SqlRequest::Query { .. }) => {
+            return SqlRejection::Invalid("[cursor] is missing".to_string()).into_response();
+        }
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(rejection) = state.sql_service.authorize_cursor(&identity, &cursor) {
+        return rejection.into_response();
+    }
+
+    let payload = serde_json::json!({ "cursor": cursor });
+    match state.opensearch_repo.close_sql_cursor(payload).await {
+        Ok(result) => {
+            state.sql_service.close_cursor(&cursor);
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_get_task(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(task_id): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(&state, &identity);
+
+    let params = state
+        .task_service
+        .authorize(&identity, &task_id)
+        .map_err(SearchRejection::from)
+        .and_then(|_| {
+            Ok(state.search_params_service.filter(
+                SearchEndpoint::Task,
+                params,
+                &policies.params,
+            )?)
+        });
+    let params = match params {
+        Ok(params) => params,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    match state.opensearch_repo.get_task(&task_id, &params).await {
+        Ok(mut result) => {
+            // A failed task reports the error of the filtered operation
+            if state.error_sanitizer_service.is_error(&result) {
+                let error = serde_json::json!({ "error": result["error"].take() });
+                result["error"] =
+                    state.error_sanitizer_service.sanitize(error, &filter.0)["error"].take();
+            }
+            Json(result).into_response()
+        }
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_cancel_task(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(task_id): Path<String>,
+) -> impl IntoResponse {
+    let filter = state.filter_repository.get_filter();
+    if let Err(rejection) = state.task_service.authorize(&identity, &task_id) {
+        return rejection.into_response();
+    }
+
+    match state.opensearch_repo.cancel_task(&task_id).await {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => repository_error_response(&state, e, &filter.0),
+    }
+}
+
+pub async fn handle_cluster_health(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    cluster_health(&state, &identity, None, params).await
+}
+
+pub async fn handle_index_cluster_health(
+    State(state): State<OpenSearchRouterState>,
+    identity: Identity,
+    Path(index): Path<String>,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    cluster_health(&state, &identity, Some(index), params).await
+}
+
+/// Returns the health of the cluster or of readable indices. Callers that
+/// may not monitor the cluster only receive the status.
+async fn cluster_health(
+    state: &OpenSearchRouterState,
+    identity: &Identity,
+    index: Option<String>,
+    params: SearchParams,
+) -> Response {
+    let filter = state.filter_repository.get_filter();
+    let policies = CallerPolicies::resolve(state, identity);
+    let monitor = state
+        .cluster_info_service
+        .permits_monitor(state.policy_repository.get_policy(), identity);
+
+    let (prune, mut params) = match prepare_listing(
+        state,
+        SearchEndpoint::ClusterHealth,
+        index.as_deref(),
+        params,
+        &policies,
+    ) {
+        Ok(prepared) => prepared,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if !monitor {
+        params.retain(|(name, _)| name != "level");
+    }
+
+    let (status, mut health) = match state
+        .opensearch_repo
+        .cluster_health(index.as_deref(), &params)
+        .await
+    {
+        Ok(health) => (StatusCode::OK, health),
+        // Waiting for a status that is not reached answers with the health
+        Err(RepositoryError::Upstream {
+            status: StatusCode::REQUEST_TIMEOUT,
+            body,
+        }) => (StatusCode::REQUEST_TIMEOUT, body),
+        Err(e) => return repository_error_response(state, e, &filter.0),
+    };
+    if !monitor {
+        health = state.cluster_info_service.minimal_health(&health);
+    } else if prune {
+        state
+            .cluster_info_service
+            .restrict_health(&mut health, &policies.indices);
+    }
+    (status, Json(health)).into_response()
+}
+
+/// Returns the root info of the cluster, cached by the proxy. Like every
+/// upstream backed route, it requires an identity.
+pub async fn handle_root_info(
+    State(state): State<OpenSearchRouterState>,
+    _identity: Identity,
+) -> impl IntoResponse {
+    if let Some(info) = state.cluster_info_service.cached_root_info() {
+        return Json(info).into_response();
+    }
+    match state.opensearch_repo.root_info().await {
+        Ok(result) => Json(state.cluster_info_service.root_info(&result)).into_response(),
+        Err(e) => repository_error_response(&state, e, &state.filter_repository.get_filter().0),
+    }
+}
+
+/// Answers `HEAD /` with `200 OK` if OpenSearch is reachable.
+pub async fn handle_ping(
+    State(state): State<OpenSearchRouterState>,
+    _identity: Identity,
+) -> impl IntoResponse {
+    match state.opensearch_repo.ping().await {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            error!("Ping of OpenSearch failed: {}", e);
+            e.status()
+        }
+    }
+}
+
+/// Returns the HTTP info of the nodes with the proxy, at the address the
+/// client used to reach it, as the only node.
+pub async fn handle_nodes_http(
+    State(state): State<OpenSearchRouterState>,
+    _identity: Identity,
+    headers: HeaderMap,
+    uri: Uri,
+) -> impl IntoResponse {
+    let Some(address) = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| uri.authority().map(|authority| authority.as_str()))
+    else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": {
+                    "type": "illegal_argument_exception",
+                    "reason": "the request has no Host header",
+                },
+                "status": StatusCode::BAD_REQUEST.as_u16(),
+            })),
+        )
+            .into_response();
+    };
+
+    match state.opensearch_repo.nodes_http().await {
+        Ok(result) => Json(state.cluster_info_service.nodes_http(&result, address)).into_response(),
+        Err(e) => repository_error_response(&state, e, &state.filter_repository.get_filter().0),
+    }
+}
+
+/// Applies the OpenSearch Dashboards mode before a request is routed.
+///
+/// Requests Dashboards sends on behalf of a user continue with the
+/// identity of that user, while requests of the service account to the
+/// endpoints of the system index of Dashboards are passed through to
+/// OpenSearch unchanged.
+pub async fn dashboards_mode(
+    State(state): State<OpenSearchRouterState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !state.dashboards_service.is_enabled() {
+        return next.run(request).await;
+    }
+    let caller = state
+        .dashboards_service
+        .resolve_caller(request.headers_mut());
+    if caller != DashboardsCaller::ServiceAccount
+        || !state
+            .dashboards_service
+            .targets_system_index(request.uri().path())
+    {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let method = match parts.method {
+        axum::http::Method::GET => Method::Get,
+        axum::http::Method::PUT => Method::Put,
+        axum::http::Method::POST => Method::Post,
+        axum::http::Method::DELETE => Method::Delete,
+        axum::http::Method::HEAD => Method::Head,
+        _ => return StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    };
+    let params = Query::<SearchParams>::try_from_uri(&parts.uri)
+        .map(|Query(params)| params)
+        .unwrap_or_default();
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let body = match to_bytes(body, MAX_FORWARDED_BODY).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+
+    debug!("Passing through system index request {}", parts.uri.path());
+    match state
+        .opensearch_repo
+        .forward(method, parts.uri.path(), &params, content_type, body)
+        .await
+    {
+        Ok(forwarded) => {
+            let mut response = (forwarded.status, forwarded.body).into_response();
+            if let Some(content_type) = forwarded
+                .content_type
+                .and_then(|value| HeaderValue::from_str(&value).ok())
+            {
+                response
+                    .headers_mut()
+                    .insert(header::CONTENT_TYPE, content_type);
+            }
+            response
         }
+        Err(e) => repository_error_response(&state, e, &state.filter_repository.get_filter().0),
     }
 }
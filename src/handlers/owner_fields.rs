@@ -0,0 +1,330 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+use crate::auth::identity::Identity;
+use crate::models::policy::{OwnerFieldMode, Policy};
+
+/// Placeholder in owner field values replaced by the caller's user name.
+const USER_PLACEHOLDER: &str = "{{user}}";
+
+/// Error returned when a written document violates an owner field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerFieldViolation(pub String);
+
+impl IntoResponse for OwnerFieldViolation {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected write violating owner fields: {}", self.0);
+
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": { "type": "security_exception", "reason": self.0 },
+                "status": StatusCode::FORBIDDEN.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// The owner fields enforced on the writes of a caller.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OwnerPolicy {
+    /// Field paths with their required value and enforcement
+    fields: Vec<(String, Value, OwnerFieldMode)>,
+}
+
+impl OwnerPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+/// A service enforcing owner fields on written documents.
+///
+/// Roles can require fields such as the owning tenant on every document
+/// they write. Fields are either stamped with the required value or
+/// validated, and partial updates may not change them, so that written
+/// documents stay within the security filter of their owner.
+#[derive(Clone)]
+pub struct OwnerFieldService;
+
+impl OwnerFieldService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Resolves the owner fields of the given identity. If several roles
+    /// define the same field, the first definition is used.
+    pub fn policy_for(&self, policy: &Policy, identity: &Identity) -> OwnerPolicy {
+        let mut fields: Vec<(String, Value, OwnerFieldMode)> = Vec::new();
+        for owner_field in policy
+            .roles_for(identity)
+            .into_iter()
+            .flat_map(|role| &role.owner_fields)
+        {
+            if fields
+                .iter()
+                .any(|(field, _, _)| *field == owner_field.field)
+            {
+                continue;
+            }
+            let value = match &owner_field.value {
+                Value::String(value) => {
+                    Value::String(value.replace(USER_PLACEHOLDER, &identity.user))
+                }
+                value => value.clone(),
+            };
+            fields.push((owner_field.field.clone(), value, owner_field.mode));
+        }
+        OwnerPolicy { fields }
+    }
+
+    /// Enforces the owner fields on a complete document, stamping or
+    /// validating each field.
+    pub fn check_document(
+        &self,
+        document: &mut Value,
+        policy: &OwnerPolicy,
+    ) -> Result<(), OwnerFieldViolation> {
+        let Some(object) = document.as_object_mut() else {
+            return Err(OwnerFieldViolation(
+                "document must be an object".to_string(),
+            ));
+        };
+        for (field, required, mode) in &policy.fields {
+            match mode {
+                OwnerFieldMode::Stamp => {
+                    remove_field(object, field);
+                    set_field(object, field, required.clone());
+                }
+                OwnerFieldMode::Validate => {
+                    let mut values = Vec::new();
+                    find_field(object, field, &mut values);
+                    if values.is_empty() || values.iter().any(|value| *value != required) {
+                        return Err(must_be(field, required));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects partial documents of updates that change an owner field.
+    /// Owner fields may only be repeated with their required value.
+    pub fn check_partial(
+        &self,
+        document: &Value,
+        policy: &OwnerPolicy,
+    ) -> Result<(), OwnerFieldViolation> {
+        let Some(object) = document.as_object() else {
+            return Err(OwnerFieldViolation(
+                "document must be an object".to_string(),
+            ));
+        };
+        for (field, required, _) in &policy.fields {
+            let mut values = Vec::new();
+            find_field(object, field, &mut values);
+            if values.iter().any(|value| *value != required) {
+                return Err(must_be(field, required));
+            }
+        }
+        Ok(())
+    }
+
+    /// Enforces the owner fields on the body of an update. The partial
+    /// document may not change them, upserted documents are checked like
+    /// complete ones and scripts are rejected, since they could change any
+    /// field.
+    pub fn check_update(
+        &self,
+        body: &mut Value,
+        policy: &OwnerPolicy,
+    ) -> Result<(), OwnerFieldViolation> {
+        if policy.is_empty() {
+            return Ok(());
+        }
+        if body.get("script").is_some() {
+            return Err(OwnerFieldViolation(
+                "scripted updates are not permitted with owner fields".to_string(),
+            ));
+        }
+        let doc_as_upsert = body.get("doc_as_upsert").and_then(Value::as_bool) == Some(true);
+        if let Some(document) = body.get_mut("doc") {
+            if doc_as_upsert {
+                self.check_document(document, policy)?;
+            } else {
+                self.check_partial(document, policy)?;
+            }
+        }
+        if let Some(upsert) = body.get_mut("upsert") {
+            self.check_document(upsert, policy)?;
+        }
+        Ok(())
+    }
+
+    /// Rejects the script of an update by query, which could move the
+    /// matched documents out of the security filter of their owner.
+    pub fn check_update_by_query(
+        &self,
+        body: &Value,
+        policy: &OwnerPolicy,
+    ) -> Result<(), OwnerFieldViolation> {
+        if !policy.is_empty() && body.get("script").is_some() {
+            return Err(OwnerFieldViolation(
+                "scripted updates are not permitted with owner fields".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn must_be(field: &str, required: &Value) -> OwnerFieldViolation {
+    OwnerFieldViolation(format!("field [{}] must be [{}]", field, required))
+}
+
+/// Collects the values of a dotted field path, which may be stored as
+/// nested objects, as keys containing dots or a mix of both.
+fn find_field<'a>(object: &'a Map<String, Value>, path: &str, values: &mut Vec<&'a Value>) {
+    for (key, value) in object {
+        if key == path {
+            values.push(value);
+        } else if let Some(rest) = path
+            .strip_prefix(key.as_str())
+            .and_then(|rest| rest.strip_prefix('.'))
+            && let Value::Object(child) = value
+        {
+            find_field(child, rest, values);
+        }
+    }
+}
+
+/// Removes every occurrence of a dotted field path.
+fn remove_field(object: &mut Map<String, Value>, path: &str) {
+    object.remove(path);
+    for (key, value) in object.iter_mut() {
+        if let Some(rest) = path
+            .strip_prefix(key.as_str())
+            .and_then(|rest| rest.strip_prefix('.'))
+            && let Value::Object(child) = value
+        {
+            remove_field(child, rest);
+        }
+    }
+}
+
+/// Sets a dotted field path as nested objects.
+fn set_field(object: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once('.') {
+        None => {
+            object.insert(path.to_string(), value);
+        }
+        Some((key, rest)) => {
+            let child = object
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !child.is_object() {
+                *child = Value::Object(Map::new());
+            }
+            if let Value::Object(child) = child {
+                set_field(child, rest, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> OwnerPolicy {
+        let policy: Policy = serde_json::from_value(json!({
+            "roles": {
+                "tenant": { "owner_fields": [
+                    { "field": "tenant_id", "value": "acme", "mode": "validate" },
+                    { "field": "meta.owner", "value": "{{user}}" }
+                ] },
+                "other": { "owner_fields": [{ "field": "tenant_id", "value": "other" }] }
+            }
+        }))
+        .unwrap();
+        let identity = Identity {
+            user: "alice".to_string(),
+            roles: vec!["tenant".to_string(), "other".to_string()],
+        };
+        OwnerFieldService::new().policy_for(&policy, &identity)
+    }
+
+    #[test]
+    fn test_check_document() {
+        let service = OwnerFieldService::new();
+        let policy = policy();
+
+        let mut document = json!({ "tenant_id": "acme", "meta.owner": "bob", "title": "Rust" });
+        service.check_document(&mut document, &policy).unwrap();
+        assert_eq!(
+            document,
+            json!({ "tenant_id": "acme", "meta": { "owner": "alice" }, "title": "Rust" })
+        );
+
+        assert_eq!(
+            service.check_document(&mut json!({ "tenant_id": "other" }), &policy),
+            Err(OwnerFieldViolation(
+                "field [tenant_id] must be [\"acme\"]".to_string()
+            ))
+        );
+        assert!(
+            service
+                .check_document(&mut json!({ "title": "Rust" }), &policy)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_partial() {
+        let service = OwnerFieldService::new();
+        let policy = policy();
+
+        assert!(
+            service
+                .check_partial(&json!({ "title": "Rust", "tenant_id": "acme" }), &policy)
+                .is_ok()
+        );
+        assert!(
+            service
+                .check_partial(&json!({ "meta": { "owner": "bob" } }), &policy)
+                .is_err()
+        );
+        assert!(
+            service
+                .check_update(
+                    &mut json!({ "script": { "source": "ctx._source.x = 1" } }),
+                    &policy
+                )
+                .is_err()
+        );
+
+        let script = json!({ "script": { "source": "ctx._source.tenant_id = 'other'" } });
+        assert!(service.check_update_by_query(&script, &policy).is_err());
+        assert!(
+            service
+                .check_update_by_query(&script, &OwnerPolicy::default())
+                .is_ok()
+        );
+        assert!(
+            service
+                .check_update_by_query(&json!({ "query": { "match_all": {} } }), &policy)
+                .is_ok()
+        );
+
+        let mut body = json!({ "doc": { "title": "Go" }, "upsert": { "tenant_id": "acme" } });
+        service.check_update(&mut body, &policy).unwrap();
+        assert_eq!(
+            body["upsert"],
+            json!({ "tenant_id": "acme", "meta": { "owner": "alice" } })
+        );
+    }
+}
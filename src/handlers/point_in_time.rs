@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+use crate::auth::identity::Identity;
+use crate::repositories::context::SearchContextRepository;
+
+/// Reasons a point in time request is rejected before it reaches
+/// OpenSearch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PitRejection {
+    /// An index-less search does not name a point in time
+    MissingPit,
+    /// The point in time is unknown, expired or was created by another
+    /// identity
+    ContextMissing(String),
+}
+
+impl IntoResponse for PitRejection {
+    fn into_response(self) -> Response {
+        let (status, error_type, reason) = match self {
+            Self::MissingPit => (
+                StatusCode::BAD_REQUEST,
+                "action_request_validation_exception",
+                "Validation Failed: 1: searches without an index require a [pit] with an [id];"
+                    .to_string(),
+            ),
+            Self::ContextMissing(pit_id) => {
+                tracing::warn!("Rejected use of unknown point in time '{}'", pit_id);
+                (
+                    StatusCode::NOT_FOUND,
+                    "search_context_missing_exception",
+                    format!("No search context found for id [{}]", pit_id),
+                )
+            }
+        };
+
+        (
+            status,
+            Json(json!({
+                "error": { "type": error_type, "reason": reason },
+                "status": status.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A point in time created through the proxy.
+#[derive(Debug, Clone)]
+pub struct PitContext {
+    /// The index expression the point in time was created for
+    pub index: String,
+    /// The keep-alive of the point in time
+    pub keep_alive: Duration,
+}
+
+/// A service binding points in time to the identity that created them.
+///
+/// A PIT id identifies the indices of a search on its own, so searches
+/// using it have no index path to authorize. Every point in time created
+/// through the proxy is therefore remembered with its creator and indices,
+/// and only that identity may search or delete it.
+#[derive(Clone)]
+pub struct PointInTimeService {
+    contexts: SearchContextRepository<PitContext>,
+}
+
+impl PointInTimeService {
+    pub fn new(contexts: SearchContextRepository<PitContext>) -> Self {
+        Self { contexts }
+    }
+
+    /// Remembers the point in time created by a create response, if any.
+    pub fn register(&self, identity: &Identity, response: &Value, context: PitContext) {
+        if let Some(pit_id) = response.get("pit_id").and_then(Value::as_str) {
+            self.contexts
+                .insert(pit_id, identity, context.keep_alive, context);
+        }
+    }
+
+    /// Returns the id of the point in time a search body uses.
+    pub fn pit_id<'a>(&self, body: &'a Value) -> Result<&'a str, PitRejection> {
+        body.pointer("/pit/id")
+            .and_then(Value::as_str)
+            .ok_or(PitRejection::MissingPit)
+    }
+
+    /// Returns the context of a point in time created by the given identity.
+    pub fn context(&self, identity: &Identity, pit_id: &str) -> Result<PitContext, PitRejection> {
+        self.contexts
+            .get(pit_id, identity)
+            .ok_or_else(|| PitRejection::ContextMissing(pit_id.to_string()))
+    }
+
+    /// Updates a point in time after a search, which may hand out a new
+    /// id.
+    pub fn searched(
+        &self,
+        identity: &Identity,
+        pit_id: &str,
+        response: &Value,
+        context: PitContext,
+    ) {
+        match response.get("pit_id").and_then(Value::as_str) {
+            Some(next) if next != pit_id => {
+                self.contexts.remove(pit_id);
+                self.register(identity, response, context);
+            }
+            _ => self.contexts.extend(pit_id, context.keep_alive),
+        }
+    }
+
+    /// Returns the PIT ids of a delete request that belong to the given
+    /// identity. Without a body, every point in time of the identity is
+    /// selected.
+    pub fn owned_pit_ids(&self, identity: &Identity, body: Option<&Value>) -> Vec<String> {
+        let Some(body) = body else {
+            return self.contexts.ids_of(identity);
+        };
+        let mut requested: Vec<String> = match body.get("pit_id") {
+            Some(Value::String(pit_id)) => vec![pit_id.clone()],
+            Some(Value::Array(pit_ids)) => pit_ids
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+        requested.retain(|pit_id| self.contexts.get(pit_id, identity).is_some());
+        requested
+    }
+
+    /// Forgets deleted or expired points in time.
+    pub fn forget(&self, pit_ids: &[String]) {
+        for pit_id in pit_ids {
+            self.contexts.remove(pit_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(user: &str) -> Identity {
+        Identity {
+            user: user.to_string(),
+            roles: vec!["reader".to_string()],
+        }
+    }
+
+    fn context() -> PitContext {
+        PitContext {
+            index: "movies".to_string(),
+            keep_alive: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_pit_is_bound_to_creator() {
+        let service = PointInTimeService::new(SearchContextRepository::new());
+        service.register(&identity("alice"), &json!({ "pit_id": "abc" }), context());
+
+        let body = json!({ "pit": { "id": "abc", "keep_alive": "1m" } });
+        let pit_id = service.pit_id(&body).unwrap();
+        assert_eq!(
+            service.context(&identity("alice"), pit_id).unwrap().index,
+            "movies"
+        );
+        assert_eq!(
+            service.context(&identity("bob"), pit_id).unwrap_err(),
+            PitRejection::ContextMissing("abc".to_string())
+        );
+        assert_eq!(
+            service.pit_id(&json!({ "query": {} })),
+            Err(PitRejection::MissingPit)
+        );
+    }
+
+    #[test]
+    fn test_owned_pit_ids() {
+        let service = PointInTimeService::new(SearchContextRepository::new());
+        let alice = identity("alice");
+        service.register(&alice, &json!({ "pit_id": "abc" }), context());
+        service.register(&identity("bob"), &json!({ "pit_id": "def" }), context());
+
+        assert_eq!(
+            service.owned_pit_ids(&alice, Some(&json!({ "pit_id": ["abc", "def"] }))),
+            vec!["abc"]
+        );
+        assert_eq!(service.owned_pit_ids(&alice, None), vec!["abc"]);
+    }
+}
@@ -0,0 +1,680 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+use crate::auth::identity::Identity;
+use crate::config::Config;
+use crate::handlers::field_security::{FieldPolicy, pattern_covers, pattern_overlaps};
+use crate::handlers::masking::{REDACTED, hmac_hex, scalar_to_string};
+use crate::handlers::query_inspector::QueryInspectorService;
+use crate::handlers::search_response::for_each_hit;
+use crate::models::policy::{Policy, RolePolicy};
+use crate::repositories::pseudonym::PseudonymRepository;
+
+/// Prefix of every pseudonym handed out by the proxy.
+pub const PSEUDONYM_PREFIX: &str = "pseu_";
+
+/// Number of hex characters of the HMAC kept in a pseudonym.
+const PSEUDONYM_LENGTH: usize = 32;
+
+/// Queries that may reference pseudonymized fields, with their values
+/// translated back to the real ones.
+const TRANSLATED_QUERIES: &[&str] = &["term", "terms", "match"];
+
+/// Aggregations that may run on pseudonymized fields, since their results
+/// are either pseudonymized bucket keys or plain counts.
+const PSEUDONYMIZED_AGGS: &[&str] = &[
+    "terms",
+    "significant_terms",
+    "rare_terms",
+    "cardinality",
+    "value_count",
+];
+
+/// The fields pseudonymized for a caller.
+///
+/// As with masking, a field is only pseudonymized if every role of the
+/// caller that may read it pseudonymizes it.
+#[derive(Debug, Clone, Default)]
+pub struct PseudonymPolicy {
+    fields: Vec<String>,
+}
+
+impl PseudonymPolicy {
+    pub fn from_roles(roles: &[&RolePolicy]) -> Self {
+        let mut fields: Vec<String> = Vec::new();
+        for field in roles.iter().flat_map(|role| &role.pseudonymize) {
+            let pseudonymized_by_readers = roles.iter().all(|role| {
+                role.pseudonymize.contains(field)
+                    || !FieldPolicy::from_roles(&[role]).permits_pattern(field)
+            });
+            if pseudonymized_by_readers && !fields.contains(field) {
+                fields.push(field.clone());
+            }
+        }
+
+        Self { fields }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    fn covers(&self, field: &str) -> bool {
+        self.fields
+            .iter()
+            .any(|pattern| pattern_covers(pattern, field))
+    }
+
+    /// Returns whether a field reference of a request may touch a
+    /// pseudonymized field, including wildcards and references to any
+    /// field.
+    fn touches(&self, reference: &str) -> bool {
+        self.fields
+            .iter()
+            .any(|pattern| pattern_overlaps(reference, pattern))
+    }
+}
+
+/// Error returned when a pseudonymized field is used in a way that could
+/// reveal its real values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PseudonymizedField(pub String);
+
+impl IntoResponse for PseudonymizedField {
+    fn into_response(self) -> Response {
+        tracing::warn!(
+            "Rejected request revealing pseudonymized field '{}'",
+            self.0
+        );
+
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": {
+                    "type": "security_exception",
+                    "reason": format!(
+                        "field [{}] is pseudonymized and can only be queried by pseudonym using term, terms or match queries",
+                        self.0
+                    ),
+                },
+                "status": StatusCode::FORBIDDEN.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A service replacing configured field values with keyed deterministic
+/// pseudonyms.
+///
+/// Responses have pseudonymized fields and aggregation bucket keys replaced
+/// by pseudonyms, while pseudonyms used in `term`, `terms` and `match`
+/// queries are translated back to the real values. This allows analysts to
+/// group and drill down on a pseudonym without ever learning the real value.
+#[derive(Clone)]
+pub struct PseudonymizationService {
+    key: Option<Arc<[u8]>>,
+    repository: PseudonymRepository,
+}
+
+impl PseudonymizationService {
+    pub fn new(config: &Config, repository: PseudonymRepository) -> Self {
+        Self {
+            key: config
+                .masking_key
+                .as_ref()
+                .map(|key| Arc::from(key.as_bytes())),
+            repository,
+        }
+    }
+
+    /// Resolves the effective pseudonymization policy of the given identity.
+    pub fn policy_for(&self, policy: &Policy, identity: &Identity) -> PseudonymPolicy {
+        PseudonymPolicy::from_roles(&policy.roles_for(identity))
+    }
+
+    /// Translates pseudonyms in the queries of a search body back to real
+    /// values and rejects any other use of a pseudonymized field.
+    pub fn translate_request(
+        &self,
+        mut body: Value,
+        policy: &PseudonymPolicy,
+    ) -> Result<Value, PseudonymizedField> {
+        if policy.is_empty() {
+            return Ok(body);
+        }
+
+        let mut remaining = body.clone();
+        neutralize_permitted_uses(&mut remaining, policy);
+        if let Some(field) = QueryInspectorService::new()
+            .referenced_fields(&remaining)
+            .into_iter()
+            .find(|field| policy.touches(field))
+        {
+            return Err(PseudonymizedField(field));
+        }
+
+        for key in ["query", "post_filter"] {
+            if let Some(query) = body.get_mut(key) {
+                self.translate_query(query, policy)?;
+            }
+        }
+        for key in ["aggs", "aggregations"] {
+            if let Some(aggs) = body.get_mut(key).and_then(Value::as_object_mut) {
+                self.translate_aggs(aggs, policy)?;
+            }
+        }
+        Ok(body)
+    }
+
+    /// Pseudonymizes the hits and aggregation buckets of a search response.
+    ///
+    /// The request body is needed to know which aggregations run on
+    /// pseudonymized fields.
+    pub fn pseudonymize_response(
+        &self,
+        response: &mut Value,
+        request: &Value,
+        policy: &PseudonymPolicy,
+    ) {
+        if policy.is_empty() {
+            return;
+        }
+
+        for_each_hit(response, &mut |hit, path| {
+            if let Some(source) = hit.get_mut("_source").and_then(Value::as_object_mut) {
+                self.pseudonymize_object(source, path, policy);
+            }
+            if let Some(fields) = hit.get_mut("fields").and_then(Value::as_object_mut) {
+                for (field, value) in fields.iter_mut() {
+                    if policy.covers(field) {
+                        self.pseudonymize_value(value);
+                    }
+                }
+            }
+            // Highlight fragments cannot be pseudonymized consistently
+            if let Some(highlight) = hit.get_mut("highlight").and_then(Value::as_object_mut) {
+                highlight.retain(|field, _| !policy.covers(field));
+            }
+        });
+
+        let definitions = request
+            .get("aggs")
+            .or(request.get("aggregations"))
+            .and_then(Value::as_object);
+        let results = response
+            .get_mut("aggregations")
+            .and_then(Value::as_object_mut);
+        if let (Some(definitions), Some(results)) = (definitions, results) {
+            self.pseudonymize_aggs(definitions, results, policy);
+        }
+    }
+
+    /// Returns the pseudonym of a value, remembering it for translation.
+    ///
+    /// Without a configured key no pseudonyms can be created and the value
+    /// is redacted instead.
+    fn pseudonym(&self, value: &Value) -> Value {
+        let Some(key) = self.key.as_ref() else {
+            tracing::warn!("No MASKING_KEY configured, redacting pseudonymized field instead");
+            return Value::String(REDACTED.to_string());
+        };
+
+        let hash = hmac_hex(key, &scalar_to_string(value));
+        let pseudonym = format!("{}{}", PSEUDONYM_PREFIX, &hash[..PSEUDONYM_LENGTH]);
+        self.repository.insert(&pseudonym, value);
+        Value::String(pseudonym)
+    }
+
+    fn pseudonymize_value(&self, value: &mut Value) {
+        match value {
+            Value::Null => {}
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.pseudonymize_value(item)),
+            Value::Object(object) => object
+                .values_mut()
+                .for_each(|child| self.pseudonymize_value(child)),
+            _ => *value = self.pseudonym(value),
+        }
+    }
+
+    fn pseudonymize_object(
+        &self,
+        object: &mut Map<String, Value>,
+        prefix: &str,
+        policy: &PseudonymPolicy,
+    ) {
+        for (key, value) in object.iter_mut() {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            if policy.covers(&path) {
+                self.pseudonymize_value(value);
+            } else if let Value::Object(child) = value {
+                self.pseudonymize_object(child, &path, policy);
+            } else if let Value::Array(items) = value {
+                for child in items.iter_mut().filter_map(Value::as_object_mut) {
+                    self.pseudonymize_object(child, &path, policy);
+                }
+            }
+        }
+    }
+
+    /// Walks aggregation results alongside their definitions and replaces
+    /// the bucket keys of aggregations on pseudonymized fields.
+    fn pseudonymize_aggs(
+        &self,
+        definitions: &Map<String, Value>,
+        results: &mut Map<String, Value>,
+        policy: &PseudonymPolicy,
+    ) {
+        for (name, definition) in definitions {
+            let Some(result) = results.get_mut(name) else {
+                continue;
+            };
+            let pseudonymized = agg_field(definition).is_some_and(|field| policy.covers(field));
+            let sources = composite_sources(definition, policy);
+            if let Some(after_key) = result.get_mut("after_key") {
+                self.pseudonymize_sources(after_key, &sources);
+            }
+            let sub_definitions = definition
+                .get("aggs")
+                .or(definition.get("aggregations"))
+                .and_then(Value::as_object);
+
+            let buckets: Vec<&mut Value> = match result.get_mut("buckets") {
+                Some(Value::Array(buckets)) => buckets.iter_mut().collect(),
+                Some(Value::Object(buckets)) => buckets.values_mut().collect(),
+                _ => {
+                    // Single bucket aggregations hold sub-aggregations directly
+                    if let (Some(sub), Some(result)) = (sub_definitions, result.as_object_mut()) {
+                        self.pseudonymize_aggs(sub, result, policy);
+                    }
+                    continue;
+                }
+            };
+
+            for bucket in buckets.into_iter().filter_map(Value::as_object_mut) {
+                if pseudonymized {
+                    if let Some(key) = bucket.get_mut("key") {
+                        self.pseudonymize_value(key);
+                    }
+                    bucket.remove("key_as_string");
+                }
+                if let Some(key) = bucket.get_mut("key") {
+                    self.pseudonymize_sources(key, &sources);
+                }
+                if let Some(sub) = sub_definitions {
+                    self.pseudonymize_aggs(sub, bucket, policy);
+                }
+            }
+        }
+    }
+
+    /// Pseudonymizes the values of the given sources in a composite bucket
+    /// key or `after_key`.
+    fn pseudonymize_sources(&self, key: &mut Value, sources: &[String]) {
+        let Some(key) = key.as_object_mut() else {
+            return;
+        };
+        for source in sources {
+            if let Some(value) = key.get_mut(source) {
+                self.pseudonymize_value(value);
+            }
+        }
+    }
+
+    /// Translates the pseudonyms of the `after` key of composite
+    /// aggregations paging through pseudonymized sources.
+    fn translate_aggs(
+        &self,
+        aggs: &mut Map<String, Value>,
+        policy: &PseudonymPolicy,
+    ) -> Result<(), PseudonymizedField> {
+        for definition in aggs.values_mut() {
+            let sources = composite_sources(definition, policy);
+            if let Some(after) = definition
+                .pointer_mut("/composite/after")
+                .and_then(Value::as_object_mut)
+            {
+                for (source, value) in after.iter_mut() {
+                    if sources.contains(source) && !value.is_null() {
+                        self.translate_value(source, value)?;
+                    }
+                }
+            }
+            for key in ["aggs", "aggregations"] {
+                if let Some(sub) = definition.get_mut(key).and_then(Value::as_object_mut) {
+                    self.translate_aggs(sub, policy)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn translate_query(
+        &self,
+        query: &mut Value,
+        policy: &PseudonymPolicy,
+    ) -> Result<(), PseudonymizedField> {
+        match query {
+            Value::Object(object) => {
+                for (kind, body) in object.iter_mut() {
+                    if TRANSLATED_QUERIES.contains(&kind.as_str()) {
+                        self.translate_leaf(kind, body, policy)?;
+                    } else {
+                        self.translate_query(body, policy)?;
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.translate_query(item, policy)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Translates the values of a `term`, `terms` or `match` query on
+    /// pseudonymized fields.
+    fn translate_leaf(
+        &self,
+        kind: &str,
+        body: &mut Value,
+        policy: &PseudonymPolicy,
+    ) -> Result<(), PseudonymizedField> {
+        let Some(body) = body.as_object_mut() else {
+            return Ok(());
+        };
+
+        for (field, value) in body.iter_mut().filter(|(field, _)| policy.covers(field)) {
+            let target = match (kind, value) {
+                ("terms", Value::Array(values)) => {
+                    for value in values {
+                        self.translate_value(field, value)?;
+                    }
+                    continue;
+                }
+                ("term", Value::Object(options)) => options.get_mut("value"),
+                ("match", Value::Object(options)) => options.get_mut("query"),
+                (_, value @ (Value::String(_) | Value::Number(_) | Value::Bool(_))) => Some(value),
+                _ => None,
+            };
+            match target {
+                Some(value) => self.translate_value(field, value)?,
+                None => return Err(PseudonymizedField(field.clone())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces a pseudonym with its real value. Real values are rejected so
+    /// they cannot be confirmed by querying. Unknown pseudonyms are kept and
+    /// match nothing.
+    fn translate_value(&self, field: &str, value: &mut Value) -> Result<(), PseudonymizedField> {
+        let Some(pseudonym) = value.as_str().filter(|v| v.starts_with(PSEUDONYM_PREFIX)) else {
+            return Err(PseudonymizedField(field.to_string()));
+        };
+        if let Some(real) = self.repository.get(pseudonym) {
+            *value = real;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the field of an aggregation definition, if any.
+fn agg_field(definition: &Value) -> Option<&str> {
+    definition
+        .as_object()?
+        .iter()
+        .find(|(kind, _)| !matches!(kind.as_str(), "aggs" | "aggregations" | "meta"))
+        .and_then(|(_, body)| body.get("field"))
+        .and_then(Value::as_str)
+}
+
+/// Returns the names of the sources of a composite aggregation definition
+/// that run on pseudonymized fields.
+fn composite_sources(definition: &Value, policy: &PseudonymPolicy) -> Vec<String> {
+    let Some(sources) = definition
+        .pointer("/composite/sources")
+        .and_then(Value::as_array)
+    else {
+        return Vec::new();
+    };
+    sources
+        .iter()
+        .filter_map(Value::as_object)
+        .flatten()
+        .filter(|(_, source)| agg_field(source).is_some_and(|field| policy.covers(field)))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Returns whether a bucket aggregation reveals real values of its field
+/// through `include` or `exclude` patterns or ordering by key.
+fn reveals_keys(body: &Value) -> bool {
+    let by_key = |order: &Value| order.get("_key").is_some() || order.get("_term").is_some();
+    body.get("include").is_some()
+        || body.get("exclude").is_some()
+        || body
+            .get("order")
+            .is_some_and(|order| as_slice(order).iter().any(by_key))
+}
+
+fn as_slice(value: &Value) -> &[Value] {
+    match value {
+        Value::Array(items) => items,
+        other => std::slice::from_ref(other),
+    }
+}
+
+/// Replaces the permitted uses of pseudonymized fields in a copy of the
+/// body so any remaining reference can be rejected.
+fn neutralize_permitted_uses(value: &mut Value, policy: &PseudonymPolicy) {
+    match value {
+        Value::Object(object) => {
+            let permitted_query = object.iter().any(|(kind, body)| {
+                (TRANSLATED_QUERIES.contains(&kind.as_str()) || kind == "exists")
+                    && body
+                        .as_object()
+                        .is_some_and(|body| body.keys().any(|field| policy.covers(field)))
+            });
+            if permitted_query {
+                *value = json!({ "match_all": {} });
+                return;
+            }
+            for (kind, body) in object.iter_mut() {
+                if PSEUDONYMIZED_AGGS.contains(&kind.as_str())
+                    && body
+                        .get("field")
+                        .and_then(Value::as_str)
+                        .is_some_and(|field| policy.covers(field))
+                    && !reveals_keys(body)
+                {
+                    body["field"] = json!("_pseudonymized");
+                }
+                neutralize_permitted_uses(body, policy);
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| neutralize_permitted_uses(item, policy)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> PseudonymizationService {
+        PseudonymizationService {
+            key: Some(Arc::from("secret".as_bytes())),
+            repository: PseudonymRepository::new(),
+        }
+    }
+
+    fn pseudonym_policy(fields: Value) -> PseudonymPolicy {
+        let role: RolePolicy = serde_json::from_value(json!({ "pseudonymize": fields })).unwrap();
+        PseudonymPolicy::from_roles(&[&role])
+    }
+
+    #[test]
+    fn test_pseudonymization_requires_every_reading_role() {
+        let pseudonymized: RolePolicy =
+            serde_json::from_value(json!({ "pseudonymize": ["user_id"] })).unwrap();
+        let denied: RolePolicy =
+            serde_json::from_value(json!({ "field_security": { "deny": ["user_id"] } })).unwrap();
+        let unrestricted = RolePolicy::default();
+
+        assert!(PseudonymPolicy::from_roles(&[&pseudonymized, &unrestricted]).is_empty());
+        assert!(PseudonymPolicy::from_roles(&[&denied, &pseudonymized]).covers("user_id"));
+        assert!(PseudonymPolicy::from_roles(&[&pseudonymized, &denied]).covers("user_id"));
+    }
+
+    #[test]
+    fn test_pseudonymize_hits_and_buckets() {
+        let service = service();
+        let policy = pseudonym_policy(json!(["user_id"]));
+        let request = json!({
+            "aggs": { "by_user": {
+                "terms": { "field": "user_id" },
+                "aggs": { "by_genre": { "terms": { "field": "genre" } } }
+            }}
+        });
+        let mut response = json!({
+            "hits": { "hits": [{
+                "_source": { "title": "Rust", "user_id": "alice" },
+                "fields": { "user_id": ["alice"] },
+                "highlight": { "user_id": ["<em>alice</em>"] }
+            }]},
+            "aggregations": { "by_user": { "buckets": [{
+                "key": "alice",
+                "doc_count": 1,
+                "by_genre": { "buckets": [{ "key": "Sci-Fi", "doc_count": 1 }] }
+            }]}}
+        });
+
+        service.pseudonymize_response(&mut response, &request, &policy);
+
+        let pseudonym = service.pseudonym(&json!("alice"));
+        let hit = &response["hits"]["hits"][0];
+        assert!(pseudonym.as_str().unwrap().starts_with(PSEUDONYM_PREFIX));
+        assert_eq!(
+            hit["_source"],
+            json!({ "title": "Rust", "user_id": pseudonym })
+        );
+        assert_eq!(hit["fields"]["user_id"], json!([pseudonym]));
+        assert_eq!(hit["highlight"], json!({}));
+
+        let bucket = &response["aggregations"]["by_user"]["buckets"][0];
+        assert_eq!(bucket["key"], pseudonym);
+        assert_eq!(bucket["by_genre"]["buckets"][0]["key"], "Sci-Fi");
+    }
+
+    #[test]
+    fn test_translate_request_restores_real_values() {
+        let service = service();
+        let policy = pseudonym_policy(json!(["user_id"]));
+        let pseudonym = service.pseudonym(&json!(42));
+
+        let body = json!({
+            "query": { "bool": { "filter": [
+                { "term": { "user_id": { "value": pseudonym } } },
+                { "terms": { "user_id": [pseudonym, "pseu_unknown"] } },
+                { "match": { "title": "rust" } }
+            ]}},
+            "aggs": { "users": { "terms": { "field": "user_id" } } }
+        });
+
+        let result = service.translate_request(body, &policy).unwrap();
+
+        assert_eq!(
+            result["query"]["bool"]["filter"],
+            json!([
+                { "term": { "user_id": { "value": 42 } } },
+                { "terms": { "user_id": [42, "pseu_unknown"] } },
+                { "match": { "title": "rust" } }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_translate_request_rejects_revealing_uses() {
+        let service = service();
+        let policy = pseudonym_policy(json!(["user_id"]));
+        let rejected = Err(PseudonymizedField("user_id".to_string()));
+
+        let raw_value = json!({ "query": { "term": { "user_id": "alice" } } });
+        assert_eq!(service.translate_request(raw_value, &policy), rejected);
+
+        let prefix = json!({ "query": { "prefix": { "user_id": "a" } } });
+        assert_eq!(service.translate_request(prefix, &policy), rejected);
+
+        let sort = json!({ "sort": ["user_id"] });
+        assert_eq!(service.translate_request(sort, &policy), rejected);
+
+        let max = json!({ "aggs": { "m": { "max": { "field": "user_id" } } } });
+        assert_eq!(service.translate_request(max, &policy), rejected);
+
+        let include =
+            json!({ "aggs": { "u": { "terms": { "field": "user_id", "include": "a.*" } } } });
+        assert_eq!(service.translate_request(include, &policy), rejected);
+
+        let by_key = json!({ "aggs": { "u": { "terms": { "field": "user_id", "order": [{ "_key": "asc" }] } } } });
+        assert_eq!(service.translate_request(by_key, &policy), rejected);
+
+        for any_field in [
+            json!({ "query": { "query_string": { "query": "alice" } } }),
+            json!({ "query": { "multi_match": { "query": "alice", "fields": ["*"] } } }),
+            json!({ "query": { "query_string": { "query": "user_*:alice" } } }),
+        ] {
+            assert!(service.translate_request(any_field, &policy).is_err());
+        }
+    }
+
+    #[test]
+    fn test_pseudonymize_composite_keys() {
+        let service = service();
+        let policy = pseudonym_policy(json!(["user_id"]));
+        let pseudonym = service.pseudonym(&json!("alice"));
+        let request = json!({ "aggs": { "pairs": { "composite": {
+            "sources": [{ "user": { "terms": { "field": "user_id" } } }, { "genre": { "terms": { "field": "genre" } } }],
+            "after": { "user": pseudonym, "genre": "Drama" }
+        } } } });
+
+        let translated = service.translate_request(request.clone(), &policy).unwrap();
+        assert_eq!(
+            translated["aggs"]["pairs"]["composite"]["after"],
+            json!({ "user": "alice", "genre": "Drama" })
+        );
+
+        let mut response = json!({ "aggregations": { "pairs": {
+            "after_key": { "user": "bob", "genre": "Sci-Fi" },
+            "buckets": [{ "key": { "user": "bob", "genre": "Sci-Fi" }, "doc_count": 1 }]
+        } } });
+        service.pseudonymize_response(&mut response, &request, &policy);
+
+        let bob = service.pseudonym(&json!("bob"));
+        let pairs = &response["aggregations"]["pairs"];
+        assert_eq!(
+            pairs["after_key"],
+            json!({ "user": bob, "genre": "Sci-Fi" })
+        );
+        assert_eq!(
+            pairs["buckets"][0]["key"],
+            json!({ "user": bob, "genre": "Sci-Fi" })
+        );
+    }
+}
@@ -0,0 +1,1082 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value};
+
+use crate::handlers::field_security::FieldPolicy;
+
+/// Field name used for references that cannot be resolved to concrete
+/// fields, such as stored scripts or `wrapper` queries.
+const ANY_FIELD: &str = "*";
+
+/// Index and field from which `geo_shape` reads indexed shapes by default.
+const DEFAULT_SHAPE_INDEX: &str = "shapes";
+const DEFAULT_SHAPE_PATH: &str = "shape";
+
+/// Query types whose body contains other queries rather than fields.
+const COMPOUND_QUERY_KEYS: &[(&str, &[&str])] = &[
+    ("bool", &["must", "should", "filter", "must_not"]),
+    ("dis_max", &["queries"]),
+    ("constant_score", &["filter"]),
+    ("boosting", &["positive", "negative"]),
+    ("has_child", &["query"]),
+    ("has_parent", &["query"]),
+    ("span_near", &["clauses"]),
+    ("span_or", &["clauses"]),
+    ("span_not", &["include", "exclude"]),
+    ("span_first", &["match"]),
+    ("span_containing", &["big", "little"]),
+    ("span_within", &["big", "little"]),
+    ("span_multi", &["match"]),
+];
+
+/// Option keys of leaf queries that are not field names.
+const QUERY_OPTION_KEYS: &[&str] = &[
+    "boost",
+    "_name",
+    "distance",
+    "distance_type",
+    "validation_method",
+    "ignore_unmapped",
+    "type",
+    "relation",
+    "minimum_should_match",
+    "score_mode",
+];
+
+/// Error returned when a request references a field the caller may not
+/// read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForbiddenField(pub String);
+
+impl IntoResponse for ForbiddenField {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected request referencing forbidden field '{}'", self.0);
+
+        (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": {
+                    "type": "security_exception",
+                    "reason": format!("access to field [{}] is forbidden", self.0),
+                },
+                "status": StatusCode::FORBIDDEN.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// Error returned when a request reads documents the security filter does
+/// not apply to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnfilteredRead(pub String);
+
+impl IntoResponse for UnfilteredRead {
+    fn into_response(self) -> Response {
+        tracing::warn!(
+            "Rejected request reading unfiltered documents through '{}'",
+            self.0
+        );
+
+        (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": {
+                    "type": "security_exception",
+                    "reason": format!("[{}] reads documents outside the security filter", self.0),
+                },
+                "status": StatusCode::FORBIDDEN.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A service inspecting search bodies for the fields they reference.
+///
+/// Hiding a field from `_source` is not enough, since its values can still
+/// be learned by querying, sorting or aggregating on it. The inspector
+/// collects every field referenced in queries, sorts, aggregations,
+/// collapse, highlight, suggesters and scripts so they can be checked
+/// against the caller's field policy.
+#[derive(Clone)]
+pub struct QueryInspectorService;
+
+impl QueryInspectorService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Rejects the body if it references a field the policy does not
+    /// permit.
+    pub fn inspect(&self, body: &Value, policy: &FieldPolicy) -> Result<(), ForbiddenField> {
+        if policy.is_unrestricted() {
+            return Ok(());
+        }
+        match self
+            .referenced_fields(body)
+            .into_iter()
+            .find(|field| !policy.permits_pattern(field))
+        {
+            Some(field) => Err(ForbiddenField(field)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns every field referenced by a search body.
+    pub fn referenced_fields(&self, body: &Value) -> Vec<String> {
+        let mut fields = Vec::new();
+        let Some(body) = body.as_object() else {
+            return fields;
+        };
+
+        for key in ["query", "post_filter"] {
+            if let Some(query) = body.get(key) {
+                collect_query(query, &mut fields);
+            }
+        }
+        if let Some(sort) = body.get("sort") {
+            collect_sort(sort, &mut fields);
+        }
+        if let Some(aggs) = body.get("aggs").or(body.get("aggregations")) {
+            collect_aggs(aggs, &mut fields);
+        }
+        if let Some(collapse) = body.get("collapse") {
+            collect_collapse(collapse, &mut fields);
+        }
+        if let Some(highlight) = body.get("highlight") {
+            collect_highlight(highlight, &mut fields);
+        }
+        if let Some(rescore) = body.get("rescore") {
+            for rescorer in as_list(rescore) {
+                if let Some(query) = rescorer.pointer("/query/rescore_query") {
+                    collect_query(query, &mut fields);
+                }
+            }
+        }
+        if let Some(suggest) = body.get("suggest").and_then(Value::as_object) {
+            for suggester in suggest.values().filter_map(Value::as_object) {
+                for kind in ["term", "phrase", "completion"] {
+                    if let Some(field) = suggester.get(kind).and_then(|s| s.get("field")) {
+                        collect_field_value(field, &mut fields);
+                    }
+                }
+                if let Some(generators) = suggester
+                    .get("phrase")
+                    .and_then(|phrase| phrase.get("direct_generator"))
+                {
+                    for generator in as_list(generators) {
+                        if let Some(field) = generator.get("field") {
+                            collect_field_value(field, &mut fields);
+                        }
+                    }
+                }
+            }
+        }
+        for key in ["script_fields", "runtime_mappings"] {
+            if let Some(definitions) = body.get(key).and_then(Value::as_object) {
+                for definition in definitions.values() {
+                    if let Some(script) = definition.get("script") {
+                        collect_script(script, &mut fields);
+                    }
+                }
+            }
+        }
+        // The script of an update by query reads and writes the matched
+        // documents
+        if let Some(script) = body.get("script") {
+            collect_script(script, &mut fields);
+        }
+
+        fields
+    }
+
+    /// Rejects constructs reading documents the injected security filter
+    /// does not apply to: `global` aggregations, inner hits of joins and
+    /// documents fetched by terms lookups, indexed shapes,
+    /// `more_like_this` and `percolate`.
+    pub fn check_unfiltered_reads(&self, body: &Value) -> Result<(), UnfilteredRead> {
+        let mut constructs = Vec::new();
+        collect_unfiltered_reads(body, &mut constructs);
+        for key in ["aggs", "aggregations"] {
+            if let Some(aggs) = body.get(key) {
+                collect_global_aggs(aggs, &mut constructs);
+            }
+        }
+        match constructs.into_iter().next() {
+            Some(construct) => Err(UnfilteredRead(construct.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the indices a search body reads documents from besides the
+    /// searched ones, through terms lookups, `more_like_this` documents,
+    /// indexed shapes and percolated documents. These reads bypass the
+    /// index authorization of the request path.
+    pub fn referenced_indices(&self, body: &Value) -> Vec<String> {
+        let mut indices = Vec::new();
+        collect_lookup_indices(body, &mut indices);
+        indices
+    }
+}
+
+/// Walks a body and collects the indices of documents read by lookups,
+/// wherever queries are nested.
+fn collect_lookup_indices(value: &Value, indices: &mut Vec<String>) {
+    let push = |index: Option<&Value>, indices: &mut Vec<String>| {
+        if let Some(index) = index.and_then(Value::as_str) {
+            indices.push(index.to_string());
+        }
+    };
+
+    match value {
+        Value::Object(object) => {
+            for (key, body) in object {
+                match key.as_str() {
+                    "terms" | "geo_shape" => {
+                        for lookup in body.as_object().into_iter().flat_map(Map::values) {
+                            if lookup.get("id").is_some() {
+                                push(lookup.get("index"), indices);
+                            }
+                            if let Some(shape) = lookup.get("indexed_shape") {
+                                match shape.get("index") {
+                                    Some(index) => push(Some(index), indices),
+                                    None => indices.push(DEFAULT_SHAPE_INDEX.to_string()),
+                                }
+                            }
+                        }
+                    }
+                    "more_like_this" => {
+                        for key in ["like", "unlike"] {
+                            for document in body.get(key).map(as_list).unwrap_or_default() {
+                                push(document.get("_index"), indices);
+                            }
+                        }
+                    }
+                    "percolate" => push(body.get("index"), indices),
+                    _ => {}
+                }
+                collect_lookup_indices(body, indices);
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_lookup_indices(item, indices)),
+        _ => {}
+    }
+}
+
+/// Walks a body and collects the queries reading documents outside the
+/// security filter, wherever queries are nested.
+fn collect_unfiltered_reads(value: &Value, constructs: &mut Vec<&'static str>) {
+    match value {
+        Value::Object(object) => {
+            for (key, body) in object {
+                let lookups = || body.as_object().into_iter().flat_map(Map::values);
+                let documents = |key: &str| {
+                    body.get(key)
+                        .map(as_list)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .any(|document| document.get("_id").is_some())
+                };
+                let unfiltered = match key.as_str() {
+                    "terms" if lookups().any(|lookup| lookup.get("id").is_some()) => Some("terms"),
+                    "geo_shape"
+                        if lookups().any(|lookup| lookup.get("indexed_shape").is_some()) =>
+                    {
+                        Some("geo_shape")
+                    }
+                    "more_like_this" if documents("like") || documents("unlike") => {
+                        Some("more_like_this")
+                    }
+                    "percolate" if body.get("id").is_some() => Some("percolate"),
+                    "has_child" if body.get("inner_hits").is_some() => Some("has_child"),
+                    "has_parent" if body.get("inner_hits").is_some() => Some("has_parent"),
+                    _ => None,
+                };
+                constructs.extend(unfiltered);
+                collect_unfiltered_reads(body, constructs);
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_unfiltered_reads(item, constructs)),
+        _ => {}
+    }
+}
+
+/// Collects `global` aggregations, which run on every document of the
+/// searched indices regardless of the query.
+fn collect_global_aggs(aggs: &Value, constructs: &mut Vec<&'static str>) {
+    for aggregation in aggs.as_object().into_iter().flat_map(Map::values) {
+        if aggregation.get("global").is_some() {
+            constructs.push("global");
+        }
+        for key in ["aggs", "aggregations"] {
+            if let Some(sub) = aggregation.get(key) {
+                collect_global_aggs(sub, constructs);
+            }
+        }
+    }
+}
+
+/// Treats a value as a list, wrapping single objects.
+fn as_list(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    }
+}
+
+/// Adds a field name, dropping any `^boost` suffix.
+fn push_field(name: &str, fields: &mut Vec<String>) {
+    let name = name.split('^').next().unwrap_or(name);
+    if !name.is_empty() {
+        fields.push(name.to_string());
+    }
+}
+
+/// Adds a `field` or `fields` style value holding one or more names.
+fn collect_field_value(value: &Value, fields: &mut Vec<String>) {
+    match value {
+        Value::String(name) => push_field(name, fields),
+        Value::Array(names) => {
+            for name in names.iter().filter_map(Value::as_str) {
+                push_field(name, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_query(query: &Value, fields: &mut Vec<String>) {
+    let Some(query) = query.as_object() else {
+        return;
+    };
+
+    for (kind, body) in query {
+        if let Some((_, keys)) = COMPOUND_QUERY_KEYS.iter().find(|(k, _)| k == kind) {
+            for key in *keys {
+                if let Some(clauses) = body.get(key) {
+                    for clause in as_list(clauses) {
+                        collect_query(clause, fields);
+                    }
+                }
+            }
+            // Inner hits of joins return the child or parent documents
+            if let Some(inner_hits) = body.get("inner_hits") {
+                collect_inner_hits(inner_hits, fields);
+            }
+            continue;
+        }
+
+        match kind.as_str() {
+            "match_all" | "match_none" | "ids" | "parent_id" => {}
+            "exists" => {
+                if let Some(field) = body.get("field") {
+                    collect_field_value(field, fields);
+                }
+            }
+            // The nested path only scopes the inner query, which uses full
+            // field paths
+            "nested" => {
+                if let Some(inner) = body.get("query") {
+                    collect_query(inner, fields);
+                }
+                if let Some(inner_hits) = body.get("inner_hits") {
+                    collect_inner_hits(inner_hits, fields);
+                }
+            }
+            // These queries name their field in a `field` option
+            "distance_feature" | "rank_feature" => match body.get("field") {
+                Some(field) => collect_field_value(field, fields),
+                None => fields.push(ANY_FIELD.to_string()),
+            },
+            "terms_set" => {
+                collect_leaf_fields(body, fields);
+                for options in body.as_object().into_iter().flat_map(Map::values) {
+                    if let Some(field) = options.get("minimum_should_match_field") {
+                        collect_field_value(field, fields);
+                    }
+                }
+            }
+            "intervals" => {
+                collect_leaf_fields(body, fields);
+                collect_interval_fields(body, fields);
+            }
+            "field_masking_span" => {
+                if let Some(field) = body.get("field") {
+                    collect_field_value(field, fields);
+                }
+                if let Some(inner) = body.get("query") {
+                    collect_query(inner, fields);
+                }
+            }
+            "function_score" | "script_score" => collect_function_score(body, fields),
+            "script" => {
+                if let Some(script) = body.get("script") {
+                    collect_script(script, fields);
+                }
+            }
+            "query_string" => {
+                collect_full_text_fields(body, fields);
+                if let Some(query_string) = body.get("query").and_then(Value::as_str) {
+                    for field in query_string_fields(query_string) {
+                        push_field(&field, fields);
+                    }
+                }
+            }
+            "multi_match" | "simple_query_string" | "combined_fields" | "more_like_this" => {
+                collect_full_text_fields(body, fields)
+            }
+            // Wrapped queries are opaque, so they may reference any field
+            "wrapper" => fields.push(ANY_FIELD.to_string()),
+            // Lookups also read a field of the referenced document
+            "terms" | "geo_shape" => {
+                collect_leaf_fields(body, fields);
+                for lookup in body.as_object().into_iter().flat_map(Map::values) {
+                    if let Some(path) = lookup.get("path") {
+                        collect_field_value(path, fields);
+                    } else if let Some(shape) = lookup.get("indexed_shape") {
+                        match shape.get("path") {
+                            Some(path) => collect_field_value(path, fields),
+                            None => push_field(DEFAULT_SHAPE_PATH, fields),
+                        }
+                    }
+                }
+            }
+            _ => collect_leaf_fields(body, fields),
+        }
+    }
+}
+
+/// Collects the field names of a leaf query such as `term`, `match` or
+/// `range`, which are the keys of the query body. Scripts within the
+/// options of a field reference any field.
+fn collect_leaf_fields(body: &Value, fields: &mut Vec<String>) {
+    match body {
+        Value::Object(object) => {
+            for (key, options) in object {
+                if !QUERY_OPTION_KEYS.contains(&key.as_str()) {
+                    push_field(key, fields);
+                }
+                if contains_script(options) {
+                    fields.push(ANY_FIELD.to_string());
+                }
+            }
+        }
+        // Unknown query shapes cannot be verified
+        _ => fields.push(ANY_FIELD.to_string()),
+    }
+}
+
+/// Returns whether a value holds a `script` or `*_script` key at any
+/// depth.
+fn contains_script(value: &Value) -> bool {
+    match value {
+        Value::Object(object) => object.iter().any(|(key, child)| {
+            key == "script" || key.ends_with("_script") || contains_script(child)
+        }),
+        Value::Array(items) => items.iter().any(contains_script),
+        _ => false,
+    }
+}
+
+/// Collects the `use_field` options of interval rules, which search
+/// another field than the one of the query, at any depth.
+fn collect_interval_fields(value: &Value, fields: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object {
+                if key == "use_field" {
+                    collect_field_value(child, fields);
+                } else {
+                    collect_interval_fields(child, fields);
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_interval_fields(item, fields)),
+        _ => {}
+    }
+}
+
+/// Collects the searched fields of full text queries. Queries without
+/// explicit fields search every field.
+fn collect_full_text_fields(body: &Value, fields: &mut Vec<String>) {
+    let explicit = body.get("fields").or(body.get("default_field"));
+    match explicit {
+        Some(value) => collect_field_value(value, fields),
+        None => fields.push(ANY_FIELD.to_string()),
+    }
+}
+
+fn collect_function_score(body: &Value, fields: &mut Vec<String>) {
+    if let Some(inner) = body.get("query") {
+        collect_query(inner, fields);
+    }
+    if let Some(script) = body.get("script") {
+        collect_script(script, fields);
+    }
+
+    let functions = body.get("functions").map(as_list).unwrap_or_default();
+    for function in functions.into_iter().chain(std::iter::once(body)) {
+        let Some(function) = function.as_object() else {
+            continue;
+        };
+        if let Some(filter) = function.get("filter") {
+            collect_query(filter, fields);
+        }
+        if let Some(field) = function
+            .get("field_value_factor")
+            .and_then(|factor| factor.get("field"))
+        {
+            collect_field_value(field, fields);
+        }
+        if let Some(field) = function
+            .get("random_score")
+            .and_then(|random| random.get("field"))
+        {
+            collect_field_value(field, fields);
+        }
+        if let Some(script) = function.get("script_score").and_then(|s| s.get("script")) {
+            collect_script(script, fields);
+        }
+        for decay in ["gauss", "exp", "linear"] {
+            if let Some(decay) = function.get(decay) {
+                collect_leaf_fields(decay, fields);
+            }
+        }
+    }
+}
+
+fn collect_sort(sort: &Value, fields: &mut Vec<String>) {
+    for entry in as_list(sort) {
+        match entry {
+            Value::String(field) if field != "_score" && field != "_doc" => {
+                push_field(field, fields)
+            }
+            Value::Object(object) => {
+                for (key, options) in object {
+                    match key.as_str() {
+                        "_score" | "_doc" => {}
+                        "_script" => {
+                            if let Some(script) = options.get("script") {
+                                collect_script(script, fields);
+                            }
+                        }
+                        "_geo_distance" => collect_leaf_fields(options, fields),
+                        _ => push_field(key, fields),
+                    }
+                    if let Some(nested) = options.get("nested") {
+                        collect_nested_sort(nested, fields);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_nested_sort(nested: &Value, fields: &mut Vec<String>) {
+    if let Some(filter) = nested.get("filter") {
+        collect_query(filter, fields);
+    }
+    if let Some(inner) = nested.get("nested") {
+        collect_nested_sort(inner, fields);
+    }
+}
+
+fn collect_aggs(aggs: &Value, fields: &mut Vec<String>) {
+    let Some(aggs) = aggs.as_object() else {
+        return;
+    };
+
+    for aggregation in aggs.values().filter_map(Value::as_object) {
+        for (kind, body) in aggregation {
+            match kind.as_str() {
+                "aggs" | "aggregations" => collect_aggs(body, fields),
+                "meta" => {}
+                "filter" => collect_query(body, fields),
+                "filters" | "adjacency_matrix" => {
+                    let filters = body.get("filters").unwrap_or(body);
+                    match filters {
+                        Value::Object(named) => named
+                            .values()
+                            .for_each(|filter| collect_query(filter, fields)),
+                        Value::Array(anonymous) => anonymous
+                            .iter()
+                            .for_each(|filter| collect_query(filter, fields)),
+                        _ => {}
+                    }
+                }
+                "top_hits" => {
+                    collect_top_hits(body, fields);
+                }
+                _ => collect_agg_body(body, fields),
+            }
+        }
+    }
+}
+
+/// Collects the fields of a metric or bucket aggregation body.
+fn collect_agg_body(body: &Value, fields: &mut Vec<String>) {
+    let Some(body) = body.as_object() else {
+        return;
+    };
+
+    for key in ["field", "fields"] {
+        if let Some(value) = body.get(key) {
+            collect_field_value(value, fields);
+        }
+    }
+    // scripted_metric runs a script in each phase
+    for key in [
+        "script",
+        "init_script",
+        "map_script",
+        "combine_script",
+        "reduce_script",
+    ] {
+        if let Some(script) = body.get(key) {
+            collect_script(script, fields);
+        }
+    }
+    if let Some(filter) = body.get("background_filter") {
+        collect_query(filter, fields);
+    }
+    // multi_terms, top_metrics and weighted_avg nest their fields
+    for key in ["terms", "metrics"] {
+        if let Some(Value::Array(entries)) = body.get(key) {
+            entries
+                .iter()
+                .for_each(|entry| collect_agg_body(entry, fields));
+        }
+    }
+    for key in ["value", "weight"] {
+        if let Some(value) = body.get(key) {
+            collect_agg_body(value, fields);
+        }
+    }
+    if let Some(sort) = body.get("sort") {
+        collect_sort(sort, fields);
+    }
+    // composite sources are named single-aggregation objects
+    if let Some(Value::Array(sources)) = body.get("sources") {
+        for source in sources.iter().filter_map(Value::as_object) {
+            source
+                .values()
+                .filter_map(Value::as_object)
+                .flat_map(Map::values)
+                .for_each(|agg| collect_agg_body(agg, fields));
+        }
+    }
+}
+
+fn collect_top_hits(body: &Value, fields: &mut Vec<String>) {
+    if let Some(sort) = body.get("sort") {
+        collect_sort(sort, fields);
+    }
+    if let Some(highlight) = body.get("highlight") {
+        collect_highlight(highlight, fields);
+    }
+    if let Some(script_fields) = body.get("script_fields").and_then(Value::as_object) {
+        for definition in script_fields.values() {
+            if let Some(script) = definition.get("script") {
+                collect_script(script, fields);
+            }
+        }
+    }
+}
+
+/// Collects the fields of inner hits, which accept the options of
+/// `top_hits` along with requested doc values and a nested collapse.
+fn collect_inner_hits(inner_hits: &Value, fields: &mut Vec<String>) {
+    for inner in as_list(inner_hits) {
+        collect_top_hits(inner, fields);
+        for entry in inner
+            .get("docvalue_fields")
+            .map(as_list)
+            .unwrap_or_default()
+        {
+            match entry.get("field") {
+                Some(field) => collect_field_value(field, fields),
+                None => collect_field_value(entry, fields),
+            }
+        }
+        if let Some(nested) = inner.get("collapse") {
+            collect_collapse(nested, fields);
+        }
+    }
+}
+
+fn collect_collapse(collapse: &Value, fields: &mut Vec<String>) {
+    if let Some(field) = collapse.get("field") {
+        collect_field_value(field, fields);
+    }
+    if let Some(inner_hits) = collapse.get("inner_hits") {
+        collect_inner_hits(inner_hits, fields);
+    }
+}
+
+fn collect_highlight(highlight: &Value, fields: &mut Vec<String>) {
+    if let Some(query) = highlight.get("highlight_query") {
+        collect_query(query, fields);
+    }
+
+    let entries: Vec<(&String, &Value)> = match highlight.get("fields") {
+        Some(Value::Object(object)) => object.iter().collect(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_object)
+            .flat_map(|object| object.iter())
+            .collect(),
+        _ => Vec::new(),
+    };
+    for (field, options) in entries {
+        push_field(field, fields);
+        if let Some(matched) = options.get("matched_fields") {
+            collect_field_value(matched, fields);
+        }
+        if let Some(query) = options.get("highlight_query") {
+            collect_query(query, fields);
+        }
+    }
+}
+
+/// Collects the document fields read by a script.
+///
+/// Painless reaches fields through `doc`, `params._source` and computed
+/// names, so neither inline nor stored scripts can be verified and every
+/// script references any field.
+fn collect_script(_script: &Value, fields: &mut Vec<String>) {
+    fields.push(ANY_FIELD.to_string());
+}
+
+/// Extracts the field names used in Lucene query string syntax, e.g.
+/// `title:rust AND salary:>100` or `_exists_:email`.
+pub(crate) fn query_string_fields(query: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut token = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut exists_pending = false;
+
+    for c in query.chars() {
+        if escaped {
+            token.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' => {
+                in_quotes = !in_quotes;
+                token.clear();
+            }
+            _ if in_quotes => {}
+            ':' => {
+                if token == "_exists_" {
+                    exists_pending = true;
+                } else if !token.is_empty() {
+                    fields.push(token.clone());
+                }
+                token.clear();
+            }
+            c if c.is_alphanumeric() || matches!(c, '_' | '.' | '*' | '-' | '@') => token.push(c),
+            _ => flush_token(&mut token, &mut exists_pending, &mut fields),
+        }
+    }
+    flush_token(&mut token, &mut exists_pending, &mut fields);
+
+    fields
+}
+
+/// Ends the current query string token, recording it if it is the field
+/// of a preceding `_exists_:`.
+fn flush_token(token: &mut String, exists_pending: &mut bool, fields: &mut Vec<String>) {
+    if *exists_pending && !token.is_empty() {
+        fields.push(token.clone());
+        *exists_pending = false;
+    }
+    token.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::policy::RolePolicy;
+    use serde_json::json;
+
+    fn deny(fields: Value) -> FieldPolicy {
+        let role: RolePolicy =
+            serde_json::from_value(json!({ "field_security": { "deny": fields } })).unwrap();
+        FieldPolicy::from_roles(&[&role])
+    }
+
+    #[test]
+    fn test_referenced_fields_in_queries() {
+        let service = QueryInspectorService::new();
+        let body = json!({
+            "query": {
+                "bool": {
+                    "must": [{ "match": { "title": "rust" } }],
+                    "filter": { "range": { "year": { "gte": 2020 }, "boost": 2 } },
+                    "should": [{ "multi_match": { "query": "x", "fields": ["summary^3"] } }],
+                    "must_not": { "nested": { "path": "user", "query": { "term": { "user.name": "bob" } } } }
+                }
+            },
+            "post_filter": { "exists": { "field": "genre" } }
+        });
+
+        assert_eq!(
+            service.referenced_fields(&body),
+            vec!["title", "summary", "year", "user.name", "genre"]
+        );
+    }
+
+    #[test]
+    fn test_referenced_fields_in_sort_aggs_collapse_highlight() {
+        let service = QueryInspectorService::new();
+        let body = json!({
+            "sort": ["_score", { "rating": "desc" }, "year"],
+            "aggs": {
+                "genres": {
+                    "terms": { "field": "genre.keyword" },
+                    "aggs": { "avg_salary": { "avg": { "field": "salary" } } }
+                },
+                "recent": { "filter": { "term": { "year": 2021 } } }
+            },
+            "collapse": { "field": "director" },
+            "highlight": { "fields": { "title": { "matched_fields": ["title.english"] } } }
+        });
+
+        assert_eq!(
+            service.referenced_fields(&body),
+            vec![
+                "rating",
+                "year",
+                "salary",
+                "genre.keyword",
+                "year",
+                "director",
+                "title",
+                "title.english"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_referenced_fields_in_scripts() {
+        let service = QueryInspectorService::new();
+        let body = json!({
+            "query": { "script": { "script": "doc['salary'].value > 100" } },
+            "script_fields": { "x": { "script": { "id": "stored" } } }
+        });
+
+        assert_eq!(service.referenced_fields(&body), vec!["*", "*"]);
+
+        let body = json!({ "aggs": { "total": { "scripted_metric": {
+            "init_script": "state.x = []",
+            "map_script": { "id": "stored" }
+        } } } });
+        assert_eq!(service.referenced_fields(&body), vec!["*", "*"]);
+    }
+
+    #[test]
+    fn test_referenced_fields_in_query_options() {
+        let service = QueryInspectorService::new();
+        let body = json!({ "query": { "bool": { "should": [
+            { "intervals": { "title": { "match": { "query": "rust", "use_field": "salary" } } } },
+            { "terms_set": { "tags": { "terms": ["a"], "minimum_should_match_field": "level" } } },
+            { "distance_feature": { "field": "born", "origin": "now", "pivot": "7d" } },
+            { "rank_feature": { "field": "pagerank" } },
+            { "function_score": { "random_score": { "seed": 1, "field": "user_id" } } }
+        ] } } });
+
+        assert_eq!(
+            service.referenced_fields(&body),
+            vec![
+                "title", "salary", "tags", "level", "born", "pagerank", "user_id"
+            ]
+        );
+
+        let body = json!({ "query": { "intervals": { "title": { "match": {
+            "query": "rust",
+            "filter": { "script": "interval.start > 1" }
+        } } } } });
+        assert_eq!(service.referenced_fields(&body), vec!["title", "*"]);
+
+        let body = json!({ "query": { "terms_set": { "tags": {
+            "terms": ["a"],
+            "minimum_should_match_script": { "source": "doc['salary'].value" }
+        } } } });
+        assert_eq!(service.referenced_fields(&body), vec!["tags", "*"]);
+    }
+
+    #[test]
+    fn test_referenced_fields_in_inner_hits() {
+        let service = QueryInspectorService::new();
+        let body = json!({ "query": { "bool": { "should": [
+            { "nested": {
+                "path": "comments",
+                "query": { "match": { "comments.text": "rust" } },
+                "inner_hits": {
+                    "sort": [{ "comments.email": "asc" }],
+                    "docvalue_fields": ["comments.salary", { "field": "comments.date" }]
+                }
+            } },
+            { "has_child": {
+                "type": "answer",
+                "query": { "match_all": {} },
+                "inner_hits": { "script_fields": { "x": { "script": "1" } } }
+            } }
+        ] } } });
+
+        assert_eq!(
+            service.referenced_fields(&body),
+            vec![
+                "comments.text",
+                "comments.email",
+                "comments.salary",
+                "comments.date",
+                "*"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_referenced_lookups() {
+        let service = QueryInspectorService::new();
+        let body = json!({
+            "query": { "bool": { "should": [
+                { "terms": { "user": { "index": "users", "id": "2", "path": "followers" } } },
+                { "more_like_this": { "fields": ["title"], "like": [{ "_index": "drafts", "_id": "1" }, "text"] } },
+                { "geo_shape": { "location": { "indexed_shape": { "id": "berlin" } } } }
+            ] } },
+            "suggest": { "fix": { "phrase": {
+                "field": "title.trigram",
+                "direct_generator": [{ "field": "title.trigram" }, { "field": "salary" }]
+            } } }
+        });
+
+        assert_eq!(
+            service.referenced_fields(&body),
+            vec![
+                "user",
+                "followers",
+                "title",
+                "location",
+                "shape",
+                "title.trigram",
+                "title.trigram",
+                "salary"
+            ]
+        );
+        assert_eq!(
+            service.referenced_indices(&body),
+            vec!["users", "drafts", "shapes"]
+        );
+    }
+
+    #[test]
+    fn test_check_unfiltered_reads() {
+        let service = QueryInspectorService::new();
+        let rejected = |construct: &str| Err(UnfilteredRead(construct.to_string()));
+
+        let body = json!({ "aggs": { "all": { "global": {}, "aggs": {
+            "salaries": { "avg": { "field": "salary" } }
+        } } } });
+        assert_eq!(service.check_unfiltered_reads(&body), rejected("global"));
+        let body = json!({ "aggs": { "genres": { "terms": { "field": "genre" }, "aggs": {
+            "all": { "global": {} }
+        } } } });
+        assert_eq!(service.check_unfiltered_reads(&body), rejected("global"));
+        let body = json!({ "query": { "has_child": {
+            "type": "answer",
+            "query": { "match_all": {} },
+            "inner_hits": {}
+        } } });
+        assert_eq!(service.check_unfiltered_reads(&body), rejected("has_child"));
+        let body = json!({ "query": { "bool": { "filter": [
+            { "terms": { "user": { "index": "movies", "id": "2", "path": "followers" } } }
+        ] } } });
+        assert_eq!(service.check_unfiltered_reads(&body), rejected("terms"));
+        let body = json!({ "query": { "more_like_this": {
+            "fields": ["title"],
+            "like": [{ "_id": "1" }]
+        } } });
+        assert_eq!(
+            service.check_unfiltered_reads(&body),
+            rejected("more_like_this")
+        );
+        let body = json!({ "query": { "geo_shape": { "location": {
+            "indexed_shape": { "id": "berlin" }
+        } } } });
+        assert_eq!(service.check_unfiltered_reads(&body), rejected("geo_shape"));
+
+        let body = json!({
+            "query": { "bool": { "must": [
+                { "terms": { "genre": ["Sci-Fi", "Drama"] } },
+                { "more_like_this": { "fields": ["title"], "like": ["space"] } },
+                { "has_parent": { "parent_type": "question", "query": { "match_all": {} } } }
+            ] } },
+            "aggs": { "genres": { "terms": { "field": "genre" } } }
+        });
+        assert_eq!(service.check_unfiltered_reads(&body), Ok(()));
+    }
+
+    #[test]
+    fn test_query_string_fields() {
+        assert_eq!(
+            query_string_fields("title:rust AND (salary:>100 OR \"a:b\") _exists_:email"),
+            vec!["title", "salary", "email"]
+        );
+        assert_eq!(
+            query_string_fields("rust programming"),
+            Vec::<String>::new()
+        );
+        assert_eq!(query_string_fields("user\\:name:x"), vec!["user:name"]);
+    }
+
+    #[test]
+    fn test_inspect_rejects_forbidden_fields() {
+        let service = QueryInspectorService::new();
+        let policy = deny(json!(["salary", "name"]));
+
+        let body = json!({ "query": { "term": { "salary": 100 } } });
+        assert_eq!(
+            service.inspect(&body, &policy),
+            Err(ForbiddenField("salary".to_string()))
+        );
+
+        let body = json!({ "aggs": { "names": { "terms": { "field": "name.keyword" } } } });
+        assert_eq!(
+            service.inspect(&body, &policy),
+            Err(ForbiddenField("name.keyword".to_string()))
+        );
+
+        let body = json!({ "query": { "query_string": { "query": "rust" } } });
+        assert_eq!(
+            service.inspect(&body, &policy),
+            Err(ForbiddenField("*".to_string()))
+        );
+
+        let update_by_query = json!({
+            "query": { "term": { "title": "rust" } },
+            "script": "ctx._source.title = ctx._source.salary"
+        });
+        assert_eq!(
+            service.inspect(&update_by_query, &policy),
+            Err(ForbiddenField("*".to_string()))
+        );
+
+        let body = json!({ "query": { "match": { "title": "rust" } }, "sort": ["year"] });
+        assert_eq!(service.inspect(&body, &policy), Ok(()));
+    }
+}
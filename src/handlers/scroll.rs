@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+use crate::auth::identity::Identity;
+use crate::handlers::search_params::SearchParams;
+use crate::repositories::context::SearchContextRepository;
+
+/// Scroll id value clearing every scroll of the caller.
+const ALL_SCROLLS: &str = "_all";
+
+/// Reasons a scroll request is rejected before it reaches OpenSearch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScrollRejection {
+    /// The request does not name a scroll
+    MissingScrollId,
+    /// The scroll is unknown, expired or was created by another identity
+    ContextMissing(String),
+}
+
+impl IntoResponse for ScrollRejection {
+    fn into_response(self) -> Response {
+        let (status, error_type, reason) = match self {
+            Self::MissingScrollId => (
+                StatusCode::BAD_REQUEST,
+                "action_request_validation_exception",
+                "Validation Failed: 1: scrollId is missing;".to_string(),
+            ),
+            Self::ContextMissing(scroll_id) => {
+                tracing::warn!("Rejected continuation of unknown scroll '{}'", scroll_id);
+                (
+                    StatusCode::NOT_FOUND,
+                    "search_context_missing_exception",
+                    format!("No search context found for id [{}]", scroll_id),
+                )
+            }
+        };
+
+        (
+            status,
+            Json(json!({
+                "error": { "type": error_type, "reason": reason },
+                "status": status.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A scroll created through the proxy.
+#[derive(Debug, Clone)]
+pub struct ScrollContext {
+    /// The prepared search body that created the scroll
+    pub request: Value,
+    /// The keep-alive of the scroll
+    pub keep_alive: Duration,
+}
+
+/// A scroll continuation request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollRequest {
+    pub scroll_id: String,
+    pub scroll: Option<String>,
+}
+
+/// A service binding scrolls to the identity that created them.
+///
+/// OpenSearch hands out scroll ids that any client can continue, and
+/// continuations carry no query the security filter could be applied to.
+/// Every scroll created by a filtered search is therefore remembered with
+/// its creator, and only that identity may continue or clear it.
+#[derive(Clone)]
+pub struct ScrollService {
+    contexts: SearchContextRepository<ScrollContext>,
+}
+
+impl ScrollService {
+    pub fn new(contexts: SearchContextRepository<ScrollContext>) -> Self {
+        Self { contexts }
+    }
+
+    /// Remembers the scroll created by a search response, if any.
+    pub fn register(&self, identity: &Identity, response: &Value, context: ScrollContext) {
+        if let Some(scroll_id) = response.get("_scroll_id").and_then(Value::as_str) {
+            self.contexts
+                .insert(scroll_id, identity, context.keep_alive, context);
+        }
+    }
+
+    /// Reads a continuation request from the body, falling back to the
+    /// `scroll_id` and `scroll` query string parameters.
+    pub fn parse_request(
+        &self,
+        params: &SearchParams,
+        body: &Value,
+    ) -> Result<ScrollRequest, ScrollRejection> {
+        let lookup = |name: &str| {
+            body.get(name)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .or_else(|| {
+                    params
+                        .iter()
+                        .find(|(param, _)| param == name)
+                        .map(|(_, value)| value.clone())
+                })
+        };
+
+        Ok(ScrollRequest {
+            scroll_id: lookup("scroll_id").ok_or(ScrollRejection::MissingScrollId)?,
+            scroll: lookup("scroll"),
+        })
+    }
+
+    /// Returns the context of a scroll created by the given identity.
+    pub fn context(
+        &self,
+        identity: &Identity,
+        scroll_id: &str,
+    ) -> Result<ScrollContext, ScrollRejection> {
+        self.contexts
+            .get(scroll_id, identity)
+            .ok_or_else(|| ScrollRejection::ContextMissing(scroll_id.to_string()))
+    }
+
+    /// Updates a scroll after a continuation, which may hand out a new
+    /// scroll id.
+    pub fn continued(
+        &self,
+        identity: &Identity,
+        scroll_id: &str,
+        response: &Value,
+        context: ScrollContext,
+    ) {
+        match response.get("_scroll_id").and_then(Value::as_str) {
+            Some(next) if next == scroll_id => self.contexts.extend(scroll_id, context.keep_alive),
+            Some(_) => {
+                self.contexts.remove(scroll_id);
+                self.register(identity, response, context);
+            }
+            None => self.contexts.remove(scroll_id),
+        }
+    }
+
+    /// Returns the scroll ids of a clear request that belong to the given
+    /// identity. `_all` selects every scroll of the identity.
+    pub fn owned_scroll_ids(
+        &self,
+        identity: &Identity,
+        params: &SearchParams,
+        body: &Value,
+    ) -> Vec<String> {
+        let mut requested: Vec<String> = match body.get("scroll_id") {
+            Some(Value::String(scroll_id)) => vec![scroll_id.clone()],
+            Some(Value::Array(scroll_ids)) => scroll_ids
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+        requested.extend(
+            params
+                .iter()
+                .filter(|(name, _)| name == "scroll_id")
+                .flat_map(|(_, value)| value.split(',').map(str::to_string)),
+        );
+
+        if requested.iter().any(|scroll_id| scroll_id == ALL_SCROLLS) {
+            return self.contexts.ids_of(identity);
+        }
+        requested.retain(|scroll_id| self.contexts.get(scroll_id, identity).is_some());
+        requested
+    }
+
+    /// Forgets cleared or expired scrolls.
+    pub fn forget(&self, scroll_ids: &[String]) {
+        for scroll_id in scroll_ids {
+            self.contexts.remove(scroll_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(user: &str) -> Identity {
+        Identity {
+            user: user.to_string(),
+            roles: vec!["reader".to_string()],
+        }
+    }
+
+    fn context() -> ScrollContext {
+        ScrollContext {
+            request: json!({ "query": { "match_all": {} } }),
+            keep_alive: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_scroll_is_bound_to_creator() {
+        let service = ScrollService::new(SearchContextRepository::new());
+        service.register(
+            &identity("alice"),
+            &json!({ "_scroll_id": "abc" }),
+            context(),
+        );
+
+        assert!(service.context(&identity("alice"), "abc").is_ok());
+        assert_eq!(
+            service.context(&identity("bob"), "abc").unwrap_err(),
+            ScrollRejection::ContextMissing("abc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_continued_tracks_new_scroll_id() {
+        let service = ScrollService::new(SearchContextRepository::new());
+        let alice = identity("alice");
+        service.register(&alice, &json!({ "_scroll_id": "abc" }), context());
+
+        service.continued(&alice, "abc", &json!({ "_scroll_id": "def" }), context());
+
+        assert!(service.context(&alice, "abc").is_err());
+        assert!(service.context(&alice, "def").is_ok());
+    }
+
+    #[test]
+    fn test_owned_scroll_ids() {
+        let service = ScrollService::new(SearchContextRepository::new());
+        let alice = identity("alice");
+        service.register(&alice, &json!({ "_scroll_id": "abc" }), context());
+        service.register(&identity("bob"), &json!({ "_scroll_id": "def" }), context());
+
+        assert_eq!(
+            service.owned_scroll_ids(&alice, &Vec::new(), &json!({ "scroll_id": ["abc", "def"] })),
+            vec!["abc"]
+        );
+        assert_eq!(
+            service.owned_scroll_ids(&alice, &Vec::new(), &json!({ "scroll_id": "_all" })),
+            vec!["abc"]
+        );
+    }
+}
@@ -0,0 +1,869 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+use crate::auth::identity::Identity;
+use crate::models::policy::Policy;
+
+/// Query string parameters forwarded to OpenSearch, in order.
+pub type SearchParams = Vec<(String, String)>;
+
+/// Parameters that are never forwarded as sent by the client because they
+/// could bypass the security filter.
+const FORBIDDEN_PARAMS: &[&str] = &["q", "search_pipeline"];
+
+/// Key of search bodies and `_msearch` headers naming a search pipeline,
+/// which may also define an ad-hoc pipeline rewriting the filtered query.
+const SEARCH_PIPELINE: &str = "search_pipeline";
+
+/// Keep-alive of scrolls and points in time permitted to roles that do not
+/// configure their own.
+const DEFAULT_MAX_KEEP_ALIVE: Duration = Duration::from_secs(5 * 60);
+
+/// Keep-alive of asynchronous searches permitted to roles that do not
+/// configure their own. Their results are meant to be fetched long after
+/// submission, so it matches the default of the plugin.
+const DEFAULT_MAX_ASYNC_KEEP_ALIVE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Parameters holding a keep-alive, which is capped by policy.
+const KEEP_ALIVE_PARAMS: &[&str] = &["scroll", "keep_alive"];
+
+/// Parameters of a URI search, which are converted into a `query_string`
+/// query instead of being forwarded.
+const URI_SEARCH_PARAMS: &[&str] = &["q", "df", "default_operator", "analyzer"];
+
+const DEFAULT_OPERATORS: &[&str] = &["AND", "OR", "and", "or"];
+
+/// Expected format of a query string parameter value.
+#[derive(Debug, Clone, Copy)]
+enum ParamKind {
+    Bool,
+    Count,
+    Number,
+    /// A time value such as `500ms` or `10s`
+    Time,
+    Text,
+    OneOf(&'static [&'static str]),
+    /// A boolean or a count, as accepted by `track_total_hits`
+    BoolOrCount,
+}
+
+const SEARCH_TYPES: &[&str] = &["query_then_fetch", "dfs_query_then_fetch"];
+const EXPAND_WILDCARDS: &[&str] = &["open", "closed", "hidden", "none", "all"];
+const CONFLICTS: &[&str] = &["abort", "proceed"];
+const REFRESH: &[&str] = &["", "true", "false", "wait_for"];
+const OP_TYPES: &[&str] = &["index", "create"];
+const VERSION_TYPES: &[&str] = &["internal", "external", "external_gte"];
+const SQL_FORMATS: &[&str] = &["jdbc", "json"];
+const CAT_FORMATS: &[&str] = &["json", "text"];
+const BYTE_UNITS: &[&str] = &["b", "kb", "mb", "gb", "tb", "pb"];
+const TIME_UNITS: &[&str] = &["d", "h", "m", "s", "ms", "micros", "nanos"];
+const HEALTH: &[&str] = &["green", "yellow", "red"];
+const HEALTH_LEVELS: &[&str] = &["cluster", "indices", "shards"];
+const PRIORITIES: &[&str] = &["immediate", "urgent", "high", "normal", "low", "languid"];
+
+const SEARCH_PARAMS: &[(&str, ParamKind)] = &[
+    ("allow_no_indices", ParamKind::Bool),
+    ("allow_partial_search_results", ParamKind::Bool),
+    ("batched_reduce_size", ParamKind::Count),
+    ("ccs_minimize_roundtrips", ParamKind::Bool),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("from", ParamKind::Count),
+    ("ignore_unavailable", ParamKind::Bool),
+    ("max_concurrent_shard_requests", ParamKind::Count),
+    ("pre_filter_shard_size", ParamKind::Count),
+    ("preference", ParamKind::Text),
+    ("request_cache", ParamKind::Bool),
+    ("rest_total_hits_as_int", ParamKind::Bool),
+    ("routing", ParamKind::Text),
+    ("scroll", ParamKind::Time),
+    ("search_type", ParamKind::OneOf(SEARCH_TYPES)),
+    ("seq_no_primary_term", ParamKind::Bool),
+    ("size", ParamKind::Count),
+    ("terminate_after", ParamKind::Count),
+    ("timeout", ParamKind::Time),
+    ("track_total_hits", ParamKind::BoolOrCount),
+    ("typed_keys", ParamKind::Bool),
+    ("version", ParamKind::Bool),
+];
+
+const SEARCH_TEMPLATE_PARAMS: &[(&str, ParamKind)] = &[
+    ("allow_no_indices", ParamKind::Bool),
+    ("ccs_minimize_roundtrips", ParamKind::Bool),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("ignore_unavailable", ParamKind::Bool),
+    ("preference", ParamKind::Text),
+    ("rest_total_hits_as_int", ParamKind::Bool),
+    ("routing", ParamKind::Text),
+    ("scroll", ParamKind::Time),
+    ("search_type", ParamKind::OneOf(SEARCH_TYPES)),
+    ("typed_keys", ParamKind::Bool),
+];
+
+const ASYNC_SEARCH_PARAMS: &[(&str, ParamKind)] = &[
+    ("allow_no_indices", ParamKind::Bool),
+    ("allow_partial_search_results", ParamKind::Bool),
+    ("batched_reduce_size", ParamKind::Count),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("from", ParamKind::Count),
+    ("ignore_unavailable", ParamKind::Bool),
+    ("keep_alive", ParamKind::Time),
+    ("keep_on_completion", ParamKind::Bool),
+    ("max_concurrent_shard_requests", ParamKind::Count),
+    ("pre_filter_shard_size", ParamKind::Count),
+    ("preference", ParamKind::Text),
+    ("request_cache", ParamKind::Bool),
+    ("routing", ParamKind::Text),
+    ("search_type", ParamKind::OneOf(SEARCH_TYPES)),
+    ("seq_no_primary_term", ParamKind::Bool),
+    ("size", ParamKind::Count),
+    ("terminate_after", ParamKind::Count),
+    ("timeout", ParamKind::Time),
+    ("track_total_hits", ParamKind::BoolOrCount),
+    ("typed_keys", ParamKind::Bool),
+    ("version", ParamKind::Bool),
+    ("wait_for_completion_timeout", ParamKind::Time),
+];
+
+const ASYNC_SEARCH_RESULT_PARAMS: &[(&str, ParamKind)] = &[
+    ("keep_alive", ParamKind::Time),
+    ("wait_for_completion_timeout", ParamKind::Time),
+];
+
+const UPDATE_BY_QUERY_PARAMS: &[(&str, ParamKind)] = &[
+    ("allow_no_indices", ParamKind::Bool),
+    ("conflicts", ParamKind::OneOf(CONFLICTS)),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("ignore_unavailable", ParamKind::Bool),
+    ("max_docs", ParamKind::Count),
+    ("refresh", ParamKind::Bool),
+    ("requests_per_second", ParamKind::Number),
+    ("routing", ParamKind::Text),
+    ("scroll", ParamKind::Time),
+    ("scroll_size", ParamKind::Count),
+    ("slices", ParamKind::Text),
+    ("timeout", ParamKind::Time),
+    ("wait_for_active_shards", ParamKind::Text),
+    ("wait_for_completion", ParamKind::Bool),
+];
+
+const BULK_PARAMS: &[(&str, ParamKind)] = &[
+    ("refresh", ParamKind::OneOf(REFRESH)),
+    ("require_alias", ParamKind::Bool),
+    ("routing", ParamKind::Text),
+    ("timeout", ParamKind::Time),
+    ("wait_for_active_shards", ParamKind::Text),
+];
+
+const WRITE_PARAMS: &[(&str, ParamKind)] = &[
+    ("if_primary_term", ParamKind::Count),
+    ("if_seq_no", ParamKind::Count),
+    ("op_type", ParamKind::OneOf(OP_TYPES)),
+    ("refresh", ParamKind::OneOf(REFRESH)),
+    ("require_alias", ParamKind::Bool),
+    ("retry_on_conflict", ParamKind::Count),
+    ("routing", ParamKind::Text),
+    ("timeout", ParamKind::Time),
+    ("version", ParamKind::Count),
+    ("version_type", ParamKind::OneOf(VERSION_TYPES)),
+    ("wait_for_active_shards", ParamKind::Text),
+];
+
+const SQL_PARAMS: &[(&str, ParamKind)] = &[("format", ParamKind::OneOf(SQL_FORMATS))];
+
+const TASK_PARAMS: &[(&str, ParamKind)] = &[
+    ("timeout", ParamKind::Time),
+    ("wait_for_completion", ParamKind::Bool),
+];
+
+const COUNT_PARAMS: &[(&str, ParamKind)] = &[
+    ("allow_no_indices", ParamKind::Bool),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("ignore_unavailable", ParamKind::Bool),
+    ("min_score", ParamKind::Number),
+    ("preference", ParamKind::Text),
+    ("routing", ParamKind::Text),
+    ("terminate_after", ParamKind::Count),
+];
+
+const FIELD_CAPS_PARAMS: &[(&str, ParamKind)] = &[
+    ("allow_no_indices", ParamKind::Bool),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("fields", ParamKind::Text),
+    ("ignore_unavailable", ParamKind::Bool),
+    ("include_unmapped", ParamKind::Bool),
+];
+
+const MAPPING_PARAMS: &[(&str, ParamKind)] = &[
+    ("allow_no_indices", ParamKind::Bool),
+    ("cluster_manager_timeout", ParamKind::Time),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("ignore_unavailable", ParamKind::Bool),
+    ("local", ParamKind::Bool),
+];
+
+const CAT_INDICES_PARAMS: &[(&str, ParamKind)] = &[
+    ("bytes", ParamKind::OneOf(BYTE_UNITS)),
+    ("cluster_manager_timeout", ParamKind::Time),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("format", ParamKind::OneOf(CAT_FORMATS)),
+    ("h", ParamKind::Text),
+    ("health", ParamKind::OneOf(HEALTH)),
+    ("include_unloaded_segments", ParamKind::Bool),
+    ("local", ParamKind::Bool),
+    ("pri", ParamKind::Bool),
+    ("s", ParamKind::Text),
+    ("time", ParamKind::OneOf(TIME_UNITS)),
+    ("v", ParamKind::Bool),
+];
+
+const CAT_ALIASES_PARAMS: &[(&str, ParamKind)] = &[
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("format", ParamKind::OneOf(CAT_FORMATS)),
+    ("h", ParamKind::Text),
+    ("local", ParamKind::Bool),
+    ("s", ParamKind::Text),
+    ("v", ParamKind::Bool),
+];
+
+const RESOLVE_INDEX_PARAMS: &[(&str, ParamKind)] =
+    &[("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS))];
+
+const CLUSTER_HEALTH_PARAMS: &[(&str, ParamKind)] = &[
+    ("cluster_manager_timeout", ParamKind::Time),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("level", ParamKind::OneOf(HEALTH_LEVELS)),
+    ("local", ParamKind::Bool),
+    ("timeout", ParamKind::Time),
+    ("wait_for_active_shards", ParamKind::Text),
+    ("wait_for_events", ParamKind::OneOf(PRIORITIES)),
+    ("wait_for_no_initializing_shards", ParamKind::Bool),
+    ("wait_for_no_relocating_shards", ParamKind::Bool),
+    ("wait_for_nodes", ParamKind::Text),
+    ("wait_for_status", ParamKind::OneOf(HEALTH)),
+];
+
+/// `_terms_enum` is answered by a search built by the proxy, so none of
+/// the search parameters apply.
+const TERMS_ENUM_PARAMS: &[(&str, ParamKind)] = &[];
+
+const GET_PARAMS: &[(&str, ParamKind)] = &[
+    ("preference", ParamKind::Text),
+    ("routing", ParamKind::Text),
+];
+
+const SCROLL_PARAMS: &[(&str, ParamKind)] =
+    &[("scroll", ParamKind::Time), ("scroll_id", ParamKind::Text)];
+
+const POINT_IN_TIME_PARAMS: &[(&str, ParamKind)] = &[
+    ("allow_partial_pit_creation", ParamKind::Bool),
+    ("expand_wildcards", ParamKind::OneOf(EXPAND_WILDCARDS)),
+    ("keep_alive", ParamKind::Time),
+    ("preference", ParamKind::Text),
+    ("routing", ParamKind::Text),
+];
+
+const MSEARCH_PARAMS: &[(&str, ParamKind)] = &[
+    ("ccs_minimize_roundtrips", ParamKind::Bool),
+    ("max_concurrent_searches", ParamKind::Count),
+    ("max_concurrent_shard_requests", ParamKind::Count),
+    ("pre_filter_shard_size", ParamKind::Count),
+    ("rest_total_hits_as_int", ParamKind::Bool),
+    ("search_type", ParamKind::OneOf(SEARCH_TYPES)),
+    ("typed_keys", ParamKind::Bool),
+];
+
+/// Endpoints accepting query string parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchEndpoint {
+    Search,
+    SearchTemplate,
+    AsyncSearch,
+    AsyncSearchResult,
+    Msearch,
+    Count,
+    TermsEnum,
+    FieldCaps,
+    Mapping,
+    CatIndices,
+    CatAliases,
+    ResolveIndex,
+    ClusterHealth,
+    Get,
+    Mget,
+    Scroll,
+    PointInTime,
+    UpdateByQuery,
+    DeleteByQuery,
+    Bulk,
+    Write,
+    Sql,
+    Task,
+}
+
+impl SearchEndpoint {
+    fn path(&self) -> &'static str {
+        match self {
+            Self::Search => "/_search",
+            Self::SearchTemplate => "/_search/template",
+            Self::AsyncSearch | Self::AsyncSearchResult => "/_plugins/_asynchronous_search",
+            Self::Msearch => "/_msearch",
+            Self::Count => "/_count",
+            Self::TermsEnum => "/_terms_enum",
+            Self::FieldCaps => "/_field_caps",
+            Self::Mapping => "/_mapping",
+            Self::CatIndices => "/_cat/indices",
+            Self::CatAliases => "/_cat/aliases",
+            Self::ResolveIndex => "/_resolve/index",
+            Self::ClusterHealth => "/_cluster/health",
+            Self::Get => "/_doc",
+            Self::Mget => "/_mget",
+            Self::Scroll => "/_search/scroll",
+            Self::PointInTime => "/_search/point_in_time",
+            Self::UpdateByQuery => "/_update_by_query",
+            Self::DeleteByQuery => "/_delete_by_query",
+            Self::Bulk => "/_bulk",
+            Self::Write => "/_doc",
+            Self::Sql => "/_plugins/_sql",
+            Self::Task => "/_tasks",
+        }
+    }
+
+    /// Returns whether the endpoint accepts a URI search in `q`.
+    pub fn supports_uri_search(&self) -> bool {
+        matches!(self, Self::Search | Self::Count)
+    }
+
+    fn allowed(&self) -> &'static [(&'static str, ParamKind)] {
+        match self {
+            Self::Search => SEARCH_PARAMS,
+            Self::SearchTemplate => SEARCH_TEMPLATE_PARAMS,
+            Self::AsyncSearch => ASYNC_SEARCH_PARAMS,
+            Self::AsyncSearchResult => ASYNC_SEARCH_RESULT_PARAMS,
+            Self::Msearch => MSEARCH_PARAMS,
+            Self::Count => COUNT_PARAMS,
+            Self::TermsEnum => TERMS_ENUM_PARAMS,
+            Self::FieldCaps => FIELD_CAPS_PARAMS,
+            Self::Mapping => MAPPING_PARAMS,
+            Self::CatIndices => CAT_INDICES_PARAMS,
+            Self::CatAliases => CAT_ALIASES_PARAMS,
+            Self::ResolveIndex => RESOLVE_INDEX_PARAMS,
+            Self::ClusterHealth => CLUSTER_HEALTH_PARAMS,
+            Self::Get | Self::Mget => GET_PARAMS,
+            Self::Scroll => SCROLL_PARAMS,
+            Self::PointInTime => POINT_IN_TIME_PARAMS,
+            Self::UpdateByQuery | Self::DeleteByQuery => UPDATE_BY_QUERY_PARAMS,
+            Self::Bulk => BULK_PARAMS,
+            Self::Write => WRITE_PARAMS,
+            Self::Sql => SQL_PARAMS,
+            Self::Task => TASK_PARAMS,
+        }
+    }
+}
+
+/// Error returned when a query string parameter is not accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamRejection {
+    /// The parameter is not known for the endpoint
+    Unrecognized {
+        endpoint: SearchEndpoint,
+        name: String,
+    },
+    /// The value of the parameter could not be parsed
+    InvalidValue { name: String, value: String },
+    /// The parameter is known but may not be set by clients
+    Forbidden(String),
+}
+
+impl IntoResponse for ParamRejection {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected query string parameter: {:?}", self);
+
+        let (status, error_type, reason) = match self {
+            Self::Unrecognized { endpoint, name } => (
+                StatusCode::BAD_REQUEST,
+                "illegal_argument_exception",
+                format!(
+                    "request [{}] contains unrecognized parameter: [{}]",
+                    endpoint.path(),
+                    name
+                ),
+            ),
+            Self::InvalidValue { name, value } => (
+                StatusCode::BAD_REQUEST,
+                "illegal_argument_exception",
+                format!("failed to parse value [{}] for parameter [{}]", value, name),
+            ),
+            Self::Forbidden(name) => (
+                StatusCode::FORBIDDEN,
+                "security_exception",
+                format!("the [{}] parameter is not permitted", name),
+            ),
+        };
+
+        (
+            status,
+            Json(json!({
+                "error": { "type": error_type, "reason": reason },
+                "status": status.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// The effective query string policy of a caller.
+#[derive(Debug, Clone)]
+pub struct ParamPolicy {
+    /// Search pipeline forced onto every search of the caller
+    search_pipeline: Option<String>,
+    /// Longest keep-alive of scrolls and points in time of the caller
+    max_keep_alive: Duration,
+    /// Longest keep-alive of asynchronous searches of the caller
+    max_async_keep_alive: Duration,
+}
+
+impl Default for ParamPolicy {
+    fn default() -> Self {
+        Self {
+            search_pipeline: None,
+            max_keep_alive: DEFAULT_MAX_KEEP_ALIVE,
+            max_async_keep_alive: DEFAULT_MAX_ASYNC_KEEP_ALIVE,
+        }
+    }
+}
+
+/// A service validating the query string parameters of searches.
+///
+/// Only parameters on the allowlist of an endpoint are forwarded, after
+/// their values have been validated. Parameters that could bypass the
+/// security filter are rejected, and a role may instead force a search
+/// pipeline onto its searches.
+#[derive(Clone)]
+pub struct SearchParamsService;
+
+impl SearchParamsService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Resolves the query string policy of the given identity. The search
+    /// pipeline of the first role defining one and the longest keep-alive
+    /// of any role are used. The `max_keep_alive` of a role caps
+    /// asynchronous searches as well, while roles without one fall back to
+    /// a separate default for them.
+    pub fn policy_for(&self, policy: &Policy, identity: &Identity) -> ParamPolicy {
+        let roles = policy.roles_for(identity);
+        let max_keep_alive = |default: Duration| {
+            roles
+                .iter()
+                .map(|role| match &role.max_keep_alive {
+                    None => default,
+                    Some(value) => parse_time(value).unwrap_or_else(|| {
+                        tracing::warn!("Ignoring invalid max_keep_alive '{}'", value);
+                        default
+                    }),
+                })
+                .max()
+                .unwrap_or(default)
+        };
+
+        ParamPolicy {
+            search_pipeline: roles.iter().find_map(|role| role.search_pipeline.clone()),
+            max_keep_alive: max_keep_alive(DEFAULT_MAX_KEEP_ALIVE),
+            max_async_keep_alive: max_keep_alive(DEFAULT_MAX_ASYNC_KEEP_ALIVE),
+        }
+    }
+
+    /// Parses the keep-alive of a scroll or point in time given in the
+    /// named option, capped to the longest keep-alive permitted to the
+    /// caller.
+    pub fn keep_alive(
+        &self,
+        name: &str,
+        value: &str,
+        policy: &ParamPolicy,
+    ) -> Result<Duration, ParamRejection> {
+        match parse_time(value) {
+            Some(keep_alive) => Ok(keep_alive.min(policy.max_keep_alive)),
+            None => Err(ParamRejection::InvalidValue {
+                name: name.to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+
+    /// Parses the keep-alive of an asynchronous search, capped to the
+    /// longest keep-alive of asynchronous searches permitted to the caller.
+    fn async_keep_alive(
+        &self,
+        name: &str,
+        value: &str,
+        policy: &ParamPolicy,
+    ) -> Result<Duration, ParamRejection> {
+        match parse_time(value) {
+            Some(keep_alive) => Ok(keep_alive.min(policy.max_async_keep_alive)),
+            None => Err(ParamRejection::InvalidValue {
+                name: name.to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+
+    /// Removes the parameters of a URI search and converts them into an
+    /// equivalent `query_string` query, if `q` is set.
+    pub fn take_uri_query(
+        &self,
+        params: &mut SearchParams,
+    ) -> Result<Option<Value>, ParamRejection> {
+        let mut query_string = Map::new();
+        for (name, value) in
+            params.extract_if(.., |(name, _)| URI_SEARCH_PARAMS.contains(&name.as_str()))
+        {
+            let key = match name.as_str() {
+                "q" => "query",
+                "df" => "default_field",
+                "default_operator" if !DEFAULT_OPERATORS.contains(&value.as_str()) => {
+                    return Err(ParamRejection::InvalidValue { name, value });
+                }
+                other => other,
+            };
+            query_string.insert(key.to_string(), Value::String(value));
+        }
+
+        if !query_string.contains_key("query") {
+            return Ok(None);
+        }
+        Ok(Some(json!({ "query_string": query_string })))
+    }
+
+    /// Validates the parameters of a request to the given endpoint and
+    /// returns the parameters to forward.
+    pub fn filter(
+        &self,
+        endpoint: SearchEndpoint,
+        params: SearchParams,
+        policy: &ParamPolicy,
+    ) -> Result<SearchParams, ParamRejection> {
+        let mut forwarded = SearchParams::with_capacity(params.len() + 1);
+        for (name, value) in params {
+            if FORBIDDEN_PARAMS.contains(&name.as_str()) {
+                return Err(ParamRejection::Forbidden(name));
+            }
+            let Some((_, kind)) = endpoint.allowed().iter().find(|(known, _)| *known == name)
+            else {
+                return Err(ParamRejection::Unrecognized { endpoint, name });
+            };
+            if !is_valid(*kind, &value) {
+                return Err(ParamRejection::InvalidValue { name, value });
+            }
+            if KEEP_ALIVE_PARAMS.contains(&name.as_str()) {
+                let keep_alive = match endpoint {
+                    SearchEndpoint::AsyncSearch | SearchEndpoint::AsyncSearchResult => {
+                        self.async_keep_alive(&name, &value, policy)?
+                    }
+                    _ => self.keep_alive(&name, &value, policy)?,
+                };
+                forwarded.push((name, format_time(keep_alive)));
+                continue;
+            }
+            forwarded.push((name, value));
+        }
+
+        // Only searches accept a search pipeline parameter, and rendered
+        // templates, terms enumerations and document gets are sent to
+        // `_search`
+        if let (
+            SearchEndpoint::Search
+            | SearchEndpoint::SearchTemplate
+            | SearchEndpoint::AsyncSearch
+            | SearchEndpoint::TermsEnum
+            | SearchEndpoint::Get,
+            Some(pipeline),
+        ) = (endpoint, &policy.search_pipeline)
+        {
+            forwarded.push((SEARCH_PIPELINE.to_string(), pipeline.clone()));
+        }
+        Ok(forwarded)
+    }
+
+    /// Rejects a search body choosing its own search pipeline.
+    pub fn check_body(&self, body: &Value) -> Result<(), ParamRejection> {
+        match body.get(SEARCH_PIPELINE) {
+            Some(_) => Err(ParamRejection::Forbidden(SEARCH_PIPELINE.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Rejects an `_msearch` header choosing its own search pipeline and
+    /// forces the pipeline of the caller onto it, since the query string
+    /// of `_msearch` does not apply one.
+    pub fn restrict_msearch_header(
+        &self,
+        header: &mut Value,
+        policy: &ParamPolicy,
+    ) -> Result<(), ParamRejection> {
+        self.check_body(header)?;
+        if let (Some(header), Some(pipeline)) = (header.as_object_mut(), &policy.search_pipeline) {
+            header.insert(SEARCH_PIPELINE.to_string(), Value::String(pipeline.clone()));
+        }
+        Ok(())
+    }
+}
+
+fn is_valid(kind: ParamKind, value: &str) -> bool {
+    match kind {
+        ParamKind::Bool => is_bool(value),
+        ParamKind::Count => value.parse::<u64>().is_ok(),
+        ParamKind::Number => value.parse::<f64>().is_ok_and(f64::is_finite),
+        ParamKind::Time => is_time(value),
+        ParamKind::Text => !value.is_empty(),
+        ParamKind::OneOf(options) => value.split(',').all(|option| options.contains(&option)),
+        ParamKind::BoolOrCount => is_bool(value) || value.parse::<u64>().is_ok(),
+    }
+}
+
+/// Accepts `true` and `false`, as well as an empty value, which OpenSearch
+/// treats as `true`.
+fn is_bool(value: &str) -> bool {
+    matches!(value, "" | "true" | "false")
+}
+
+/// Parses an OpenSearch time value such as `30s` or `1m`. `-1` has no
+/// duration.
+pub(crate) fn parse_time(value: &str) -> Option<Duration> {
+    let unit_start = value.find(|c: char| !c.is_ascii_digit())?;
+    let amount: u64 = value[..unit_start].parse().ok()?;
+    let duration = match &value[unit_start..] {
+        "nanos" => Duration::from_nanos(amount),
+        "micros" => Duration::from_micros(amount),
+        "ms" => Duration::from_millis(amount),
+        "s" => Duration::from_secs(amount),
+        "m" => Duration::from_secs(amount.checked_mul(60)?),
+        "h" => Duration::from_secs(amount.checked_mul(60 * 60)?),
+        "d" => Duration::from_secs(amount.checked_mul(24 * 60 * 60)?),
+        _ => return None,
+    };
+    Some(duration)
+}
+
+/// Formats a duration as an OpenSearch time value in milliseconds.
+pub(crate) fn format_time(duration: Duration) -> String {
+    format!("{}ms", duration.as_millis())
+}
+
+fn is_time(value: &str) -> bool {
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(unit_start);
+
+    value == "-1"
+        || (!amount.is_empty() && matches!(unit, "nanos" | "micros" | "ms" | "s" | "m" | "h" | "d"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> SearchParams {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_forwards_allowed_params() {
+        let service = SearchParamsService::new();
+        let request = params(&[
+            ("size", "10"),
+            ("track_total_hits", "true"),
+            ("timeout", "500ms"),
+            ("search_type", "dfs_query_then_fetch"),
+        ]);
+
+        let result = service.filter(
+            SearchEndpoint::Search,
+            request.clone(),
+            &ParamPolicy::default(),
+        );
+
+        assert_eq!(result, Ok(request));
+    }
+
+    #[test]
+    fn test_filter_rejects_params() {
+        let service = SearchParamsService::new();
+        let policy = ParamPolicy::default();
+
+        assert_eq!(
+            service.filter(
+                SearchEndpoint::Search,
+                params(&[("q", "title:rust")]),
+                &policy
+            ),
+            Err(ParamRejection::Forbidden("q".to_string()))
+        );
+        assert_eq!(
+            service.filter(SearchEndpoint::Msearch, params(&[("size", "10")]), &policy),
+            Err(ParamRejection::Unrecognized {
+                endpoint: SearchEndpoint::Msearch,
+                name: "size".to_string()
+            })
+        );
+        assert_eq!(
+            service.filter(
+                SearchEndpoint::Search,
+                params(&[("timeout", "soon")]),
+                &policy
+            ),
+            Err(ParamRejection::InvalidValue {
+                name: "timeout".to_string(),
+                value: "soon".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_filter_caps_scroll_keep_alive() {
+        let service = SearchParamsService::new();
+        let policy = ParamPolicy::default();
+
+        assert_eq!(
+            service.filter(SearchEndpoint::Search, params(&[("scroll", "1h")]), &policy),
+            Ok(params(&[("scroll", "300000ms")]))
+        );
+        assert_eq!(
+            service.filter(
+                SearchEndpoint::Search,
+                params(&[("scroll", "30s")]),
+                &policy
+            ),
+            Ok(params(&[("scroll", "30000ms")]))
+        );
+        assert_eq!(parse_time("-1"), None);
+    }
+
+    #[test]
+    fn test_filter_caps_async_search_keep_alive() {
+        let service = SearchParamsService::new();
+        let policy = ParamPolicy::default();
+
+        assert_eq!(
+            service.filter(
+                SearchEndpoint::AsyncSearch,
+                params(&[("keep_alive", "12h")]),
+                &policy
+            ),
+            Ok(params(&[("keep_alive", "43200000ms")]))
+        );
+        assert_eq!(
+            service.filter(
+                SearchEndpoint::AsyncSearchResult,
+                params(&[("keep_alive", "5d")]),
+                &policy
+            ),
+            Ok(params(&[("keep_alive", "43200000ms")]))
+        );
+
+        let roles: Policy = serde_json::from_value(json!({
+            "roles": { "analyst": { "max_keep_alive": "1h" } }
+        }))
+        .unwrap();
+        let identity = Identity {
+            user: "alice".to_string(),
+            roles: vec!["analyst".to_string()],
+        };
+        let configured = service.policy_for(&roles, &identity);
+        assert_eq!(
+            service.filter(
+                SearchEndpoint::AsyncSearch,
+                params(&[("keep_alive", "12h")]),
+                &configured
+            ),
+            Ok(params(&[("keep_alive", "3600000ms")]))
+        );
+    }
+
+    #[test]
+    fn test_take_uri_query() {
+        let service = SearchParamsService::new();
+        let mut request = params(&[
+            ("q", "title:rust"),
+            ("size", "5"),
+            ("df", "title"),
+            ("default_operator", "AND"),
+        ]);
+
+        let query = service.take_uri_query(&mut request);
+
+        assert_eq!(
+            query,
+            Ok(Some(json!({
+                "query_string": {
+                    "query": "title:rust",
+                    "default_field": "title",
+                    "default_operator": "AND"
+                }
+            })))
+        );
+        assert_eq!(request, params(&[("size", "5")]));
+
+        let mut request = params(&[("q", "rust"), ("default_operator", "XOR")]);
+        assert!(service.take_uri_query(&mut request).is_err());
+    }
+
+    #[test]
+    fn test_filter_forces_role_pipeline() {
+        let service = SearchParamsService::new();
+        let policy = ParamPolicy {
+            search_pipeline: Some("tenant-pipeline".to_string()),
+            ..ParamPolicy::default()
+        };
+
+        let result = service.filter(SearchEndpoint::Search, params(&[("size", "5")]), &policy);
+
+        assert_eq!(
+            result,
+            Ok(params(&[
+                ("size", "5"),
+                ("search_pipeline", "tenant-pipeline")
+            ]))
+        );
+
+        let mut header = json!({ "index": "movies" });
+        assert_eq!(
+            service.restrict_msearch_header(&mut header, &policy),
+            Ok(())
+        );
+        assert_eq!(
+            header,
+            json!({ "index": "movies", "search_pipeline": "tenant-pipeline" })
+        );
+    }
+
+    #[test]
+    fn test_rejects_search_pipeline_in_body() {
+        let service = SearchParamsService::new();
+        let forbidden = Err(ParamRejection::Forbidden("search_pipeline".to_string()));
+
+        let body = json!({
+            "query": { "match_all": {} },
+            "search_pipeline": { "request_processors": [{ "script": { "source": "" } }] }
+        });
+        assert_eq!(service.check_body(&body), forbidden);
+
+        let mut header = json!({ "index": "movies", "search_pipeline": "other" });
+        assert_eq!(
+            service.restrict_msearch_header(&mut header, &ParamPolicy::default()),
+            forbidden
+        );
+        assert_eq!(service.check_body(&json!({ "size": 0 })), Ok(()));
+    }
+}
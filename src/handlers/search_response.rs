@@ -0,0 +1,81 @@
+use serde_json::{Map, Value};
+
+/// Calls `f` for every hit of a search response, including hits nested in
+/// `inner_hits` and hits returned by `top_hits` aggregations.
+///
+/// `f` also receives the path of the hit's `_source` within its document,
+/// which is the nested field for inner hits of `nested` queries and empty
+/// for any other hit.
+pub(crate) fn for_each_hit<F>(response: &mut Value, f: &mut F)
+where
+    F: FnMut(&mut Map<String, Value>, &str),
+{
+    visit_hits(response, f);
+    if let Some(aggregations) = response.get_mut("aggregations") {
+        visit_aggregation_hits(aggregations, f);
+    }
+}
+
+fn visit_hits<F>(response: &mut Value, f: &mut F)
+where
+    F: FnMut(&mut Map<String, Value>, &str),
+{
+    let Some(hits) = response
+        .pointer_mut("/hits/hits")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+
+    for hit in hits.iter_mut().filter_map(Value::as_object_mut) {
+        f(hit, &nested_path(hit));
+        if let Some(inner_hits) = hit.get_mut("inner_hits").and_then(Value::as_object_mut) {
+            for inner in inner_hits.values_mut() {
+                visit_hits(inner, f);
+            }
+        }
+    }
+}
+
+/// Returns the nested field a hit was found in, following the `_nested`
+/// identity through every level, e.g. `comments.replies`.
+fn nested_path(hit: &Map<String, Value>) -> String {
+    let mut fields = Vec::new();
+    let mut identity = hit.get("_nested");
+    while let Some(nested) = identity {
+        if let Some(field) = nested.get("field").and_then(Value::as_str) {
+            fields.push(field);
+        }
+        identity = nested.get("_nested");
+    }
+    fields.join(".")
+}
+
+/// Walks an aggregation result tree and visits every embedded hit list, as
+/// produced by `top_hits`.
+fn visit_aggregation_hits<F>(value: &mut Value, f: &mut F)
+where
+    F: FnMut(&mut Map<String, Value>, &str),
+{
+    match value {
+        Value::Object(object) => {
+            if object
+                .get("hits")
+                .and_then(|hits| hits.get("hits"))
+                .is_some()
+            {
+                visit_hits(value, f);
+                return;
+            }
+            for child in object.values_mut() {
+                visit_aggregation_hits(child, f);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                visit_aggregation_hits(item, f);
+            }
+        }
+        _ => {}
+    }
+}
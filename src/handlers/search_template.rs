@@ -0,0 +1,292 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+use crate::auth::identity::Identity;
+use crate::handlers::mustache;
+use crate::models::policy::Policy;
+
+/// Keys accepted in a search template body.
+const TEMPLATE_KEYS: &[&str] = &["id", "source", "params", "explain", "profile"];
+
+/// Reasons a search template is rejected before it reaches OpenSearch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateRejection {
+    /// The body does not describe a template
+    Invalid(String),
+    /// The template could not be rendered into a search body
+    Render(String),
+    /// The stored template does not exist or is not allowed for the caller
+    UnknownTemplate(String),
+}
+
+impl IntoResponse for TemplateRejection {
+    fn into_response(self) -> Response {
+        let (status, error_type, reason) = match self {
+            Self::Invalid(reason) => (
+                StatusCode::BAD_REQUEST,
+                "illegal_argument_exception",
+                reason,
+            ),
+            Self::Render(reason) => (
+                StatusCode::BAD_REQUEST,
+                "general_script_exception",
+                format!("Failed to render search template: {}", reason),
+            ),
+            Self::UnknownTemplate(id) => {
+                tracing::warn!("Rejected unknown or unlisted search template '{}'", id);
+                (
+                    StatusCode::NOT_FOUND,
+                    "resource_not_found_exception",
+                    format!("unable to find script [{}]", id),
+                )
+            }
+        };
+
+        (
+            status,
+            Json(json!({
+                "error": { "type": error_type, "reason": reason },
+                "status": status.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// The stored search templates a caller may run.
+#[derive(Debug, Clone, Default)]
+pub struct TemplatePolicy {
+    stored: Vec<String>,
+}
+
+/// The template of a search template request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Template {
+    /// A template given in the request
+    Inline(String),
+    /// The id of a stored template
+    Stored(String),
+}
+
+/// A parsed search template request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateRequest {
+    pub template: Template,
+    pub params: Value,
+    explain: Option<bool>,
+    profile: Option<bool>,
+}
+
+/// A service rendering search templates in the proxy.
+///
+/// OpenSearch renders search templates after the request has passed the
+/// proxy, so the resulting query would never see the security filter.
+/// Templates are therefore rendered here into a plain search body, which
+/// takes the same path as any other search. Inline templates are always
+/// accepted, stored templates only if a role of the caller lists their id.
+#[derive(Clone)]
+pub struct SearchTemplateService;
+
+impl SearchTemplateService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Resolves the stored templates of the given identity, combining the
+    /// templates of all its roles.
+    pub fn policy_for(&self, policy: &Policy, identity: &Identity) -> TemplatePolicy {
+        TemplatePolicy {
+            stored: policy
+                .roles_for(identity)
+                .into_iter()
+                .flat_map(|role| role.search_templates.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// Parses a search template body with either an inline `source` or the
+    /// `id` of a stored template.
+    pub fn parse_request(&self, body: &Value) -> Result<TemplateRequest, TemplateRejection> {
+        let Some(object) = body.as_object() else {
+            return Err(TemplateRejection::Invalid(
+                "search template must be an object".to_string(),
+            ));
+        };
+        if let Some(key) = object
+            .keys()
+            .find(|key| !TEMPLATE_KEYS.contains(&key.as_str()))
+        {
+            return Err(TemplateRejection::Invalid(format!(
+                "unknown key [{}] in search template",
+                key
+            )));
+        }
+
+        let template = match (object.get("source"), object.get("id")) {
+            (Some(Value::String(source)), None) => Template::Inline(source.clone()),
+            // Object sources are rendered from their serialized form
+            (Some(source @ Value::Object(_)), None) => Template::Inline(source.to_string()),
+            (None, Some(Value::String(id))) => Template::Stored(id.clone()),
+            _ => {
+                return Err(TemplateRejection::Invalid(
+                    "either [source] or [id] must be provided".to_string(),
+                ));
+            }
+        };
+        let params = match object.get("params") {
+            None | Some(Value::Null) => json!({}),
+            Some(params @ Value::Object(_)) => params.clone(),
+            Some(_) => {
+                return Err(TemplateRejection::Invalid(
+                    "[params] must be an object".to_string(),
+                ));
+            }
+        };
+
+        Ok(TemplateRequest {
+            template,
+            params,
+            explain: object.get("explain").and_then(Value::as_bool),
+            profile: object.get("profile").and_then(Value::as_bool),
+        })
+    }
+
+    /// Rejects stored templates that no role of the caller lists.
+    pub fn authorize(&self, id: &str, policy: &TemplatePolicy) -> Result<(), TemplateRejection> {
+        if policy.stored.iter().any(|stored| stored == id) {
+            Ok(())
+        } else {
+            Err(TemplateRejection::UnknownTemplate(id.to_string()))
+        }
+    }
+
+    /// Returns the source of a mustache template from a get script
+    /// response.
+    pub fn stored_source(&self, id: &str, script: &Value) -> Result<String, TemplateRejection> {
+        let lang = script.pointer("/script/lang").and_then(Value::as_str);
+        match (lang, script.pointer("/script/source")) {
+            (Some("mustache"), Some(Value::String(source))) => Ok(source.clone()),
+            (Some("mustache"), Some(source @ Value::Object(_))) => Ok(source.to_string()),
+            _ => Err(TemplateRejection::UnknownTemplate(id.to_string())),
+        }
+    }
+
+    /// Renders a template into a search body, carrying over the `explain`
+    /// and `profile` flags of the request.
+    pub fn render(
+        &self,
+        source: &str,
+        request: &TemplateRequest,
+    ) -> Result<Value, TemplateRejection> {
+        let rendered = mustache::render(source, &request.params)
+            .map_err(|e| TemplateRejection::Render(e.0))?;
+        let mut body: Value = serde_json::from_str(&rendered)
+            .map_err(|e| TemplateRejection::Render(format!("invalid JSON: {}", e)))?;
+        if !body.is_object() {
+            return Err(TemplateRejection::Render(
+                "the rendered template is not an object".to_string(),
+            ));
+        }
+
+        for (flag, value) in [("explain", request.explain), ("profile", request.profile)] {
+            if let Some(value) = value {
+                body[flag] = Value::Bool(value);
+            }
+        }
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let service = SearchTemplateService::new();
+
+        let request = service
+            .parse_request(&json!({
+                "source": { "query": { "match": { "title": "{{title}}" } } },
+                "params": { "title": "Rust" }
+            }))
+            .unwrap();
+        assert_eq!(
+            request.template,
+            Template::Inline(r#"{"query":{"match":{"title":"{{title}}"}}}"#.to_string())
+        );
+
+        let request = service.parse_request(&json!({ "id": "by-title" })).unwrap();
+        assert_eq!(request.template, Template::Stored("by-title".to_string()));
+        assert_eq!(request.params, json!({}));
+
+        assert!(service.parse_request(&json!({ "params": {} })).is_err());
+        assert!(
+            service
+                .parse_request(&json!({ "id": "a", "source": "{}" }))
+                .is_err()
+        );
+        assert!(
+            service
+                .parse_request(&json!({ "id": "a", "lang": "painless" }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_authorize_stored_templates() {
+        let service = SearchTemplateService::new();
+        let policy = TemplatePolicy {
+            stored: vec!["by-title".to_string()],
+        };
+
+        assert!(service.authorize("by-title", &policy).is_ok());
+        assert_eq!(
+            service.authorize("by-genre", &policy),
+            Err(TemplateRejection::UnknownTemplate("by-genre".to_string()))
+        );
+        assert_eq!(
+            service.stored_source(
+                "by-title",
+                &json!({ "found": true, "script": { "lang": "mustache", "source": "{}" } })
+            ),
+            Ok("{}".to_string())
+        );
+        assert!(
+            service
+                .stored_source(
+                    "by-title",
+                    &json!({ "found": true, "script": { "lang": "painless", "source": "1" } })
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let service = SearchTemplateService::new();
+        let request = service
+            .parse_request(&json!({
+                "source": r#"{"query": {"match": {"title": "{{title}}"}}, "size": {{size}}}"#,
+                "params": { "title": "Rust", "size": 5 },
+                "explain": true
+            }))
+            .unwrap();
+        let Template::Inline(source) = &request.template else {
+            panic!("expected an inline template");
+        };
+
+        assert_eq!(
+            service.render(source, &request).unwrap(),
+            json!({ "query": { "match": { "title": "Rust" } }, "size": 5, "explain": true })
+        );
+        assert!(matches!(
+            service.render("{\"size\": {{size}", &request),
+            Err(TemplateRejection::Render(_))
+        ));
+    }
+}
@@ -24,12 +24,24 @@ impl SecurityFilterService {
                 }
             }
             None => {
-                // throw error or handle case where "query" is missing
+                // Bodies without a query match every document, so the filter
+                // becomes the query
+                query["query"] = json!({ "bool": { "filter": filter_snippet } });
             }
         }
         query
     }
 
+    /// Returns whether a filter matches every document, so that reads
+    /// bypassing it reveal nothing.
+    pub fn is_match_all(&self, filter: &Value) -> bool {
+        match filter {
+            Value::Array(filters) => filters.iter().all(|filter| self.is_match_all(filter)),
+            Value::Object(object) => object.len() == 1 && object.contains_key("match_all"),
+            _ => false,
+        }
+    }
+
     fn wrap_in_bool_query(&self, original_query: Value, filter: Value) -> Value {
         json!({
             "bool": {
@@ -92,6 +104,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_is_match_all() {
+        let service = SecurityFilterService::new();
+
+        assert!(service.is_match_all(&json!({ "match_all": {} })));
+        assert!(service.is_match_all(&json!([{ "match_all": { "boost": 1.0 } }])));
+        assert!(!service.is_match_all(&json!({ "term": { "user": "john" } })));
+        assert!(
+            !service.is_match_all(&json!([{ "match_all": {} }, { "term": { "user": "john" } }]))
+        );
+    }
+
+    #[test]
+    fn test_apply_to_body_without_query() {
+        let service = SecurityFilterService::new();
+        let query = json!({ "size": 0 });
+        let filter = json!({"term": {"user": "john"}});
+
+        let result = service.apply(query, filter);
+
+        assert_eq!(
+            result,
+            json!({"query":{"bool":{"filter":{"term":{"user":"john"}}}},"size":0})
+        );
+    }
+
     #[test]
     fn test_add_filter_to_existing_bool_query() {
         let service = SecurityFilterService::new();
@@ -0,0 +1,710 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+use crate::auth::identity::Identity;
+use crate::handlers::{
+    field_security::FieldPolicy, masking::MaskingPolicy, pseudonymization::PseudonymPolicy,
+};
+use crate::repositories::context::SearchContextRepository;
+
+/// How long a cursor of a paginated SQL query remains accessible to its
+/// creator.
+const CURSOR_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// Keys accepted in the body of an SQL query.
+const SQL_KEYS: &[&str] = &["query", "filter", "fetch_size", "parameters"];
+
+/// Keys accepted in the body of a PPL query.
+const PPL_KEYS: &[&str] = &["query"];
+
+/// SQL keywords combining several sources, which the filter of the SQL
+/// API does not cover.
+const FORBIDDEN_SQL_KEYWORDS: &[&str] = &["JOIN", "UNION", "INTERSECT", "MINUS", "EXCEPT"];
+
+/// PPL commands reading further sources.
+const FORBIDDEN_PPL_COMMANDS: &[&str] = &[
+    "append",
+    "appendcols",
+    "appendpipe",
+    "join",
+    "lookup",
+    "multisearch",
+];
+
+/// Reasons an SQL or PPL request is rejected before it reaches OpenSearch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlRejection {
+    /// The request could not be parsed
+    Invalid(String),
+    /// The query uses a construct the security filter cannot cover
+    Forbidden(String),
+    /// The cursor is unknown, expired or was created by another identity
+    UnknownCursor,
+}
+
+impl IntoResponse for SqlRejection {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected SQL request: {:?}", self);
+
+        let (status, error_type, reason) = match self {
+            Self::Invalid(reason) => (
+                StatusCode::BAD_REQUEST,
+                "illegal_argument_exception",
+                reason,
+            ),
+            Self::Forbidden(reason) => (StatusCode::FORBIDDEN, "security_exception", reason),
+            Self::UnknownCursor => (
+                StatusCode::NOT_FOUND,
+                "resource_not_found_exception",
+                "cursor not found or expired".to_string(),
+            ),
+        };
+
+        (
+            status,
+            Json(json!({
+                "error": { "type": error_type, "reason": reason },
+                "status": status.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// The query languages of the SQL plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlLanguage {
+    Sql,
+    Ppl,
+}
+
+/// A parsed request to the SQL plugin.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlRequest {
+    /// A query reading the given index expressions
+    Query { indices: Vec<String>, body: Value },
+    /// The continuation of a paginated SQL query
+    Cursor(String),
+}
+
+/// A token of an SQL or PPL query.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// A quoted identifier or string with its quote character
+    Quoted(char, String),
+    Symbol(char),
+}
+
+/// A service checking SQL and PPL queries of the SQL plugin.
+///
+/// The plugin runs its own queries, so the proxy only sees the query text.
+/// Queries are parsed far enough to find the indices they read, which are
+/// authorized like the indices of a search. SQL queries receive the
+/// security filter in the `filter` field of the request, which the plugin
+/// adds to the generated search. Joins, set operations and subqueries are
+/// rejected, as the plugin does not apply the filter to them. PPL has no
+/// such field, so the filter is translated into a `where` command
+/// following the source command.
+///
+/// Results are returned unchanged, so callers restricted by field level
+/// security, masking or pseudonymization may not use SQL or PPL.
+#[derive(Clone)]
+pub struct SqlService {
+    cursors: SearchContextRepository<()>,
+}
+
+impl SqlService {
+    pub fn new(cursors: SearchContextRepository<()>) -> Self {
+        Self { cursors }
+    }
+
+    /// Rejects callers whose results would have to be rewritten.
+    pub fn authorize_caller(
+        &self,
+        fields: &FieldPolicy,
+        masking: &MaskingPolicy,
+        pseudonyms: &PseudonymPolicy,
+    ) -> Result<(), SqlRejection> {
+        if fields.is_unrestricted() && masking.is_empty() && pseudonyms.is_empty() {
+            Ok(())
+        } else {
+            Err(SqlRejection::Forbidden(
+                "SQL and PPL are not permitted to callers with field restrictions".to_string(),
+            ))
+        }
+    }
+
+    /// Parses a request body, returning the indices read by its query.
+    pub fn parse_request(
+        &self,
+        language: SqlLanguage,
+        body: Value,
+    ) -> Result<SqlRequest, SqlRejection> {
+        let Value::Object(object) = body else {
+            return Err(SqlRejection::Invalid(
+                "request body must be an object".to_string(),
+            ));
+        };
+        if language == SqlLanguage::Sql
+            && object.len() == 1
+            && let Some(Value::String(cursor)) = object.get("cursor")
+        {
+            return Ok(SqlRequest::Cursor(cursor.clone()));
+        }
+
+        let allowed = match language {
+            SqlLanguage::Sql => SQL_KEYS,
+            SqlLanguage::Ppl => PPL_KEYS,
+        };
+        if let Some(key) = object.keys().find(|key| !allowed.contains(&key.as_str())) {
+            return Err(SqlRejection::Invalid(format!(
+                "unknown key [{}] in request",
+                key
+            )));
+        }
+        let Some(Value::String(query)) = object.get("query") else {
+            return Err(SqlRejection::Invalid("[query] is missing".to_string()));
+        };
+
+        let tokens = tokenize(query)?;
+        let indices = match language {
+            SqlLanguage::Sql => sql_indices(&tokens)?,
+            SqlLanguage::Ppl => ppl_source(&tokens)?.0,
+        };
+        Ok(SqlRequest::Query {
+            indices,
+            body: Value::Object(object),
+        })
+    }
+
+    /// Adds the security filter to a query body. SQL queries combine it
+    /// with their own `filter`, PPL queries are rewritten.
+    pub fn apply_filter(
+        &self,
+        language: SqlLanguage,
+        mut body: Value,
+        filter: &Value,
+    ) -> Result<Value, SqlRejection> {
+        match language {
+            SqlLanguage::Sql => {
+                body["filter"] = match body.get_mut("filter").map(Value::take) {
+                    None | Some(Value::Null) => filter.clone(),
+                    Some(own) => json!({ "bool": { "filter": [own, filter] } }),
+                };
+            }
+            SqlLanguage::Ppl => {
+                let query = body["query"].as_str().unwrap_or_default();
+                let condition = ppl_condition(filter).ok_or_else(|| {
+                    SqlRejection::Forbidden(
+                        "the security filter cannot be applied to PPL queries".to_string(),
+                    )
+                })?;
+                let (_, pipe) = ppl_source(&tokenize(query)?)?;
+                let (head, tail) = query.split_at(pipe.unwrap_or(query.len()));
+                let rewritten = match tail.trim() {
+                    "" => format!("{} | where {}", head.trim_end(), condition),
+                    tail => format!("{} | where {} {}", head.trim_end(), condition, tail),
+                };
+                body["query"] = Value::String(rewritten);
+            }
+        }
+        Ok(body)
+    }
+
+    /// Remembers the cursor returned by a paginated SQL query, if any.
+    pub fn register_cursor(&self, identity: &Identity, response: &Value) {
+        if let Some(cursor) = response.get("cursor").and_then(Value::as_str) {
+            self.cursors.insert(cursor, identity, CURSOR_RETENTION, ());
+        }
+    }
+
+    /// Checks that a cursor was handed out to the given identity.
+    pub fn authorize_cursor(&self, identity: &Identity, cursor: &str) -> Result<(), SqlRejection> {
+        self.cursors
+            .get(cursor, identity)
+            .ok_or(SqlRejection::UnknownCursor)
+    }
+
+    /// Forgets a closed cursor.
+    pub fn close_cursor(&self, cursor: &str) {
+        self.cursors.remove(cursor);
+    }
+}
+
+/// Splits a query into tokens with their byte offsets. Comments are
+/// rejected, so that the proxy and the plugin read the same query.
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, SqlRejection> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '\'' | '"' | '`' => {
+                let mut content = String::new();
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(SqlRejection::Invalid(
+                                "unterminated quote in query".to_string(),
+                            ));
+                        }
+                        Some((_, '\\')) => {
+                            if let Some((_, escaped)) = chars.next() {
+                                content.push(escaped);
+                            }
+                        }
+                        Some((_, quote)) if quote == c => {
+                            // A doubled quote stands for the quote itself
+                            if chars.next_if(|(_, next)| *next == c).is_none() {
+                                break;
+                            }
+                            content.push(c);
+                        }
+                        Some((_, other)) => content.push(other),
+                    }
+                }
+                tokens.push((offset, Token::Quoted(c, content)));
+            }
+            '-' if chars.peek().is_some_and(|(_, next)| *next == '-') => {
+                return Err(SqlRejection::Invalid(
+                    "comments are not supported".to_string(),
+                ));
+            }
+            '/' if chars.peek().is_some_and(|(_, next)| *next == '*') => {
+                return Err(SqlRejection::Invalid(
+                    "comments are not supported".to_string(),
+                ));
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some((_, next)) = chars.next_if(|(_, next)| is_word_char(*next)) {
+                    word.push(next);
+                }
+                tokens.push((offset, Token::Word(word)));
+            }
+            c => tokens.push((offset, Token::Symbol(c))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Characters of unquoted words, which include those of index names.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '*' | '@' | ':')
+}
+
+fn is_word(token: &Token, word: &str) -> bool {
+    matches!(token, Token::Word(w) if w.eq_ignore_ascii_case(word))
+}
+
+/// Returns the index name of an identifier token.
+fn identifier(token: Option<&Token>) -> Option<String> {
+    match token {
+        Some(Token::Word(word)) => Some(word.clone()),
+        Some(Token::Quoted('`' | '"', name)) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the index of an SQL `SELECT` query, which may only read a
+/// single index expression without joins, set operations or subqueries.
+fn sql_indices(tokens: &[(usize, Token)]) -> Result<Vec<String>, SqlRejection> {
+    let tokens: Vec<&Token> = tokens.iter().map(|(_, token)| token).collect();
+    if !tokens.first().is_some_and(|token| is_word(token, "select")) {
+        return Err(SqlRejection::Forbidden(
+            "only SELECT statements are supported".to_string(),
+        ));
+    }
+
+    let mut from = None;
+    let mut depth = 0usize;
+    for (position, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Word(word) => {
+                let upper = word.to_ascii_uppercase();
+                if FORBIDDEN_SQL_KEYWORDS.contains(&upper.as_str()) {
+                    return Err(SqlRejection::Forbidden(format!(
+                        "[{}] is not supported",
+                        upper
+                    )));
+                }
+                if position > 0 && upper == "SELECT" {
+                    return Err(SqlRejection::Forbidden(
+                        "subqueries are not supported".to_string(),
+                    ));
+                }
+                if depth == 0 && upper == "FROM" && from.is_none() {
+                    from = Some(position);
+                }
+            }
+            Token::Symbol('(') => depth += 1,
+            Token::Symbol(')') => depth = depth.saturating_sub(1),
+            Token::Symbol(';') if position + 1 < tokens.len() => {
+                return Err(SqlRejection::Invalid(
+                    "multiple statements are not supported".to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    let Some(from) = from else {
+        return Ok(Vec::new());
+    };
+    let index = identifier(tokens.get(from + 1).copied())
+        .ok_or_else(|| SqlRejection::Forbidden("FROM must name a single index".to_string()))?;
+    // An alias may follow the index, but no further sources
+    let mut next = from + 2;
+    if tokens.get(next).is_some_and(|token| is_word(token, "as")) {
+        next += 2;
+    } else if let Some(Token::Word(word)) = tokens.get(next)
+        && !is_clause_keyword(word)
+    {
+        next += 1;
+    }
+    if matches!(tokens.get(next), Some(Token::Symbol(','))) {
+        return Err(SqlRejection::Forbidden(
+            "multiple indices in FROM are not supported".to_string(),
+        ));
+    }
+    Ok(vec![index])
+}
+
+/// Keywords that may follow the index of a `FROM` clause.
+fn is_clause_keyword(word: &str) -> bool {
+    ["WHERE", "GROUP", "HAVING", "ORDER", "LIMIT", "OFFSET"]
+        .iter()
+        .any(|keyword| word.eq_ignore_ascii_case(keyword))
+}
+
+/// Returns the indices of the source command starting a PPL query and the
+/// byte offset of the pipe ending the command, if further commands follow.
+fn ppl_source(tokens: &[(usize, Token)]) -> Result<(Vec<String>, Option<usize>), SqlRejection> {
+    if tokens
+        .iter()
+        .any(|(_, token)| matches!(token, Token::Symbol('[' | ']')))
+    {
+        return Err(SqlRejection::Forbidden(
+            "subsearches are not supported".to_string(),
+        ));
+    }
+    let mut commands = tokens.split(|(_, token)| *token == Token::Symbol('|'));
+    let source = commands.next().unwrap_or_default();
+    for command in commands {
+        if let Some((_, Token::Word(name))) = command.first()
+            && FORBIDDEN_PPL_COMMANDS.contains(&name.to_ascii_lowercase().as_str())
+        {
+            return Err(SqlRejection::Forbidden(format!(
+                "[{}] is not supported",
+                name
+            )));
+        }
+    }
+
+    let mut source_tokens = source.iter().map(|(_, token)| token).peekable();
+    source_tokens.next_if(|token| is_word(token, "search"));
+    if !(source_tokens
+        .next()
+        .is_some_and(|token| is_word(token, "source"))
+        && source_tokens.next() == Some(&Token::Symbol('=')))
+    {
+        return Err(SqlRejection::Forbidden(
+            "PPL queries must start with a source command".to_string(),
+        ));
+    }
+    let mut indices = Vec::new();
+    loop {
+        let index = identifier(source_tokens.next())
+            .ok_or_else(|| SqlRejection::Invalid("source must name an index".to_string()))?;
+        indices.push(index);
+        if source_tokens.next_if_eq(&&Token::Symbol(',')).is_none() {
+            break;
+        }
+    }
+
+    let pipe = tokens.get(source.len()).map(|(offset, _)| *offset);
+    Ok((indices, pipe))
+}
+
+/// Translates a filter into a PPL condition. Only `term`, `terms`,
+/// `range`, `exists` and `bool` filters are supported.
+fn ppl_condition(filter: &Value) -> Option<String> {
+    let object = filter.as_object()?;
+    let (kind, body) = object.iter().next().filter(|_| object.len() == 1)?;
+    match kind.as_str() {
+        "term" => {
+            let (field, value) = single_field(body)?;
+            let value = value.get("value").unwrap_or(value);
+            Some(format!("{} = {}", ppl_field(field)?, ppl_literal(value)?))
+        }
+        "terms" => {
+            let (field, values) = single_field(body)?;
+            let values = values
+                .as_array()
+                .filter(|values| !values.is_empty())?
+                .iter()
+                .map(ppl_literal)
+                .collect::<Option<Vec<_>>>()?;
+            Some(format!("{} in ({})", ppl_field(field)?, values.join(", ")))
+        }
+        "range" => {
+            let (field, bounds) = single_field(body)?;
+            let field = ppl_field(field)?;
+            let conditions = bounds
+                .as_object()?
+                .iter()
+                .map(|(bound, value)| {
+                    let operator = match bound.as_str() {
+                        "gt" => ">",
+                        "gte" => ">=",
+                        "lt" => "<",
+                        "lte" => "<=",
+                        _ => return None,
+                    };
+                    Some(format!("{} {} {}", field, operator, ppl_literal(value)?))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            (!conditions.is_empty()).then(|| conditions.join(" and "))
+        }
+        "exists" => Some(format!(
+            "isnotnull({})",
+            ppl_field(body.get("field")?.as_str()?)?
+        )),
+        "bool" => ppl_bool_condition(body.as_object()?),
+        _ => None,
+    }
+}
+
+fn ppl_bool_condition(body: &Map<String, Value>) -> Option<String> {
+    let clauses = |key: &str| -> Option<Vec<String>> {
+        match body.get(key) {
+            None => Some(Vec::new()),
+            Some(Value::Array(filters)) => filters.iter().map(ppl_condition).collect(),
+            Some(filter) => Some(vec![ppl_condition(filter)?]),
+        }
+    };
+    if body
+        .keys()
+        .any(|key| !["must", "filter", "should", "must_not"].contains(&key.as_str()))
+    {
+        return None;
+    }
+
+    let mut conditions: Vec<String> = clauses("must")?
+        .into_iter()
+        .chain(clauses("filter")?)
+        .map(|condition| format!("({})", condition))
+        .collect();
+    // Without must or filter clauses, one should clause has to match
+    let should = clauses("should")?;
+    if conditions.is_empty() && !should.is_empty() {
+        let alternatives: Vec<String> = should
+            .into_iter()
+            .map(|condition| format!("({})", condition))
+            .collect();
+        conditions.push(format!("({})", alternatives.join(" or ")));
+    }
+    conditions.extend(
+        clauses("must_not")?
+            .into_iter()
+            .map(|condition| format!("not ({})", condition)),
+    );
+    (!conditions.is_empty()).then(|| conditions.join(" and "))
+}
+
+/// Returns the only field of a leaf query body.
+fn single_field(body: &Value) -> Option<(&str, &Value)> {
+    let object = body.as_object()?;
+    let mut fields = object.iter().filter(|(key, _)| *key != "boost");
+    match (fields.next(), fields.next()) {
+        (Some((field, value)), None) => Some((field, value)),
+        _ => None,
+    }
+}
+
+/// Quotes a field for PPL. Keyword sub-fields are referenced by their
+/// parent field, which the plugin maps back to the keyword field.
+fn ppl_field(field: &str) -> Option<String> {
+    let field = field.strip_suffix(".keyword").unwrap_or(field);
+    let valid = !field.is_empty()
+        && field
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '@' | '-'));
+    valid.then(|| format!("`{}`", field))
+}
+
+fn ppl_literal(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) if !text.contains(['\'', '\\']) => Some(format!("'{}'", text)),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> SqlService {
+        SqlService::new(SearchContextRepository::new())
+    }
+
+    fn indices(language: SqlLanguage, query: &str) -> Result<Vec<String>, SqlRejection> {
+        match service().parse_request(language, json!({ "query": query }))? {
+            SqlRequest::Query { indices, .. } => Ok(indices),
+            SqlRequest::Cursor(_) => panic!("expected a query"),
+        }
+    }
+
+    #[test]
+    fn test_sql_indices() {
+        assert_eq!(
+            indices(
+                SqlLanguage::Sql,
+                "SELECT title, EXTRACT(YEAR FROM released) FROM `movies-*` m WHERE m.rating > 5"
+            ),
+            Ok(vec!["movies-*".to_string()])
+        );
+        assert_eq!(indices(SqlLanguage::Sql, "select 1"), Ok(vec![]));
+
+        for query in [
+            "SELECT * FROM movies m JOIN actors a ON m.id = a.movie",
+            "SELECT * FROM movies, actors",
+            "SELECT * FROM movies UNION SELECT * FROM actors",
+            "SELECT * FROM (SELECT * FROM movies) t",
+            "SELECT * FROM movies WHERE id IN (SELECT movie FROM actors)",
+            "DELETE FROM movies",
+            "SHOW TABLES LIKE %",
+        ] {
+            assert!(
+                matches!(
+                    indices(SqlLanguage::Sql, query),
+                    Err(SqlRejection::Forbidden(_))
+                ),
+                "{}",
+                query
+            );
+        }
+        assert!(indices(SqlLanguage::Sql, "SELECT * FROM movies -- x").is_err());
+        assert!(indices(SqlLanguage::Sql, "SELECT * FROM movies; SELECT 1").is_err());
+    }
+
+    #[test]
+    fn test_sql_filter() {
+        let filter = json!({ "term": { "genre.keyword": "Sci-Fi" } });
+        let body = service()
+            .apply_filter(SqlLanguage::Sql, json!({ "query": "SELECT 1" }), &filter)
+            .unwrap();
+        assert_eq!(body["filter"], filter);
+
+        let own = json!({ "range": { "year": { "gte": 2000 } } });
+        let body = service()
+            .apply_filter(
+                SqlLanguage::Sql,
+                json!({ "query": "SELECT 1", "filter": own }),
+                &filter,
+            )
+            .unwrap();
+        assert_eq!(
+            body["filter"],
+            json!({ "bool": { "filter": [own, filter] } })
+        );
+        assert!(
+            service()
+                .parse_request(
+                    SqlLanguage::Sql,
+                    json!({ "query": "SELECT 1", "scroll": "1m" })
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_ppl_filter() {
+        assert_eq!(
+            indices(
+                SqlLanguage::Ppl,
+                "search source=movies, `books` | stats count()"
+            ),
+            Ok(vec!["movies".to_string(), "books".to_string()])
+        );
+        for query in [
+            "source=movies | join left=m right=a actors",
+            "source=movies | lookup actors id",
+            "source=movies | where id in [ source=actors | fields id ]",
+            "describe movies",
+        ] {
+            assert!(indices(SqlLanguage::Ppl, query).is_err(), "{}", query);
+        }
+
+        let filter = json!({ "bool": {
+            "filter": [{ "term": { "genre.keyword": "Sci-Fi" } }],
+            "must_not": { "range": { "year": { "lt": 1990 } } }
+        } });
+        let body = service()
+            .apply_filter(
+                SqlLanguage::Ppl,
+                json!({ "query": "source=movies rating > 5 | stats count() by year" }),
+                &filter,
+            )
+            .unwrap();
+        assert_eq!(
+            body["query"],
+            json!(
+                "source=movies rating > 5 | where (`genre` = 'Sci-Fi') and not (`year` < 1990) | stats count() by year"
+            )
+        );
+        let body = service()
+            .apply_filter(
+                SqlLanguage::Ppl,
+                json!({ "query": "source=movies" }),
+                &filter,
+            )
+            .unwrap();
+        assert_eq!(
+            body["query"],
+            json!("source=movies | where (`genre` = 'Sci-Fi') and not (`year` < 1990)")
+        );
+
+        assert!(matches!(
+            service().apply_filter(
+                SqlLanguage::Ppl,
+                json!({ "query": "source=movies" }),
+                &json!({ "match": { "title": "dune" } })
+            ),
+            Err(SqlRejection::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn test_cursor_is_bound_to_creator() {
+        let service = service();
+        let alice = Identity {
+            user: "alice".to_string(),
+            roles: vec![],
+        };
+        let bob = Identity {
+            user: "bob".to_string(),
+            roles: vec![],
+        };
+        service.register_cursor(&alice, &json!({ "cursor": "d:abc", "datarows": [] }));
+
+        assert_eq!(
+            service.parse_request(SqlLanguage::Sql, json!({ "cursor": "d:abc" })),
+            Ok(SqlRequest::Cursor("d:abc".to_string()))
+        );
+        assert_eq!(service.authorize_cursor(&alice, "d:abc"), Ok(()));
+        assert_eq!(
+            service.authorize_cursor(&bob, "d:abc"),
+            Err(SqlRejection::UnknownCursor)
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+use crate::auth::identity::Identity;
+use crate::repositories::context::SearchContextRepository;
+
+/// How long a started task remains accessible to its creator.
+const TASK_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Error returned for tasks that are unknown or were started by another
+/// identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskMissing(pub String);
+
+impl IntoResponse for TaskMissing {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected access to unknown task '{}'", self.0);
+
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "error": {
+                    "type": "resource_not_found_exception",
+                    "reason": format!("task [{}] isn't running and hasn't stored its results", self.0),
+                },
+                "status": StatusCode::NOT_FOUND.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A service binding asynchronous tasks to the identity that started them.
+///
+/// Operations run with `wait_for_completion=false` answer with a task id,
+/// and the task API would let any client follow or cancel it. Every task
+/// started through the proxy is therefore remembered with its creator for
+/// a day, and only that identity may access it.
+#[derive(Clone)]
+pub struct TaskService {
+    tasks: SearchContextRepository<()>,
+}
+
+impl TaskService {
+    pub fn new(tasks: SearchContextRepository<()>) -> Self {
+        Self { tasks }
+    }
+
+    /// Remembers the task started by a response, if any.
+    pub fn register(&self, identity: &Identity, response: &Value) {
+        if let Some(task_id) = response.get("task").and_then(Value::as_str) {
+            self.tasks.insert(task_id, identity, TASK_RETENTION, ());
+        }
+    }
+
+    /// Checks that a task was started by the given identity.
+    pub fn authorize(&self, identity: &Identity, task_id: &str) -> Result<(), TaskMissing> {
+        self.tasks
+            .get(task_id, identity)
+            .ok_or_else(|| TaskMissing(task_id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(user: &str) -> Identity {
+        Identity {
+            user: user.to_string(),
+            roles: vec!["maintenance".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_task_is_bound_to_creator() {
+        let service = TaskService::new(SearchContextRepository::new());
+        service.register(&identity("alice"), &json!({ "task": "node-1:42" }));
+        service.register(&identity("alice"), &json!({ "deleted": 3 }));
+
+        assert_eq!(service.authorize(&identity("alice"), "node-1:42"), Ok(()));
+        assert_eq!(
+            service.authorize(&identity("bob"), "node-1:42"),
+            Err(TaskMissing("node-1:42".to_string()))
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+/// Keys accepted in a `_terms_enum` body.
+const TERMS_ENUM_KEYS: &[&str] = &[
+    "case_insensitive",
+    "field",
+    "index_filter",
+    "search_after",
+    "size",
+    "string",
+    "timeout",
+];
+
+/// Number of terms returned when the request does not set a size.
+const DEFAULT_SIZE: u64 = 10;
+
+/// Name of the aggregation collecting the terms.
+const TERMS_AGGREGATION: &str = "terms_enum";
+
+/// Error returned for `_terms_enum` bodies that cannot be translated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTermsEnumRequest(pub String);
+
+impl IntoResponse for InvalidTermsEnumRequest {
+    fn into_response(self) -> Response {
+        tracing::warn!("Rejected invalid terms enum request: {}", self.0);
+
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": { "type": "illegal_argument_exception", "reason": self.0 },
+                "status": StatusCode::BAD_REQUEST.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A parsed `_terms_enum` request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermsEnumRequest {
+    field: String,
+    string: String,
+    size: u64,
+    case_insensitive: bool,
+    search_after: Option<String>,
+    index_filter: Option<Value>,
+    timeout: Option<String>,
+}
+
+/// A service answering `_terms_enum` requests with a filtered search.
+///
+/// `_terms_enum` reads terms straight from the index, and its
+/// `index_filter` only skips shards that cannot match rather than
+/// filtering documents, so terms of documents outside the security filter
+/// would be returned. Requests are therefore translated into a `terms`
+/// aggregation, which takes the same path as any other search, and its
+/// buckets are returned in the format of `_terms_enum`.
+#[derive(Clone)]
+pub struct TermsEnumService;
+
+impl TermsEnumService {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn parse_request(&self, body: &Value) -> Result<TermsEnumRequest, InvalidTermsEnumRequest> {
+        let Some(object) = body.as_object() else {
+            return Err(InvalidTermsEnumRequest(
+                "request body must be an object".to_string(),
+            ));
+        };
+        if let Some(key) = object
+            .keys()
+            .find(|key| !TERMS_ENUM_KEYS.contains(&key.as_str()))
+        {
+            return Err(InvalidTermsEnumRequest(format!(
+                "unknown key [{}] in terms enum request",
+                key
+            )));
+        }
+        let text = |key: &str| match object.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(InvalidTermsEnumRequest(format!(
+                "[{}] must be a string",
+                key
+            ))),
+        };
+
+        let field = text("field")?
+            .filter(|field| !field.is_empty())
+            .ok_or_else(|| InvalidTermsEnumRequest("[field] is missing".to_string()))?;
+        let size = match object.get("size") {
+            None => DEFAULT_SIZE,
+            Some(size) => size.as_u64().filter(|size| *size > 0).ok_or_else(|| {
+                InvalidTermsEnumRequest("[size] must be a positive number".to_string())
+            })?,
+        };
+        Ok(TermsEnumRequest {
+            field,
+            string: text("string")?.unwrap_or_default(),
+            size,
+            case_insensitive: object
+                .get("case_insensitive")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            search_after: text("search_after")?,
+            index_filter: object.get("index_filter").cloned(),
+            timeout: text("timeout")?,
+        })
+    }
+
+    /// Builds the search collecting the requested terms. The security
+    /// filter must be applied to it.
+    pub fn search_body(&self, request: &TermsEnumRequest) -> Value {
+        let field = &request.field;
+        let mut filters: Vec<Value> = request.index_filter.iter().cloned().collect();
+        if !request.string.is_empty() {
+            filters.push(json!({ "prefix": { field: {
+                "value": request.string,
+                "case_insensitive": request.case_insensitive,
+            } } }));
+        }
+        if let Some(search_after) = &request.search_after {
+            filters.push(json!({ "range": { field: { "gt": search_after } } }));
+        }
+
+        let mut terms = json!({
+            "field": field,
+            "size": request.size,
+            "order": { "_key": "asc" },
+        });
+        // Documents with several values may contribute terms without the
+        // prefix, which the include pattern drops
+        if !request.string.is_empty() {
+            terms["include"] = Value::String(format!(
+                "{}.*",
+                prefix_pattern(&request.string, request.case_insensitive)
+            ));
+        }
+
+        let mut body = json!({
+            "size": 0,
+            "query": { "bool": { "filter": filters } },
+            "aggs": { TERMS_AGGREGATION: { "terms": terms } },
+        });
+        if let Some(timeout) = &request.timeout {
+            body["timeout"] = Value::String(timeout.clone());
+        }
+        body
+    }
+
+    /// Converts the response of the search into a `_terms_enum` response.
+    pub fn response(&self, request: &TermsEnumRequest, mut result: Value) -> Value {
+        let aggregation = &result["aggregations"][TERMS_AGGREGATION];
+        let buckets = aggregation["buckets"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let terms: Vec<String> = buckets
+            .iter()
+            .filter_map(|bucket| {
+                let key = &bucket["key"];
+                key.as_str()
+                    .map(str::to_string)
+                    .or_else(|| (!key.is_null()).then(|| key.to_string()))
+            })
+            .filter(|term| {
+                request
+                    .search_after
+                    .as_ref()
+                    .is_none_or(|search_after| term > search_after)
+            })
+            .collect();
+        let complete = aggregation["sum_other_doc_count"].as_u64() == Some(0)
+            && result["timed_out"].as_bool() != Some(true);
+
+        json!({
+            "_shards": result["_shards"].take(),
+            "terms": terms,
+            "complete": complete,
+        })
+    }
+}
+
+/// Escapes a prefix for a Lucene regular expression, matching letters in
+/// either case if requested.
+fn prefix_pattern(prefix: &str, case_insensitive: bool) -> String {
+    let mut pattern = String::new();
+    for c in prefix.chars() {
+        let lower: String = c.to_lowercase().collect();
+        let upper: String = c.to_uppercase().collect();
+        if case_insensitive && lower != upper && lower.chars().count() == 1 {
+            pattern.push_str(&format!("[{}{}]", lower, upper));
+        } else if c.is_alphanumeric() {
+            pattern.push(c);
+        } else {
+            pattern.push('\\');
+            pattern.push(c);
+        }
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_body() {
+        let service = TermsEnumService::new();
+        let request = service
+            .parse_request(&json!({
+                "field": "genre.keyword",
+                "string": "sci-f",
+                "case_insensitive": true,
+                "index_filter": { "range": { "year": { "gte": 2000 } } }
+            }))
+            .unwrap();
+
+        assert_eq!(
+            service.search_body(&request),
+            json!({
+                "size": 0,
+                "query": { "bool": { "filter": [
+                    { "range": { "year": { "gte": 2000 } } },
+                    { "prefix": { "genre.keyword": { "value": "sci-f", "case_insensitive": true } } }
+                ] } },
+                "aggs": { "terms_enum": { "terms": {
+                    "field": "genre.keyword",
+                    "size": 10,
+                    "order": { "_key": "asc" },
+                    "include": "[sS][cC][iI]\\-[fF].*"
+                } } }
+            })
+        );
+
+        assert!(service.parse_request(&json!({ "string": "a" })).is_err());
+        assert!(
+            service
+                .parse_request(&json!({ "field": "genre", "query": {} }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_response() {
+        let service = TermsEnumService::new();
+        let request = service
+            .parse_request(&json!({ "field": "genre", "search_after": "Drama" }))
+            .unwrap();
+        let result = json!({
+            "timed_out": false,
+            "_shards": { "total": 1, "successful": 1, "failed": 0 },
+            "aggregations": { "terms_enum": {
+                "sum_other_doc_count": 0,
+                "buckets": [{ "key": "Action" }, { "key": "Sci-Fi" }]
+            } }
+        });
+
+        assert_eq!(
+            service.response(&request, result),
+            json!({
+                "_shards": { "total": 1, "successful": 1, "failed": 0 },
+                "terms": ["Sci-Fi"],
+                "complete": true
+            })
+        );
+    }
+}
@@ -1,3 +1,4 @@
+mod auth;
 mod body;
 mod config;
 mod handlers;
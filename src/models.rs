@@ -1 +1,3 @@
 pub mod health;
+pub mod named_query;
+pub mod policy;
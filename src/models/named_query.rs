@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Catalogue of the named queries offered by the proxy, keyed by name.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NamedQueryCatalogue {
+    #[serde(default)]
+    pub queries: BTreeMap<String, NamedQuery>,
+}
+
+/// A query run by name with bound parameters instead of client DSL.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NamedQuery {
+    /// Index expression the query searches
+    pub index: String,
+    /// Typed parameters of the query, keyed by name
+    #[serde(default)]
+    pub params: BTreeMap<String, QueryParam>,
+    /// Search body in which `{{name}}` placeholders are replaced by the
+    /// values of the parameters
+    pub body: Value,
+}
+
+/// A parameter of a named query.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct QueryParam {
+    #[serde(flatten)]
+    pub kind: QueryParamKind,
+    /// Value used when the caller omits the parameter. Parameters without
+    /// a default are required.
+    #[serde(default)]
+    pub default: Option<Value>,
+}
+
+/// The accepted values of a parameter.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueryParamKind {
+    String {
+        #[serde(default)]
+        max_length: Option<usize>,
+    },
+    Number {
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+    /// An ISO 8601 date or date time, or date math relative to `now`
+    Date,
+    Enum {
+        values: Vec<String>,
+    },
+    /// A list of values of another kind
+    List {
+        items: Box<QueryParamKind>,
+        #[serde(default)]
+        max_items: Option<usize>,
+    },
+}
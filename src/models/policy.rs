@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::auth::identity::Identity;
+
+/// Access policy of the proxy, keyed by role name.
+///
+/// A caller is granted the combined permissions of every role it holds that
+/// is defined here. Roles unknown to the policy grant nothing.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    pub roles: HashMap<String, RolePolicy>,
+}
+
+impl Policy {
+    /// Returns the role policies that apply to the given identity.
+    pub fn roles_for(&self, identity: &Identity) -> Vec<&RolePolicy> {
+        identity
+            .roles
+            .iter()
+            .filter_map(|role| self.roles.get(role))
+            .collect()
+    }
+}
+
+/// Permissions granted by a single role.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RolePolicy {
+    /// Indices the role may access
+    #[serde(default)]
+    pub indices: IndexPermissions,
+    /// Field level security. `None` grants access to every field.
+    #[serde(default)]
+    pub field_security: Option<FieldSecurity>,
+    /// Masking applied to readable fields in search responses
+    #[serde(default)]
+    pub masking: Vec<MaskingRule>,
+    /// Field patterns whose values are replaced by pseudonyms
+    #[serde(default)]
+    pub pseudonymize: Vec<String>,
+    /// Access to debugging output of searches
+    #[serde(default)]
+    pub debug: DebugPolicy,
+    /// Search pipeline forced onto every search of the role
+    #[serde(default)]
+    pub search_pipeline: Option<String>,
+    /// Longest keep-alive of scrolls and points in time, e.g. `10m`
+    #[serde(default)]
+    pub max_keep_alive: Option<String>,
+    /// Ids of the stored search templates the role may run
+    #[serde(default)]
+    pub search_templates: Vec<String>,
+    /// Names of the named queries the role may run
+    #[serde(default)]
+    pub named_queries: Vec<String>,
+    /// Fields written documents must carry, such as the owning tenant
+    #[serde(default)]
+    pub owner_fields: Vec<OwnerField>,
+    /// Cluster level permissions of the role
+    #[serde(default)]
+    pub cluster: Vec<ClusterPermission>,
+}
+
+/// A permission on cluster level APIs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterPermission {
+    /// Full cluster health, including index and shard level detail
+    Monitor,
+}
+
+/// Index patterns a role may access.
+///
+/// Patterns may contain `*` wildcards and are matched against the index
+/// names, aliases and patterns of a request.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IndexPermissions {
+    /// Indices the role may read. `None` allows every index.
+    #[serde(default)]
+    pub read: Option<Vec<String>>,
+    /// Indices the role may write to. Writes are denied by default.
+    #[serde(default)]
+    pub write: Vec<String>,
+}
+
+/// Field allow and deny lists of a role.
+///
+/// Patterns may contain `*` wildcards and cover all sub-fields of the
+/// matched path, e.g. `user` covers `user.name` and `name` covers
+/// `name.keyword`. Deny patterns take precedence over allow patterns.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FieldSecurity {
+    /// Fields the role may read. `None` allows every field not denied.
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+    /// Fields the role must never read
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// A field identifying the owner of written documents.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OwnerField {
+    /// Dotted path of the field
+    pub field: String,
+    /// Required value. `{{user}}` within strings is replaced by the user
+    /// name of the caller.
+    pub value: serde_json::Value,
+    #[serde(default)]
+    pub mode: OwnerFieldMode,
+}
+
+/// How the owner field of a written document is enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OwnerFieldMode {
+    /// The field is set to the required value, replacing any sent value
+    #[default]
+    Stamp,
+    /// Documents must already carry the required value
+    Validate,
+}
+
+/// Masking of a field in search responses.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MaskingRule {
+    /// Field pattern, with the same matching rules as field security
+    pub field: String,
+    #[serde(flatten)]
+    pub action: MaskingAction,
+}
+
+/// How a masked value is transformed.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaskingAction {
+    /// Replaces the value with a fixed redaction marker
+    Redact,
+    /// Replaces the value with a keyed HMAC-SHA256 of it
+    Hash,
+    /// Keeps only the first and last characters, masking the rest with `*`
+    Truncate {
+        #[serde(default)]
+        keep_first: usize,
+        #[serde(default)]
+        keep_last: usize,
+    },
+    /// Replaces the value with a configured one
+    Replace { value: serde_json::Value },
+}
+
+/// Handling of the debugging flags of a search request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct DebugPolicy {
+    #[serde(default)]
+    pub explain: DebugMode,
+    #[serde(default)]
+    pub profile: DebugMode,
+    #[serde(default)]
+    pub track_scores: DebugMode,
+}
+
+/// How a debugging flag is handled. Ordered from most to least restrictive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DebugMode {
+    /// Requests setting the flag are rejected
+    Deny,
+    /// The flag is accepted, but output contributed by the security filter
+    /// is removed. `track_scores` is dropped from the request instead.
+    #[default]
+    Strip,
+    /// The flag and its output are passed through unchanged
+    Allow,
+}
@@ -1,2 +1,6 @@
+pub mod context;
 pub mod filter;
+pub mod named_query;
 pub mod opensearch;
+pub mod policy;
+pub mod pseudonym;
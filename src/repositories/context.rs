@@ -0,0 +1,164 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::auth::identity::Identity;
+
+/// Maximum number of contexts remembered before the oldest are evicted.
+const MAX_ENTRIES: usize = 10_000;
+
+/// A repository remembering which identity created each server side search
+/// context, such as a scroll.
+///
+/// Entries expire with the keep-alive of their context and are kept in
+/// memory, evicting expired and then the oldest entries once the store is
+/// full. A context that is unknown, expired or owned by another identity
+/// can not be retrieved.
+#[derive(Clone)]
+pub struct SearchContextRepository<T> {
+    inner: Arc<Mutex<ContextStore<T>>>,
+}
+
+struct ContextStore<T> {
+    contexts: HashMap<String, SearchContext<T>>,
+    insertion_order: VecDeque<String>,
+    capacity: usize,
+}
+
+struct SearchContext<T> {
+    owner: Identity,
+    expires_at: Instant,
+    value: T,
+}
+
+impl<T: Clone> SearchContextRepository<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(MAX_ENTRIES)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ContextStore {
+                contexts: HashMap::new(),
+                insertion_order: VecDeque::new(),
+                capacity,
+            })),
+        }
+    }
+
+    /// Remembers a context created by the given identity.
+    pub fn insert(&self, id: &str, owner: &Identity, keep_alive: Duration, value: T) {
+        let mut store = self.inner.lock().expect("Context store lock poisoned");
+        let now = Instant::now();
+        let ContextStore {
+            contexts,
+            insertion_order,
+            capacity,
+        } = &mut *store;
+
+        // Ids of removed contexts are dropped from the order lazily
+        if contexts.len() >= *capacity || insertion_order.len() >= 2 * *capacity {
+            contexts.retain(|_, context| context.expires_at > now);
+            insertion_order.retain(|id| contexts.contains_key(id));
+        }
+        while contexts.len() >= *capacity {
+            match insertion_order.pop_front() {
+                Some(oldest) => contexts.remove(&oldest),
+                None => break,
+            };
+        }
+
+        let context = SearchContext {
+            owner: owner.clone(),
+            expires_at: now + keep_alive,
+            value,
+        };
+        if contexts.insert(id.to_string(), context).is_none() {
+            insertion_order.push_back(id.to_string());
+        }
+    }
+
+    /// Returns the value of a live context owned by the given identity.
+    pub fn get(&self, id: &str, owner: &Identity) -> Option<T> {
+        let store = self.inner.lock().expect("Context store lock poisoned");
+        store
+            .contexts
+            .get(id)
+            .filter(|context| context.owner == *owner && context.expires_at > Instant::now())
+            .map(|context| context.value.clone())
+    }
+
+    /// Extends the expiry of a context.
+    pub fn extend(&self, id: &str, keep_alive: Duration) {
+        let mut store = self.inner.lock().expect("Context store lock poisoned");
+        if let Some(context) = store.contexts.get_mut(id) {
+            context.expires_at = Instant::now() + keep_alive;
+        }
+    }
+
+    /// Forgets a context.
+    pub fn remove(&self, id: &str) {
+        let mut store = self.inner.lock().expect("Context store lock poisoned");
+        store.contexts.remove(id);
+    }
+
+    /// Returns the ids of every live context owned by the given identity.
+    pub fn ids_of(&self, owner: &Identity) -> Vec<String> {
+        let store = self.inner.lock().expect("Context store lock poisoned");
+        let now = Instant::now();
+        store
+            .contexts
+            .iter()
+            .filter(|(_, context)| context.owner == *owner && context.expires_at > now)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(user: &str) -> Identity {
+        Identity {
+            user: user.to_string(),
+            roles: vec!["reader".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_contexts_are_bound_to_owner() {
+        let repository = SearchContextRepository::new();
+        repository.insert("ctx", &identity("alice"), Duration::from_secs(60), 1);
+
+        assert_eq!(repository.get("ctx", &identity("alice")), Some(1));
+        assert_eq!(repository.get("ctx", &identity("bob")), None);
+        assert_eq!(repository.ids_of(&identity("alice")), vec!["ctx"]);
+
+        repository.remove("ctx");
+        assert_eq!(repository.get("ctx", &identity("alice")), None);
+    }
+
+    #[test]
+    fn test_expired_contexts_are_unavailable() {
+        let repository = SearchContextRepository::new();
+        repository.insert("ctx", &identity("alice"), Duration::ZERO, ());
+
+        assert_eq!(repository.get("ctx", &identity("alice")), None);
+        assert!(repository.ids_of(&identity("alice")).is_empty());
+    }
+
+    #[test]
+    fn test_evicts_oldest_contexts() {
+        let repository = SearchContextRepository::with_capacity(2);
+        let alice = identity("alice");
+        let keep_alive = Duration::from_secs(60);
+        repository.insert("a", &alice, keep_alive, ());
+        repository.insert("b", &alice, keep_alive, ());
+        repository.insert("c", &alice, keep_alive, ());
+
+        assert_eq!(repository.get("a", &alice), None);
+        assert_eq!(repository.get("b", &alice), Some(()));
+        assert_eq!(repository.get("c", &alice), Some(()));
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::models::named_query::{NamedQuery, NamedQueryCatalogue};
+
+/// A repository providing the catalogue of named queries.
+///
+/// The catalogue is loaded once at startup from the JSON file referenced
+/// by `QUERIES_FILE`. Without a file no named queries are offered.
+#[derive(Clone)]
+pub struct NamedQueryRepository {
+    catalogue: Arc<NamedQueryCatalogue>,
+}
+
+impl NamedQueryRepository {
+    pub fn new(config: &Config) -> Self {
+        let catalogue = match &config.queries_file {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .unwrap_or_else(|e| panic!("Failed to read queries file '{}': {}", path, e));
+                serde_json::from_str(&contents)
+                    .unwrap_or_else(|e| panic!("Failed to parse queries file '{}': {}", path, e))
+            }
+            None => NamedQueryCatalogue::default(),
+        };
+
+        Self::from_catalogue(catalogue)
+    }
+
+    pub fn from_catalogue(catalogue: NamedQueryCatalogue) -> Self {
+        Self {
+            catalogue: Arc::new(catalogue),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NamedQuery> {
+        self.catalogue.queries.get(name)
+    }
+}
@@ -1,12 +1,112 @@
-use std::error::Error;
+use std::fmt;
 
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 use bytes::Bytes;
+use opensearch::cat::{CatAliasesParts, CatIndicesParts};
 use opensearch::cluster::ClusterHealthParts;
-use opensearch::{MsearchParts, OpenSearch, SearchParts, http::transport::Transport};
-use serde_json::Value;
+use opensearch::http::response::Response as OpenSearchResponse;
+use opensearch::http::{
+    Method,
+    headers::{CONTENT_TYPE, HeaderMap, HeaderValue},
+    request::JsonBody,
+};
+use opensearch::indices::{IndicesGetMappingParts, IndicesResolveIndexParts};
+use opensearch::{
+    BulkParts, CountParts, CreatePitParts, DeleteByQueryParts, FieldCapsParts, GetScriptParts,
+    MsearchParts, OpenSearch, SearchParts, UpdateByQueryParts, http::transport::Transport,
+};
+use serde_json::{Value, json};
 
 use crate::config::Config;
 
+/// Errors returned by the OpenSearch repository.
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// OpenSearch could not be reached or the connection failed
+    Transport(String),
+    /// OpenSearch did not answer in time
+    Timeout,
+    /// OpenSearch answered with a 4xx or 5xx status and the given body
+    Upstream { status: StatusCode, body: Value },
+    /// The response of OpenSearch could not be decoded
+    Decode(String),
+}
+
+impl RepositoryError {
+    /// The HTTP status returned to clients for this error.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Transport(_) | Self::Decode(_) => StatusCode::BAD_GATEWAY,
+            Self::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            Self::Upstream { status, .. } => *status,
+        }
+    }
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(message) => write!(f, "Transport error: {}", message),
+            Self::Timeout => write!(f, "Request to OpenSearch timed out"),
+            Self::Upstream { status, body } => {
+                write!(f, "OpenSearch returned {}: {}", status, body)
+            }
+            Self::Decode(message) => write!(f, "Invalid response from OpenSearch: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<opensearch::Error> for RepositoryError {
+    fn from(error: opensearch::Error) -> Self {
+        if error.is_timeout() {
+            Self::Timeout
+        } else if error.is_json() {
+            Self::Decode(error.to_string())
+        } else {
+            Self::Transport(error.to_string())
+        }
+    }
+}
+
+impl IntoResponse for RepositoryError {
+    fn into_response(self) -> Response {
+        tracing::error!("OpenSearch request failed: {}", self);
+
+        let status = self.status();
+        let (error_type, reason) = match self {
+            Self::Upstream { body, .. } => return (status, Json(body)).into_response(),
+            Self::Transport(_) => ("transport_exception", "failed to reach OpenSearch"),
+            Self::Timeout => ("timeout_exception", "the request to OpenSearch timed out"),
+            Self::Decode(_) => (
+                "parse_exception",
+                "failed to decode the OpenSearch response",
+            ),
+        };
+        (
+            status,
+            Json(json!({
+                "error": { "type": error_type, "reason": reason },
+                "status": status.as_u16(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// A response of OpenSearch passed through without decoding.
+#[derive(Debug)]
+pub struct ForwardedResponse {
+    pub status: StatusCode,
+    pub content_type: Option<String>,
+    pub body: Bytes,
+}
+
 #[derive(Clone)]
 pub struct OpenSearchRepository {
     client: OpenSearch,
@@ -21,47 +121,532 @@ impl OpenSearchRepository {
         Self { client }
     }
 
-    pub async fn cluster_health(&self) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    /// Returns the health of the cluster, or of the given indices.
+    pub async fn cluster_health(
+        &self,
+        index: Option<&str>,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let path = match index {
+            Some(index) => ClusterHealthParts::Index(&[index]).url(),
+            None => ClusterHealthParts::None.url(),
+        };
+        self.get(&path, params).await
+    }
+
+    /// Returns the root info of the cluster, including its version.
+    pub async fn root_info(&self) -> Result<Value, RepositoryError> {
+        self.get("/", &[]).await
+    }
+
+    /// Checks that OpenSearch is reachable and answers successfully.
+    pub async fn ping(&self) -> Result<(), RepositoryError> {
         let response = self
             .client
-            .cluster()
-            .health(ClusterHealthParts::None)
-            .send()
+            .send::<(), ()>(Method::Head, "/", HeaderMap::new(), None, None, None)
             .await?;
-        let response_body = response.json::<Value>().await?;
-        Ok(response_body)
+        let status = StatusCode::from_u16(response.status_code().as_u16())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        if status.is_client_error() || status.is_server_error() {
+            return Err(RepositoryError::Upstream {
+                status,
+                body: json!({
+                    "error": { "type": "exception", "reason": "ping failed" },
+                    "status": status.as_u16(),
+                }),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the HTTP info of every node.
+    pub async fn nodes_http(&self) -> Result<Value, RepositoryError> {
+        self.get("/_nodes/http", &[]).await
     }
 
     pub async fn search(
         &self,
         index: &str,
         payload: Value,
-    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send(
+                Method::Post,
+                &SearchParts::Index(&[index]).url(),
+                HeaderMap::new(),
+                Some(params),
+                Some(JsonBody::new(payload)),
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    pub async fn update_by_query(
+        &self,
+        index: &str,
+        payload: Value,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let path = UpdateByQueryParts::Index(&[index]).url();
+        self.post(&path, payload, params).await
+    }
+
+    pub async fn delete_by_query(
+        &self,
+        index: &str,
+        payload: Value,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let path = DeleteByQueryParts::Index(&[index]).url();
+        self.post(&path, payload, params).await
+    }
+
+    /// Returns the status of a task. The id must come from OpenSearch, as
+    /// it is not encoded.
+    pub async fn get_task(
+        &self,
+        task_id: &str,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send::<(), _>(
+                Method::Get,
+                &format!("/_tasks/{}", task_id),
+                HeaderMap::new(),
+                Some(params),
+                None,
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    /// Cancels a task. The id must come from OpenSearch, as it is not
+    /// encoded.
+    pub async fn cancel_task(&self, task_id: &str) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send::<(), ()>(
+                Method::Post,
+                &format!("/_tasks/{}/_cancel", task_id),
+                HeaderMap::new(),
+                None,
+                None,
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    pub async fn submit_async_search(
+        &self,
+        payload: Value,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        self.post("/_plugins/_asynchronous_search", payload, params)
+            .await
+    }
+
+    /// Returns the state and results of an asynchronous search. The id
+    /// must come from OpenSearch, as it is not encoded.
+    pub async fn get_async_search(
+        &self,
+        id: &str,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send::<(), _>(
+                Method::Get,
+                &format!("/_plugins/_asynchronous_search/{}", id),
+                HeaderMap::new(),
+                Some(params),
+                None,
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    /// Deletes an asynchronous search. The id must come from OpenSearch, as
+    /// it is not encoded.
+    pub async fn delete_async_search(&self, id: &str) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send::<(), ()>(
+                Method::Delete,
+                &format!("/_plugins/_asynchronous_search/{}", id),
+                HeaderMap::new(),
+                None,
+                None,
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    pub async fn sql(
+        &self,
+        payload: Value,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        self.post("/_plugins/_sql", payload, params).await
+    }
+
+    pub async fn ppl(
+        &self,
+        payload: Value,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        self.post("/_plugins/_ppl", payload, params).await
+    }
+
+    pub async fn close_sql_cursor(&self, payload: Value) -> Result<Value, RepositoryError> {
+        self.post("/_plugins/_sql/close", payload, &[]).await
+    }
+
+    async fn post(
+        &self,
+        path: &str,
+        payload: Value,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send(
+                Method::Post,
+                path,
+                HeaderMap::new(),
+                Some(params),
+                Some(JsonBody::new(payload)),
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    async fn get(&self, path: &str, params: &[(String, String)]) -> Result<Value, RepositoryError> {
         let response = self
             .client
-            .search(SearchParts::Index(&[index]))
-            .body(payload)
+            .send::<(), _>(
+                Method::Get,
+                path,
+                HeaderMap::new(),
+                Some(params),
+                None,
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    /// Sends a request unchanged and returns the response of OpenSearch as
+    /// is, whatever its status. The path must be percent-encoded.
+    pub async fn forward(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(String, String)],
+        content_type: Option<&str>,
+        body: Bytes,
+    ) -> Result<ForwardedResponse, RepositoryError> {
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = content_type.and_then(|value| HeaderValue::from_str(value).ok())
+        {
+            headers.insert(CONTENT_TYPE, content_type);
+        }
+        let body = (!body.is_empty()).then_some(body);
+        let response = self
+            .client
+            .send(method, path, headers, Some(params), body, None)
+            .await?;
+
+        let status = StatusCode::from_u16(response.status_code().as_u16())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Ok(ForwardedResponse {
+            status,
+            content_type,
+            body: response.bytes().await?,
+        })
+    }
+
+    /// Returns a stored script, such as a search template.
+    pub async fn get_script(&self, id: &str) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .get_script(GetScriptParts::Id(id))
             .send()
             .await?;
+        read_response(response).await
+    }
+
+    /// Runs a search without an index path, as used with points in time.
+    pub async fn search_all(
+        &self,
+        payload: Value,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send(
+                Method::Post,
+                &SearchParts::None.url(),
+                HeaderMap::new(),
+                Some(params),
+                Some(JsonBody::new(payload)),
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    pub async fn create_pit(
+        &self,
+        index: &str,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send::<(), _>(
+                Method::Post,
+                &CreatePitParts::Index(&[index]).url(),
+                HeaderMap::new(),
+                Some(params),
+                None,
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
 
-        let response_body = response.json::<Value>().await?;
-        Ok(response_body)
+    pub async fn delete_pits(&self, payload: Value) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send(
+                Method::Delete,
+                "/_search/point_in_time",
+                HeaderMap::new(),
+                None::<&()>,
+                Some(JsonBody::new(payload)),
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    pub async fn count(
+        &self,
+        index: &str,
+        payload: Value,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send(
+                Method::Post,
+                &CountParts::Index(&[index]).url(),
+                HeaderMap::new(),
+                Some(params),
+                Some(JsonBody::new(payload)),
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    pub async fn field_caps(
+        &self,
+        index: &str,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send::<(), _>(
+                Method::Get,
+                &FieldCapsParts::Index(&[index]).url(),
+                HeaderMap::new(),
+                Some(params),
+                None,
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    /// Returns the mappings of the given indices, or of every index.
+    pub async fn get_mapping(
+        &self,
+        index: Option<&str>,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let path = match index {
+            Some(index) => IndicesGetMappingParts::Index(&[index]).url(),
+            None => IndicesGetMappingParts::None.url(),
+        };
+        self.get(&path, params).await
+    }
+
+    /// Lists indices. The parameters must request the JSON format.
+    pub async fn cat_indices(
+        &self,
+        index: Option<&str>,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let path = match index {
+            Some(index) => CatIndicesParts::Index(&[index]).url(),
+            None => CatIndicesParts::None.url(),
+        };
+        self.get(&path, params).await
+    }
+
+    /// Lists aliases. The parameters must request the JSON format.
+    pub async fn cat_aliases(
+        &self,
+        name: Option<&str>,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let path = match name {
+            Some(name) => CatAliasesParts::Name(&[name]).url(),
+            None => CatAliasesParts::None.url(),
+        };
+        self.get(&path, params).await
+    }
+
+    pub async fn resolve_index(
+        &self,
+        name: &str,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        self.get(&IndicesResolveIndexParts::Name(&[name]).url(), params)
+            .await
+    }
+
+    pub async fn scroll(&self, payload: Value) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send(
+                Method::Post,
+                "/_search/scroll",
+                HeaderMap::new(),
+                None::<&()>,
+                Some(JsonBody::new(payload)),
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    pub async fn clear_scroll(&self, payload: Value) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send(
+                Method::Delete,
+                "/_search/scroll",
+                HeaderMap::new(),
+                None::<&()>,
+                Some(JsonBody::new(payload)),
+                None,
+            )
+            .await?;
+        read_response(response).await
+    }
+
+    /// Sends a bulk body. Every action must name its index, as the request
+    /// is sent without one.
+    pub async fn bulk(
+        &self,
+        ndjson_body: Bytes,
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
+        let response = self
+            .client
+            .send(
+                Method::Post,
+                &BulkParts::None.url(),
+                HeaderMap::new(),
+                Some(params),
+                Some(ndjson_body),
+                None,
+            )
+            .await?;
+        read_response(response).await
     }
 
     pub async fn msearch(
         &self,
         index: &str,
         ndjson_body: Bytes,
-    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
-        let body_vec = ndjson_body.to_vec();
+        params: &[(String, String)],
+    ) -> Result<Value, RepositoryError> {
         let response = self
             .client
-            .msearch(MsearchParts::Index(&[index]))
-            .body(vec![body_vec.as_slice()])
-            .send()
+            .send(
+                Method::Post,
+                &MsearchParts::Index(&[index]).url(),
+                HeaderMap::new(),
+                Some(params),
+                Some(ndjson_body),
+                None,
+            )
             .await?;
+        read_response(response).await
+    }
+}
+
+/// Decodes the body of an OpenSearch response, turning 4xx and 5xx statuses
+/// into [`RepositoryError::Upstream`].
+async fn read_response(response: OpenSearchResponse) -> Result<Value, RepositoryError> {
+    let status = StatusCode::from_u16(response.status_code().as_u16())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let bytes = response.bytes().await?;
+
+    if status.is_client_error() || status.is_server_error() {
+        // Proxies in front of OpenSearch may answer with plain text or HTML
+        let body = serde_json::from_slice(&bytes).unwrap_or_else(|_| {
+            json!({
+                "error": {
+                    "type": "exception",
+                    "reason": String::from_utf8_lossy(&bytes),
+                },
+                "status": status.as_u16(),
+            })
+        });
+        return Err(RepositoryError::Upstream { status, body });
+    }
+
+    serde_json::from_slice(&bytes).map_err(|e| RepositoryError::Decode(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let response_body = response.json::<Value>().await?;
-        Ok(response_body)
+    #[test]
+    fn test_error_status() {
+        assert_eq!(
+            RepositoryError::Transport("connection refused".to_string()).status(),
+            StatusCode::BAD_GATEWAY
+        );
+        assert_eq!(
+            RepositoryError::Timeout.status(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            RepositoryError::Upstream {
+                status: StatusCode::NOT_FOUND,
+                body: json!({ "error": { "type": "index_not_found_exception" }, "status": 404 }),
+            }
+            .status(),
+            StatusCode::NOT_FOUND
+        );
     }
 }
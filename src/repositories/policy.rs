@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::models::policy::Policy;
+
+/// A repository providing the access policy of the proxy.
+///
+/// The policy is loaded once at startup from the JSON file referenced by
+/// `POLICY_FILE`. When no file is configured, a hardcoded demo policy is
+/// used instead; replace it with your own source if the policy should come
+/// from another API or a database.
+#[derive(Clone)]
+pub struct PolicyRepository {
+    policy: Arc<Policy>,
+}
+
+impl PolicyRepository {
+    pub fn new(config: &Config) -> Self {
+        let policy = match &config.policy_file {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .unwrap_or_else(|e| panic!("Failed to read policy file '{}': {}", path, e));
+                serde_json::from_str(&contents)
+                    .unwrap_or_else(|e| panic!("Failed to parse policy file '{}': {}", path, e))
+            }
+            None => Self::demo_policy(),
+        };
+
+        Self {
+            policy: Arc::new(policy),
+        }
+    }
+
+    pub fn get_policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Demo policy granting the `reader` role every field and the cluster
+    /// health, and hiding the `rating` field from the `guest` role.
+    fn demo_policy() -> Policy {
+        serde_json::from_value(serde_json::json!({
+            "roles": {
+                "reader": { "cluster": ["monitor"] },
+                "guest": { "field_security": { "deny": ["rating"] } }
+            }
+        }))
+        .expect("Demo policy must be valid")
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+/// Maximum number of pseudonyms remembered before the oldest are evicted.
+const MAX_ENTRIES: usize = 100_000;
+
+/// A repository remembering which real value each handed out pseudonym
+/// stands for.
+///
+/// Entries are kept in memory and evicted oldest first once the store is
+/// full. A pseudonym that was evicted, never handed out or handed out
+/// before a restart of the proxy cannot be translated back, so queries
+/// using it silently match nothing until a response hands it out again.
+#[derive(Clone)]
+pub struct PseudonymRepository {
+    inner: Arc<Mutex<PseudonymStore>>,
+}
+
+#[derive(Default)]
+struct PseudonymStore {
+    values: HashMap<String, Value>,
+    insertion_order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl PseudonymRepository {
+    pub fn new() -> Self {
+        Self::with_capacity(MAX_ENTRIES)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(PseudonymStore {
+                capacity,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Remembers the real value behind a pseudonym.
+    pub fn insert(&self, pseudonym: &str, value: &Value) {
+        let mut store = self.inner.lock().expect("Pseudonym store lock poisoned");
+        if store.values.contains_key(pseudonym) {
+            return;
+        }
+        while store.values.len() >= store.capacity {
+            match store.insertion_order.pop_front() {
+                Some(oldest) => store.values.remove(&oldest),
+                None => break,
+            };
+        }
+        store.values.insert(pseudonym.to_string(), value.clone());
+        store.insertion_order.push_back(pseudonym.to_string());
+    }
+
+    /// Returns the real value behind a pseudonym, if known.
+    pub fn get(&self, pseudonym: &str) -> Option<Value> {
+        let store = self.inner.lock().expect("Pseudonym store lock poisoned");
+        store.values.get(pseudonym).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_insert_and_get() {
+        let repository = PseudonymRepository::new();
+        repository.insert("pseu_a", &json!("alice"));
+
+        assert_eq!(repository.get("pseu_a"), Some(json!("alice")));
+        assert_eq!(repository.get("pseu_b"), None);
+    }
+
+    #[test]
+    fn test_evicts_oldest_entries() {
+        let repository = PseudonymRepository::with_capacity(2);
+        repository.insert("pseu_a", &json!(1));
+        repository.insert("pseu_b", &json!(2));
+        repository.insert("pseu_c", &json!(3));
+
+        assert_eq!(repository.get("pseu_a"), None);
+        assert_eq!(repository.get("pseu_b"), Some(json!(2)));
+        assert_eq!(repository.get("pseu_c"), Some(json!(3)));
+    }
+}
@@ -1,14 +1,106 @@
-use crate::handlers::opensearch::{handle_cluster_health, handle_msearch, handle_search};
+use crate::handlers::opensearch::{
+    dashboards_mode, handle_bulk, handle_cancel_task, handle_cat_aliases, handle_cat_indices,
+    handle_cat_named_aliases, handle_cat_named_indices, handle_clear_scroll,
+    handle_close_sql_cursor, handle_cluster_health, handle_count, handle_create_document,
+    handle_create_pit, handle_delete_all_pits, handle_delete_async_search, handle_delete_by_query,
+    handle_delete_document, handle_delete_pits, handle_field_caps, handle_get_async_search,
+    handle_get_document, handle_get_source, handle_get_task, handle_index_bulk,
+    handle_index_cluster_health, handle_index_document, handle_index_mapping,
+    handle_index_new_document, handle_mapping, handle_mget, handle_msearch, handle_msearch_all,
+    handle_msearch_template, handle_named_query, handle_nodes_http, handle_ping, handle_pit_search,
+    handle_ppl, handle_resolve_index, handle_root_info, handle_scroll, handle_search,
+    handle_search_template, handle_sql, handle_submit_async_search, handle_terms_enum,
+    handle_update_by_query, handle_update_document,
+};
 use crate::{config::Config, state::OpenSearchRouterState};
 use axum::{
-    Router,
-    routing::{get, post},
+    Router, middleware,
+    routing::{delete, get, post, put},
 };
 
 pub fn create_router(config: &Config) -> Router {
+    let state = OpenSearchRouterState::new(config);
+
     Router::new()
+        .route("/", get(handle_root_info).head(handle_ping))
         .route("/_cluster/health", get(handle_cluster_health))
-        .route("/{index}/_search", post(handle_search))
+        .route("/_cluster/health/{index}", get(handle_index_cluster_health))
+        .route("/_nodes", get(handle_nodes_http))
+        .route("/_nodes/http", get(handle_nodes_http))
+        .route("/_nodes/_all/http", get(handle_nodes_http))
+        .route("/{index}/_search", get(handle_search).post(handle_search))
+        .route("/_search", get(handle_pit_search).post(handle_pit_search))
+        .route("/{index}/_search/point_in_time", post(handle_create_pit))
+        .route("/_search/point_in_time", delete(handle_delete_pits))
+        .route(
+            "/_search/point_in_time/_all",
+            delete(handle_delete_all_pits),
+        )
+        .route(
+            "/_plugins/_asynchronous_search",
+            post(handle_submit_async_search),
+        )
+        .route(
+            "/_plugins/_asynchronous_search/{id}",
+            get(handle_get_async_search).delete(handle_delete_async_search),
+        )
+        .route(
+            "/_search/scroll",
+            get(handle_scroll)
+                .post(handle_scroll)
+                .delete(handle_clear_scroll),
+        )
+        .route(
+            "/{index}/_search/template",
+            get(handle_search_template).post(handle_search_template),
+        )
+        .route("/_msearch", post(handle_msearch_all))
         .route("/{index}/_msearch", post(handle_msearch))
-        .with_state(OpenSearchRouterState::new(config))
+        .route("/{index}/_msearch/template", post(handle_msearch_template))
+        .route("/queries/{name}", post(handle_named_query))
+        .route("/_bulk", post(handle_bulk))
+        .route("/{index}/_bulk", post(handle_index_bulk))
+        .route("/{index}/_update_by_query", post(handle_update_by_query))
+        .route("/{index}/_delete_by_query", post(handle_delete_by_query))
+        .route("/_plugins/_sql", post(handle_sql))
+        .route("/_plugins/_sql/close", post(handle_close_sql_cursor))
+        .route("/_plugins/_ppl", post(handle_ppl))
+        .route("/_tasks/{task_id}", get(handle_get_task))
+        .route("/_tasks/{task_id}/_cancel", post(handle_cancel_task))
+        .route("/{index}/_count", get(handle_count).post(handle_count))
+        .route(
+            "/{index}/_terms_enum",
+            get(handle_terms_enum).post(handle_terms_enum),
+        )
+        .route(
+            "/{index}/_field_caps",
+            get(handle_field_caps).post(handle_field_caps),
+        )
+        .route("/_mapping", get(handle_mapping))
+        .route("/{index}/_mapping", get(handle_index_mapping))
+        .route("/_cat/indices", get(handle_cat_indices))
+        .route("/_cat/indices/{index}", get(handle_cat_named_indices))
+        .route("/_cat/aliases", get(handle_cat_aliases))
+        .route("/_cat/aliases/{alias}", get(handle_cat_named_aliases))
+        .route("/_resolve/index/{name}", get(handle_resolve_index))
+        .route(
+            "/{index}/_doc/{id}",
+            get(handle_get_document)
+                .put(handle_index_document)
+                .post(handle_index_document)
+                .delete(handle_delete_document),
+        )
+        .route("/{index}/_doc", post(handle_index_new_document))
+        .route(
+            "/{index}/_create/{id}",
+            put(handle_create_document).post(handle_create_document),
+        )
+        .route("/{index}/_update/{id}", post(handle_update_document))
+        .route("/{index}/_source/{id}", get(handle_get_source))
+        .route("/{index}/_mget", get(handle_mget).post(handle_mget))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            dashboards_mode,
+        ))
+        .with_state(state)
 }
@@ -1,7 +1,23 @@
 use crate::{
     config::Config,
-    handlers::security_filter::SecurityFilterService,
-    repositories::{filter::FilterRepository, opensearch::OpenSearchRepository},
+    handlers::{
+        async_search::AsyncSearchService, bulk::BulkService, cluster_info::ClusterInfoService,
+        dashboards::DashboardsService, debug_output::DebugOutputService,
+        discovery::DiscoveryService, documents::DocumentService,
+        error_sanitizer::ErrorSanitizerService, field_security::FieldSecurityService,
+        index_authorization::IndexAuthorizationService, masking::MaskingService,
+        named_queries::NamedQueryService, owner_fields::OwnerFieldService,
+        point_in_time::PointInTimeService, pseudonymization::PseudonymizationService,
+        query_inspector::QueryInspectorService, scroll::ScrollService,
+        search_params::SearchParamsService, search_template::SearchTemplateService,
+        security_filter::SecurityFilterService, sql::SqlService, tasks::TaskService,
+        terms_enum::TermsEnumService,
+    },
+    repositories::{
+        context::SearchContextRepository, filter::FilterRepository,
+        named_query::NamedQueryRepository, opensearch::OpenSearchRepository,
+        policy::PolicyRepository, pseudonym::PseudonymRepository,
+    },
 };
 
 /// Shared state for OpenSearch-related routes.
@@ -12,7 +28,30 @@ use crate::{
 pub struct OpenSearchRouterState {
     pub(crate) opensearch_repo: OpenSearchRepository,
     pub(crate) security_filter_service: SecurityFilterService,
+    pub(crate) field_security_service: FieldSecurityService,
+    pub(crate) index_authorization_service: IndexAuthorizationService,
+    pub(crate) query_inspector_service: QueryInspectorService,
+    pub(crate) masking_service: MaskingService,
+    pub(crate) pseudonymization_service: PseudonymizationService,
+    pub(crate) error_sanitizer_service: ErrorSanitizerService,
+    pub(crate) debug_output_service: DebugOutputService,
+    pub(crate) discovery_service: DiscoveryService,
+    pub(crate) search_params_service: SearchParamsService,
+    pub(crate) search_template_service: SearchTemplateService,
+    pub(crate) named_query_service: NamedQueryService,
+    pub(crate) document_service: DocumentService,
+    pub(crate) owner_field_service: OwnerFieldService,
+    pub(crate) bulk_service: BulkService,
+    pub(crate) cluster_info_service: ClusterInfoService,
+    pub(crate) dashboards_service: DashboardsService,
+    pub(crate) scroll_service: ScrollService,
+    pub(crate) point_in_time_service: PointInTimeService,
+    pub(crate) task_service: TaskService,
+    pub(crate) async_search_service: AsyncSearchService,
+    pub(crate) sql_service: SqlService,
+    pub(crate) terms_enum_service: TermsEnumService,
     pub(crate) filter_repository: FilterRepository,
+    pub(crate) policy_repository: PolicyRepository,
 }
 
 impl OpenSearchRouterState {
@@ -20,7 +59,33 @@ impl OpenSearchRouterState {
         Self {
             opensearch_repo: OpenSearchRepository::new(config),
             security_filter_service: SecurityFilterService::new(),
+            field_security_service: FieldSecurityService::new(),
+            index_authorization_service: IndexAuthorizationService::new(),
+            query_inspector_service: QueryInspectorService::new(),
+            masking_service: MaskingService::new(config),
+            pseudonymization_service: PseudonymizationService::new(
+                config,
+                PseudonymRepository::new(),
+            ),
+            error_sanitizer_service: ErrorSanitizerService::new(),
+            debug_output_service: DebugOutputService::new(),
+            discovery_service: DiscoveryService::new(),
+            search_params_service: SearchParamsService::new(),
+            search_template_service: SearchTemplateService::new(),
+            named_query_service: NamedQueryService::new(NamedQueryRepository::new(config)),
+            document_service: DocumentService::new(),
+            owner_field_service: OwnerFieldService::new(),
+            bulk_service: BulkService::new(),
+            cluster_info_service: ClusterInfoService::new(),
+            dashboards_service: DashboardsService::new(config),
+            scroll_service: ScrollService::new(SearchContextRepository::new()),
+            point_in_time_service: PointInTimeService::new(SearchContextRepository::new()),
+            task_service: TaskService::new(SearchContextRepository::new()),
+            async_search_service: AsyncSearchService::new(SearchContextRepository::new()),
+            sql_service: SqlService::new(SearchContextRepository::new()),
+            terms_enum_service: TermsEnumService::new(),
             filter_repository: FilterRepository::new(config),
+            policy_repository: PolicyRepository::new(config),
         }
     }
 }